@@ -6,11 +6,17 @@ use std::path::PathBuf;
 use tracing::Level;
 use tracing_subscriber::FmtSubscriber;
 
-use unidrop_core::{DeviceId, ProtocolId, TransferIntent};
-use unidrop_engine::{Engine, EngineConfig};
+use unidrop_core::{
+    AcceptPolicy, Device, DeviceId, PairingInvite, ProtocolId, TransferIntent, TransferSource,
+    PAIRING_TIMEOUT,
+};
+use unidrop_engine::{Engine, EngineConfig, Transport};
 use unidrop_protocol_localsend::LocalSendFactory;
 use unidrop_protocol_p2p::P2pFactory;
 
+/// 接收方要求 PIN 时最多提示输入的次数
+const PIN_PROMPTS: usize = 3;
+
 #[derive(Parser)]
 #[command(name = "drop")]
 #[command(author, version, about = "UniDrop - Cross-platform file sharing")]
@@ -36,26 +42,52 @@ enum Commands {
     /// List online devices
     Devices,
 
-    /// Send files to a device
+    /// Send files or directories to a device
     Send {
-        /// Files to send
+        /// Files or directories to send (`-` reads from stdin)
         #[arg(required = true)]
         files: Vec<PathBuf>,
 
+        /// Skip files and directories matching this name pattern (repeatable, supports * and ?)
+        #[arg(long)]
+        ignore: Vec<String>,
+
+        /// File name for data read from stdin
+        #[arg(long, default_value = "stdin")]
+        stdin_name: String,
+
         /// Target device (fingerprint, name, or IP:port like 192.168.1.100:53317)
         #[arg(short, long)]
         to: Option<String>,
 
-        /// Use QUIC transport (faster, but only works with UniDrop receivers)
+        /// Force QUIC transport (used automatically when the receiver advertises it)
         #[arg(long)]
         quic: bool,
+
+        /// PIN required by the receiver (prompted for when needed)
+        #[arg(long)]
+        pin: Option<String>,
+    },
+
+    /// Pair with a device so that each trusts the other
+    Pair {
+        /// Code shown on the other device, or its pairing link (omit to show this device's code)
+        code: Option<String>,
+
+        /// Device showing the code (fingerprint or name; not needed with a pairing link)
+        #[arg(short, long)]
+        to: Option<String>,
     },
 
     /// Show registered protocols
     Protocols,
 
     /// Receive mode (wait for incoming transfers)
-    Receive,
+    Receive {
+        /// Require senders to enter this PIN
+        #[arg(long)]
+        pin: Option<String>,
+    },
 }
 
 #[tokio::main]
@@ -72,19 +104,40 @@ async fn main() -> Result<()> {
     tracing::subscriber::set_global_default(subscriber)?;
 
     // 创建 Engine
-    let engine = create_engine(cli.port, cli.name);
+    let ignore = match &cli.command {
+        Commands::Send { ignore, .. } => ignore.clone(),
+        _ => Vec::new(),
+    };
+    let receive_pin = match &cli.command {
+        Commands::Receive { pin } => pin.clone(),
+        _ => None,
+    };
+    let engine = create_engine(cli.port, cli.name, ignore, receive_pin);
 
     match cli.command {
         Commands::Devices => list_devices(&engine).await?,
-        Commands::Send { files, to, quic } => send_files(&engine, files, to, quic).await?,
+        Commands::Send {
+            files,
+            stdin_name,
+            to,
+            quic,
+            pin,
+            ..
+        } => send_files(&engine, files, stdin_name, to, quic, pin).await?,
+        Commands::Pair { code, to } => pair_device(&engine, code, to).await?,
         Commands::Protocols => list_protocols(&engine),
-        Commands::Receive => receive_mode(&engine).await?,
+        Commands::Receive { .. } => receive_mode(&engine).await?,
     }
 
     Ok(())
 }
 
-fn create_engine(port: Option<u16>, name: Option<String>) -> Engine {
+fn create_engine(
+    port: Option<u16>,
+    name: Option<String>,
+    ignore: Vec<String>,
+    pin: Option<String>,
+) -> Engine {
     let device_name = name.unwrap_or_else(|| {
         hostname::get()
             .ok()
@@ -92,7 +145,7 @@ fn create_engine(port: Option<u16>, name: Option<String>) -> Engine {
             .unwrap_or_else(|| "UniDrop-CLI".to_string())
     });
 
-    let config = EngineConfig {
+    let mut config = EngineConfig {
         device_name,
         port: port.unwrap_or(0), // 0 means use default
         save_dir: dirs::download_dir()
@@ -100,8 +153,12 @@ fn create_engine(port: Option<u16>, name: Option<String>) -> Engine {
             .unwrap_or_else(std::env::temp_dir)
             .join("UniDrop"),
         encryption: true,
-        pin: None,
+        pin,
+        // 命令行没有交互确认，自动接收
+        accept_policy: AcceptPolicy::AutoAcceptAll,
+        ..Default::default()
     };
+    config.ignore.extend(ignore);
 
     Engine::builder()
         .config(config)
@@ -141,10 +198,26 @@ async fn list_devices(engine: &Engine) -> Result<()> {
     Ok(())
 }
 
-async fn send_files(engine: &Engine, files: Vec<PathBuf>, to: Option<String>, use_quic: bool) -> Result<()> {
-    // 检查文件是否存在
-    for file in &files {
-        if !file.exists() {
+async fn send_files(
+    engine: &Engine,
+    files: Vec<PathBuf>,
+    stdin_name: String,
+    to: Option<String>,
+    use_quic: bool,
+    pin: Option<String>,
+) -> Result<()> {
+    // 检查文件是否存在，`-` 表示从 stdin 读取
+    let mut sources = Vec::with_capacity(files.len());
+    let mut reads_stdin = false;
+    for file in files {
+        if file.as_os_str() == "-" {
+            // 先暂存到临时文件，输入 PIN 后重试时可以再次发送
+            let source = TransferSource::reader(stdin_name.clone(), tokio::io::stdin(), None);
+            sources.push(source.sized().await?);
+            reads_stdin = true;
+        } else if file.exists() {
+            sources.push(TransferSource::file(file));
+        } else {
             anyhow::bail!("File not found: {:?}", file);
         }
     }
@@ -163,44 +236,60 @@ async fn send_files(engine: &Engine, files: Vec<PathBuf>, to: Option<String>, us
     }
 
     // 选择目标设备
-    let target = match to {
-        Some(ref name) => {
-            devices
-                .iter()
-                .find(|d| {
-                    d.name().to_lowercase().contains(&name.to_lowercase())
-                        || d.id().fingerprint.starts_with(name)
-                })
-                .ok_or_else(|| anyhow::anyhow!("Device not found: {}", name))?
-        }
-        None => {
-            if devices.len() == 1 {
-                &devices[0]
-            } else {
-                println!("Multiple devices found. Please specify target with --to:");
-                for device in &devices {
-                    println!("  {} ({})", device.name(), device.id().fingerprint);
-                }
-                engine.stop().await?;
-                return Ok(());
-            }
-        }
+    let Some(target) = select_device(&devices, to.as_deref())? else {
+        engine.stop().await?;
+        return Ok(());
     };
 
-    let transport = if use_quic { "QUIC" } else { "HTTPS" };
-    println!("Sending {} file(s) to {} via {}...\n", files.len(), target.name(), transport);
+    println!(
+        "Sending {} item(s) to {}...\n",
+        sources.len(),
+        target.name()
+    );
 
-    let intent = TransferIntent::new(target.id().clone(), files);
+    let mut intent = TransferIntent::from_sources(target.id().clone(), sources);
+    if let Some(pin) = pin {
+        intent = intent.with_pin(pin);
+    }
 
-    let result = if use_quic {
-        engine.send_quic(intent).await
-    } else {
-        engine.send(intent).await
+    // 发送过程中显示进度
+    let mut events = engine.subscribe();
+    let progress_printer = tokio::spawn(async move {
+        while let Ok(event) = events.recv().await {
+            if let unidrop_core::EventKind::TransferProgress(progress) = &event.kind {
+                print_progress(progress);
+            }
+        }
+    });
+
+    // 接收方要求 PIN 时提示输入并重试（stdin 已被数据占用时无法输入）
+    let mut prompts = 0;
+    let result = loop {
+        let result = if use_quic {
+            engine
+                .send_quic(intent.clone())
+                .await
+                .map(|session_id| (session_id, Transport::Quic))
+        } else {
+            engine.send_with_transport(intent.clone()).await
+        };
+        match result {
+            Err(unidrop_core::Error::PinRequired(_)) if !reads_stdin && prompts < PIN_PROMPTS => {
+                prompts += 1;
+                intent = intent.with_pin(prompt_pin(target.name())?);
+            }
+            result => break result,
+        }
     };
+    progress_printer.abort();
 
     match result {
-        Ok(session_id) => {
-            println!("Transfer completed successfully!");
+        Ok((session_id, transport)) => {
+            let transport = match transport {
+                Transport::Quic => "QUIC",
+                Transport::Default => "HTTPS",
+            };
+            println!("Transfer completed successfully via {}!", transport);
             println!("Session ID: {}", session_id);
         }
         Err(e) => {
@@ -212,6 +301,110 @@ async fn send_files(engine: &Engine, files: Vec<PathBuf>, to: Option<String>, us
     Ok(())
 }
 
+/// 按名称或指纹前缀选择设备，未指定时只有一台设备才选中
+///
+/// 有多台设备且未指定时列出设备并返回 None。
+fn select_device<'a>(devices: &'a [Device], to: Option<&str>) -> Result<Option<&'a Device>> {
+    match to {
+        Some(name) => devices
+            .iter()
+            .find(|d| {
+                d.name().to_lowercase().contains(&name.to_lowercase())
+                    || d.id().fingerprint.starts_with(name)
+            })
+            .map(Some)
+            .ok_or_else(|| anyhow::anyhow!("Device not found: {}", name)),
+        None if devices.len() == 1 => Ok(Some(&devices[0])),
+        None => {
+            println!("Multiple devices found. Please specify target with --to:");
+            for device in devices {
+                println!("  {} ({})", device.name(), device.id().fingerprint);
+            }
+            Ok(None)
+        }
+    }
+}
+
+/// 从终端读取 PIN
+fn prompt_pin(device: &str) -> Result<String> {
+    use std::io::Write;
+
+    print!("{} requires a PIN: ", device);
+    std::io::stdout().flush()?;
+    let mut line = String::new();
+    std::io::stdin().read_line(&mut line)?;
+    Ok(line.trim().to_string())
+}
+
+async fn pair_device(engine: &Engine, code: Option<String>, to: Option<String>) -> Result<()> {
+    engine.start().await?;
+
+    let result = match code {
+        None => show_pairing_code(engine).await,
+        Some(code) => {
+            let paired = if code.starts_with("unidrop://") {
+                let invite = PairingInvite::parse(&code)?;
+                Some(engine.pair_with_invite(&invite).await)
+            } else {
+                println!("Scanning for devices...\n");
+                tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+                let devices = engine.devices().await;
+                match select_device(&devices, to.as_deref())? {
+                    Some(target) => Some(engine.pair(target.id(), &code).await),
+                    None => None,
+                }
+            };
+            match paired {
+                Some(Ok(device)) => println!("Paired with {}", device.name()),
+                Some(Err(e)) => println!("Pairing failed: {}", e),
+                None => {}
+            }
+            Ok(())
+        }
+    };
+
+    engine.stop().await?;
+    result
+}
+
+/// 显示本机短码和配对链接，等待对端配对
+async fn show_pairing_code(engine: &Engine) -> Result<()> {
+    let protocol = ProtocolId::new(unidrop_protocol_localsend::PROTOCOL_ID);
+    let mut events = engine.subscribe();
+    let invite = engine.start_pairing(&protocol).await?;
+
+    println!("Pairing code: {}", invite.code);
+    println!("Pairing link: {}", invite.to_uri());
+    println!("\nEnter the code or open the link on the other device (Ctrl+C to stop)...");
+
+    let paired = async {
+        loop {
+            match events.recv().await {
+                Ok(event) => {
+                    if let unidrop_core::EventKind::DevicePaired(device) = event.kind {
+                        return Some(device);
+                    }
+                }
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(_) => return None,
+            }
+        }
+    };
+
+    tokio::select! {
+        device = paired => {
+            if let Some(device) = device {
+                println!("Paired with {}", device.name());
+            }
+        }
+        _ = tokio::time::sleep(PAIRING_TIMEOUT) => println!("Pairing code expired."),
+        _ = tokio::signal::ctrl_c() => {}
+    }
+
+    engine.stop_pairing(&protocol).await?;
+    Ok(())
+}
+
 fn list_protocols(engine: &Engine) {
     let protocols = engine.protocols();
 
@@ -237,40 +430,66 @@ async fn receive_mode(engine: &Engine) -> Result<()> {
     println!("UniDrop is now receiving...");
     println!("Press Ctrl+C to stop.\n");
 
-    tokio::spawn(async move {
-        while let Ok(event) = events.recv().await {
-            match &event.kind {
-                unidrop_core::EventKind::DeviceDiscovered(device) => {
-                    println!("Device online: {} ({})", device.name(), device.address());
-                }
-                unidrop_core::EventKind::DeviceLost(id) => {
-                    println!("Device offline: {}", id);
-                }
-                unidrop_core::EventKind::TransferRequested(request) => {
-                    println!(
-                        "\nIncoming transfer from {}: {} files ({} bytes)",
-                        request.from.name(),
-                        request.file_count(),
-                        request.total_size
-                    );
-                    // 自动接受
-                    println!("Auto-accepting...");
-                }
-                unidrop_core::EventKind::TransferCompleted { transfer_id } => {
-                    println!("Transfer completed: {}", transfer_id);
-                }
-                unidrop_core::EventKind::TransferFailed { transfer_id, error } => {
-                    println!("Transfer failed: {} - {}", transfer_id, error);
-                }
-                _ => {}
+    loop {
+        let event = tokio::select! {
+            _ = tokio::signal::ctrl_c() => break,
+            event = events.recv() => match event {
+                Ok(event) => event,
+                Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(_) => break,
+            },
+        };
+
+        match &event.kind {
+            unidrop_core::EventKind::DeviceDiscovered(device) => {
+                println!("Device online: {} ({})", device.name(), device.address());
             }
+            unidrop_core::EventKind::DeviceLost(id) => {
+                println!("Device offline: {}", id);
+            }
+            unidrop_core::EventKind::TransferAccepted(request) => {
+                println!(
+                    "\nIncoming transfer from {}: {} files ({} bytes)",
+                    request.from.name(),
+                    request.file_count(),
+                    request.total_size
+                );
+            }
+            unidrop_core::EventKind::MessageReceived { from, text } => {
+                println!("\nMessage from {}:\n{}", from.name(), text);
+            }
+            unidrop_core::EventKind::TransferProgress(progress) => {
+                print_progress(progress);
+            }
+            unidrop_core::EventKind::TransferCompleted { transfer_id } => {
+                println!("Transfer completed: {}", transfer_id);
+            }
+            unidrop_core::EventKind::TransferFailed { transfer_id, error } => {
+                println!("Transfer failed: {} - {}", transfer_id, error);
+            }
+            _ => {}
         }
-    });
-
-    tokio::signal::ctrl_c().await?;
+    }
 
     println!("\nShutting down...");
     engine.stop().await?;
 
     Ok(())
 }
+
+fn print_progress(progress: &unidrop_core::TransferProgress) {
+    let speed = progress
+        .speed_bps
+        .map(|bps| format!(", {:.1} MB/s", bps as f64 / 1_000_000.0))
+        .unwrap_or_default();
+
+    println!(
+        "  {:5.1}%  {}/{} bytes, {}/{} files{}",
+        progress.progress_percent(),
+        progress.bytes_transferred,
+        progress.bytes_total,
+        progress.files_completed,
+        progress.files_total,
+        speed
+    );
+}
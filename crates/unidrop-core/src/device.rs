@@ -1,8 +1,10 @@
 //! 设备模型 - 协议无关的设备表示
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fmt;
 use std::net::IpAddr;
+use std::time::Duration;
 
 use crate::ProtocolId;
 
@@ -111,6 +113,17 @@ impl Peer {
     }
 }
 
+/// 设备声明支持的可选能力
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Capability {
+    /// UniDrop 之间的 QUIC 传输及其端口
+    Quic { port: u16 },
+}
+
+/// 设备多久未出现后视为可能离线
+pub const DEVICE_TTL: Duration = Duration::from_secs(120);
+
 /// 在线设备 - 包含网络信息的 Peer
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Device {
@@ -123,6 +136,9 @@ pub struct Device {
     pub port: u16,
     /// 最后在线时间戳（Unix 秒）
     pub last_seen: u64,
+    /// 设备声明的可选能力
+    #[serde(default)]
+    pub capabilities: HashSet<Capability>,
 }
 
 impl Device {
@@ -132,9 +148,26 @@ impl Device {
             ip,
             port,
             last_seen: current_timestamp(),
+            capabilities: HashSet::new(),
         }
     }
 
+    /// 添加设备能力
+    pub fn with_capability(mut self, capability: Capability) -> Self {
+        self.capabilities.insert(capability);
+        self
+    }
+
+    /// 设备声明的 QUIC 端口，未声明时不支持 QUIC
+    pub fn quic_port(&self) -> Option<u16> {
+        self.capabilities
+            .iter()
+            .map(|capability| match capability {
+                Capability::Quic { port } => *port,
+            })
+            .next()
+    }
+
     /// 获取设备 ID
     pub fn id(&self) -> &DeviceId {
         &self.peer.id
@@ -159,6 +192,11 @@ impl Device {
     pub fn touch(&mut self) {
         self.last_seen = current_timestamp();
     }
+
+    /// 距最后在线是否已超过 `ttl`
+    pub fn is_expired(&self, ttl: Duration) -> bool {
+        current_timestamp().saturating_sub(self.last_seen) >= ttl.as_secs()
+    }
 }
 
 fn current_timestamp() -> u64 {
@@ -34,6 +34,9 @@ pub enum Error {
     #[error("Transfer failed: {0}")]
     TransferFailed(String),
 
+    #[error("Hash mismatch: {0}")]
+    HashMismatch(String),
+
     #[error("Invalid session: {0}")]
     InvalidSession(String),
 
@@ -47,6 +50,22 @@ pub enum Error {
     #[error("Protocol not supported: {0}")]
     ProtocolNotSupported(String),
 
+    // === 安全错误 ===
+    #[error("Certificate mismatch: {0}")]
+    CertificateMismatch(String),
+
+    /// 对端要求 PIN（未提供或不正确）
+    #[error("PIN required by {0}")]
+    PinRequired(String),
+
+    /// PIN 错误次数过多，对端暂时拒绝请求
+    #[error("Too many wrong PINs for {0}, try again later")]
+    PinLockedOut(String),
+
+    /// 配对短码错误或对端未能证明其知道短码
+    #[error("Pairing failed: {0}")]
+    PairingFailed(String),
+
     // === 文件系统错误 ===
     #[error("File not found: {0}")]
     FileNotFound(String),
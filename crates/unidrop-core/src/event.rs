@@ -12,16 +12,31 @@ pub enum EventKind {
     DeviceLost(DeviceId),
     /// 设备信息更新
     DeviceUpdated(Device),
+    /// 与设备配对成功，双方已互相信任
+    DevicePaired(Device),
 
     // === 传输事件 ===
     /// 收到传输请求
     TransferRequested(TransferRequest),
+    /// 传输请求已按接收策略自动接受
+    TransferAccepted(TransferRequest),
     /// 传输进度更新
     TransferProgress(TransferProgress),
     /// 传输完成
     TransferCompleted { transfer_id: String },
     /// 传输失败
     TransferFailed { transfer_id: String, error: String },
+    /// 收到文本消息（不保存为文件）
+    MessageReceived { from: Device, text: String },
+
+    // === 分享事件 ===
+    /// 分享的文件被下载（`remote` 为下载方地址）
+    ShareFileFetched {
+        share_id: String,
+        file_id: String,
+        file_name: String,
+        remote: String,
+    },
 
     // === 系统事件 ===
     /// 协议已启动
@@ -69,11 +84,30 @@ impl Event {
         Self::new(EventKind::DeviceLost(id)).with_protocol(protocol)
     }
 
+    pub fn device_paired(device: Device) -> Self {
+        let protocol = device.protocol().to_string();
+        Self::new(EventKind::DevicePaired(device)).with_protocol(protocol)
+    }
+
     pub fn transfer_requested(request: TransferRequest) -> Self {
         let protocol = request.from.protocol().to_string();
         Self::new(EventKind::TransferRequested(request)).with_protocol(protocol)
     }
 
+    pub fn transfer_accepted(request: TransferRequest) -> Self {
+        let protocol = request.from.protocol().to_string();
+        Self::new(EventKind::TransferAccepted(request)).with_protocol(protocol)
+    }
+
+    pub fn message_received(from: Device, text: impl Into<String>) -> Self {
+        let protocol = from.protocol().to_string();
+        Self::new(EventKind::MessageReceived {
+            from,
+            text: text.into(),
+        })
+        .with_protocol(protocol)
+    }
+
     pub fn transfer_progress(progress: TransferProgress) -> Self {
         Self::new(EventKind::TransferProgress(progress))
     }
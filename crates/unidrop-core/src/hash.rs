@@ -0,0 +1,67 @@
+//! 文件完整性校验 - SHA-256
+//!
+//! 发送方在元数据中声明每个文件的哈希，接收方边写入边计算，
+//! 完成时比较；未声明哈希的文件不校验。
+
+use sha2::{Digest, Sha256};
+
+use crate::{Error, Result};
+
+/// 流式计算 SHA-256
+#[derive(Debug, Default, Clone)]
+pub struct FileHasher(Sha256);
+
+impl FileHasher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        self.0.update(data);
+    }
+
+    /// 小写十六进制摘要
+    pub fn finish(self) -> String {
+        hex::encode(self.0.finalize())
+    }
+
+    /// 与对端声明的哈希比较，未声明时通过
+    pub fn verify(self, file_name: &str, expected: Option<&str>) -> Result<()> {
+        let Some(expected) = expected else {
+            return Ok(());
+        };
+        let actual = self.finish();
+        if actual.eq_ignore_ascii_case(expected) {
+            Ok(())
+        } else {
+            Err(Error::HashMismatch(file_name.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verify() {
+        let mut hasher = FileHasher::new();
+        hasher.update(b"hello ");
+        hasher.update(b"world");
+        let digest = hasher.clone().finish();
+        assert_eq!(
+            digest,
+            "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9"
+        );
+
+        assert!(hasher.clone().verify("a.txt", None).is_ok());
+        assert!(hasher
+            .clone()
+            .verify("a.txt", Some(&digest.to_uppercase()))
+            .is_ok());
+        assert!(matches!(
+            hasher.verify("a.txt", Some("00")),
+            Err(Error::HashMismatch(_))
+        ));
+    }
+}
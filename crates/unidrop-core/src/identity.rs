@@ -0,0 +1,162 @@
+//! 设备身份持久化
+//!
+//! 证书、私钥等身份材料首次启动时生成，之后从配置目录加载，
+//! 保证设备指纹和 DeviceId 在重启后保持不变。
+//! 具体的生成和解析由各协议负责，这里只负责存取。
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+use crate::Result;
+
+/// 配置目录下存放身份材料的子目录
+pub const IDENTITY_DIR: &str = "identity";
+
+/// 身份存储 - 配置目录下的一组命名文件
+#[derive(Debug, Clone)]
+pub struct IdentityStore {
+    dir: PathBuf,
+}
+
+impl IdentityStore {
+    /// 创建存储，`dir` 不存在时在首次写入时创建
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// 存储目录
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// 读取身份材料，不存在时返回 None
+    pub fn load(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        match fs::read(self.path(name)) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// 保存身份材料（仅当前用户可读）
+    pub fn save(&self, name: &str, data: &[u8]) -> Result<()> {
+        fs::create_dir_all(&self.dir)?;
+
+        let path = self.path(name);
+        let temp_path = self.path(&format!(".{}.tmp", name));
+
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let mut file = options.open(&temp_path)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&temp_path, &path)?;
+
+        Ok(())
+    }
+
+    /// 读取身份材料，不存在时调用 `create` 生成并保存
+    pub fn load_or_create(
+        &self,
+        name: &str,
+        create: impl FnOnce() -> Result<Vec<u8>>,
+    ) -> Result<Vec<u8>> {
+        if let Some(data) = self.load(name)? {
+            return Ok(data);
+        }
+
+        let data = create()?;
+        self.save(name, &data)?;
+        info!("Created identity {:?} in {:?}", name, self.dir);
+        Ok(data)
+    }
+
+    /// 删除指定身份材料，下次启动时重新生成
+    pub fn remove(&self, name: &str) -> Result<()> {
+        match fs::remove_file(self.path(name)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// 删除全部身份材料（轮换身份），重启协议后生效
+    pub fn clear(&self) -> Result<()> {
+        for name in self.names()? {
+            self.remove(&name)?;
+        }
+        info!("Cleared identity store {:?}", self.dir);
+        Ok(())
+    }
+
+    /// 导出全部身份材料到目标目录，返回导出的文件名
+    pub fn export_to(&self, dest: &Path) -> Result<Vec<String>> {
+        let names = self.names()?;
+        let target = IdentityStore::new(dest);
+        for name in &names {
+            if let Some(data) = self.load(name)? {
+                target.save(name, &data)?;
+            }
+        }
+        Ok(names)
+    }
+
+    /// 已保存的身份材料名称
+    pub fn names(&self) -> Result<Vec<String>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut names = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if entry.file_type()?.is_file() && !name.starts_with('.') {
+                names.push(name);
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load_or_create_persists() {
+        let dir = std::env::temp_dir().join(format!("unidrop-identity-{}", uuid::Uuid::new_v4()));
+        let store = IdentityStore::new(&dir);
+
+        let first = store
+            .load_or_create("key", || Ok(b"first".to_vec()))
+            .unwrap();
+        let second = store
+            .load_or_create("key", || Ok(b"second".to_vec()))
+            .unwrap();
+        assert_eq!(first, second);
+        assert_eq!(store.names().unwrap(), vec!["key".to_string()]);
+
+        store.clear().unwrap();
+        let rotated = store
+            .load_or_create("key", || Ok(b"second".to_vec()))
+            .unwrap();
+        assert_eq!(rotated, b"second");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -6,13 +6,31 @@
 pub mod device;
 pub mod error;
 pub mod event;
+pub mod hash;
+pub mod identity;
+pub mod naming;
+pub mod pairing;
+pub mod pin;
 pub mod protocol;
+pub mod resume;
+pub mod source;
 pub mod transfer;
 
-pub use device::{Device, DeviceId, DeviceType, Peer};
+pub use device::{Capability, Device, DeviceId, DeviceType, Peer, DEVICE_TTL};
 pub use error::{Error, Result};
 pub use event::{Event, EventKind};
-pub use protocol::{Protocol, ProtocolBuilder, ProtocolConfig, ProtocolFactory, ProtocolId, ProtocolInfo};
+pub use hash::FileHasher;
+pub use identity::IdentityStore;
+pub use naming::{create_parent_dirs, resolve_save_path, sanitize_file_name, CollisionPolicy};
+pub use pairing::{PairingAttempt, PairingGuard, PairingInvite, PAIRING_CODE_LEN, PAIRING_TIMEOUT};
+pub use pin::{PinGuard, PinStatus, MAX_PIN_ATTEMPTS, PIN_LOCKOUT};
+pub use protocol::{
+    Protocol, ProtocolBuilder, ProtocolConfig, ProtocolFactory, ProtocolId, ProtocolInfo,
+};
+pub use resume::{PartFile, PartManifest};
+pub use source::{mime_from_name, SourceReader, TransferSource, DEFAULT_IGNORE};
 pub use transfer::{
-    AcceptPolicy, FileInfo, TransferIntent, TransferProgress, TransferRequest, TransferState,
+    AcceptPolicy, CancelRegistry, CancellationToken, FileInfo, FileProgress, ProgressTracker,
+    ResumableSend, ResumeRegistry, TransferIntent, TransferProgress, TransferRequest,
+    TransferState, DEFAULT_CONCURRENT_FILES, MAX_MESSAGE_LEN,
 };
@@ -0,0 +1,260 @@
+//! 接收文件命名 - 清理对端提供的文件名并处理重名
+//!
+//! 文件名来自远端设备，不可信：可能包含 `..`、绝对路径或系统保留字符。
+//! 所有接收方在写入磁盘前都应通过 [`resolve_save_path`] 得到最终路径。
+
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// 清理后文件名为空时使用的名称
+const FALLBACK_NAME: &str = "file";
+
+/// 单个路径组件的最大字节数
+const MAX_COMPONENT_LEN: usize = 255;
+
+/// Windows 保留的设备名
+const RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// 文件名冲突策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum CollisionPolicy {
+    /// 重命名为 `name (1).ext`
+    #[default]
+    Rename,
+    /// 覆盖已有文件
+    Overwrite,
+    /// 跳过，不接收该文件
+    Skip,
+}
+
+/// 清理对端提供的文件名，返回保存目录下的相对路径
+///
+/// 去掉 `.`、`..`、盘符和根目录，替换保留字符。
+/// `keep_subdirs` 为 false 时只保留最后一级文件名。
+pub fn sanitize_file_name(name: &str, keep_subdirs: bool) -> PathBuf {
+    let mut components: Vec<String> = name
+        .split(['/', '\\'])
+        .enumerate()
+        .filter(|(i, part)| !(*i == 0 && is_drive_prefix(part)))
+        .filter_map(|(_, part)| sanitize_component(part))
+        .collect();
+
+    if !keep_subdirs && components.len() > 1 {
+        components = components.split_off(components.len() - 1);
+    }
+
+    if components.is_empty() {
+        return PathBuf::from(FALLBACK_NAME);
+    }
+
+    components.iter().collect()
+}
+
+/// 计算文件的最终保存路径
+///
+/// 返回 `None` 表示按 [`CollisionPolicy::Skip`] 跳过该文件。
+pub fn resolve_save_path(
+    save_dir: &Path,
+    name: &str,
+    keep_subdirs: bool,
+    policy: CollisionPolicy,
+) -> Option<PathBuf> {
+    let path = save_dir.join(sanitize_file_name(name, keep_subdirs));
+
+    if !path.exists() {
+        return Some(path);
+    }
+
+    match policy {
+        CollisionPolicy::Overwrite => Some(path),
+        CollisionPolicy::Skip => None,
+        CollisionPolicy::Rename => Some(next_free_path(&path)),
+    }
+}
+
+/// 创建文件所在的子目录
+///
+/// 已存在的子目录可能是指向保存目录之外的符号链接，因此逐级创建，
+/// 每一级确认真实路径仍在保存目录内后才创建下一级。
+pub async fn create_parent_dirs(save_dir: &Path, path: &Path) -> std::io::Result<()> {
+    let outside = |dir: &Path| {
+        std::io::Error::new(
+            std::io::ErrorKind::PermissionDenied,
+            format!("{} is outside of the save directory", dir.display()),
+        )
+    };
+
+    tokio::fs::create_dir_all(save_dir).await?;
+    let root = tokio::fs::canonicalize(save_dir).await?;
+    let dir = path.parent().unwrap_or(save_dir);
+    let relative = dir.strip_prefix(save_dir).map_err(|_| outside(dir))?;
+
+    let mut current = save_dir.to_path_buf();
+    for component in relative.components() {
+        current.push(component);
+        match tokio::fs::create_dir(&current).await {
+            Err(e) if e.kind() != std::io::ErrorKind::AlreadyExists => return Err(e),
+            _ => {}
+        }
+        if !tokio::fs::canonicalize(&current).await?.starts_with(&root) {
+            return Err(outside(&current));
+        }
+    }
+    Ok(())
+}
+
+/// 找到 `name (n).ext` 形式的第一个不存在的路径
+fn next_free_path(path: &Path) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| FALLBACK_NAME.to_string());
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1u32..)
+        .map(|n| path.with_file_name(format!("{} ({}){}", stem, n, ext)))
+        .find(|candidate| !candidate.exists())
+        .expect("ran out of candidate file names")
+}
+
+/// 清理单个路径组件，无效时返回 None
+fn sanitize_component(part: &str) -> Option<String> {
+    let cleaned: String = part
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+
+    // Windows 不允许结尾的点和空格
+    let cleaned = cleaned.trim().trim_end_matches(['.', ' ']);
+
+    if cleaned.is_empty() || cleaned == "." || cleaned == ".." {
+        return None;
+    }
+
+    let base = cleaned.split('.').next().unwrap_or_default();
+    let mut cleaned = if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(base)) {
+        format!("_{}", cleaned)
+    } else {
+        cleaned.to_string()
+    };
+
+    if cleaned.len() > MAX_COMPONENT_LEN {
+        let mut end = MAX_COMPONENT_LEN;
+        while !cleaned.is_char_boundary(end) {
+            end -= 1;
+        }
+        cleaned.truncate(end);
+    }
+
+    Some(cleaned)
+}
+
+/// 是否为 Windows 盘符（如 `C:`）
+fn is_drive_prefix(part: &str) -> bool {
+    let bytes = part.as_bytes();
+    bytes.len() == 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':'
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sanitize_strips_traversal() {
+        assert_eq!(
+            sanitize_file_name("../../.bashrc", true),
+            PathBuf::from(".bashrc")
+        );
+        assert_eq!(
+            sanitize_file_name("/etc/passwd", false),
+            PathBuf::from("passwd")
+        );
+        assert_eq!(
+            sanitize_file_name("C:\\Windows\\..\\evil.exe", true),
+            PathBuf::from("Windows/evil.exe")
+        );
+        assert_eq!(sanitize_file_name("..", true), PathBuf::from(FALLBACK_NAME));
+    }
+
+    #[test]
+    fn test_sanitize_subdirs_and_reserved() {
+        assert_eq!(
+            sanitize_file_name("photos/2024/a.jpg", true),
+            PathBuf::from("photos/2024/a.jpg")
+        );
+        assert_eq!(
+            sanitize_file_name("photos/2024/a.jpg", false),
+            PathBuf::from("a.jpg")
+        );
+        assert_eq!(
+            sanitize_file_name("a<b>?.txt", false),
+            PathBuf::from("a_b__.txt")
+        );
+        assert_eq!(
+            sanitize_file_name("con.txt", false),
+            PathBuf::from("_con.txt")
+        );
+    }
+
+    #[test]
+    fn test_resolve_collision() {
+        let dir = std::env::temp_dir().join(format!("unidrop-naming-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.txt"), b"x").unwrap();
+        std::fs::write(dir.join("a (1).txt"), b"x").unwrap();
+
+        assert_eq!(
+            resolve_save_path(&dir, "a.txt", true, CollisionPolicy::Rename),
+            Some(dir.join("a (2).txt"))
+        );
+        assert_eq!(
+            resolve_save_path(&dir, "a.txt", true, CollisionPolicy::Overwrite),
+            Some(dir.join("a.txt"))
+        );
+        assert_eq!(
+            resolve_save_path(&dir, "a.txt", true, CollisionPolicy::Skip),
+            None
+        );
+        assert_eq!(
+            resolve_save_path(&dir, "b.txt", true, CollisionPolicy::Skip),
+            Some(dir.join("b.txt"))
+        );
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_parent_dirs_stay_in_save_dir() {
+        let dir = std::env::temp_dir().join(format!("unidrop-subdirs-{}", uuid::Uuid::new_v4()));
+        let save_dir = dir.join("inbox");
+        std::fs::create_dir_all(&save_dir).unwrap();
+
+        let nested = save_dir.join(sanitize_file_name("project/src/main.rs", true));
+        create_parent_dirs(&save_dir, &nested).await.unwrap();
+        assert!(save_dir.join("project/src").is_dir());
+
+        // 指向保存目录之外的符号链接不能用作子目录
+        std::os::unix::fs::symlink(&dir, save_dir.join("escape")).unwrap();
+        let escaped = save_dir.join(sanitize_file_name("escape/evil.sh", true));
+        assert!(create_parent_dirs(&save_dir, &escaped).await.is_err());
+
+        // 不能经由符号链接在保存目录之外创建目录
+        let escaped = save_dir.join(sanitize_file_name("escape/created/evil.sh", true));
+        assert!(create_parent_dirs(&save_dir, &escaped).await.is_err());
+        assert!(!dir.join("created").exists());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,412 @@
+//! 设备配对 - 用短码或二维码建立双向信任
+//!
+//! 被配对方生成一次性的数字短码，也可以把指纹、地址和短码编码为链接
+//! （[`PairingInvite`]，用于二维码）。配对方输入短码或扫描二维码后，双方在
+//! 已验证身份的通道上按“先承诺后揭示”交换证明，短码本身不经过网络：
+//!
+//! 1. 被配对方发送承诺 `H(短码, 随机数, 双方指纹)`
+//! 2. 配对方发送自己的承诺，被配对方收到后揭示随机数，短码随即作废
+//! 3. 配对方校验对端的承诺后揭示自己的随机数，被配对方校验后互相加入可信设备
+//!
+//! 随机数揭示前承诺不泄露短码；先揭示的被配对方已无法更改承诺，
+//! 冒充的一方只能在线猜一次，无法离线穷举短码后通过验证。
+//!
+//! 只输入短码时需要确认选择的设备正确；扫描二维码时对端指纹已经确定。
+
+use sha2::{Digest, Sha256};
+use std::net::SocketAddr;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::{DeviceId, Error, ProtocolId, Result};
+
+/// 短码位数
+pub const PAIRING_CODE_LEN: usize = 6;
+
+/// 短码有效期
+pub const PAIRING_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+/// 配对链接前缀
+const INVITE_PREFIX: &str = "unidrop://pair?";
+
+/// 配对邀请：对端身份、地址和短码
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PairingInvite {
+    /// 对端使用的协议
+    pub protocol: ProtocolId,
+    /// 对端设备指纹
+    pub fingerprint: String,
+    /// 对端地址，未发现对端时据此连接
+    pub addresses: Vec<SocketAddr>,
+    /// 一次性短码
+    pub code: String,
+}
+
+impl PairingInvite {
+    pub fn new(target: DeviceId, code: impl Into<String>) -> Self {
+        Self {
+            protocol: target.protocol,
+            fingerprint: target.fingerprint,
+            addresses: Vec::new(),
+            code: code.into(),
+        }
+    }
+
+    pub fn with_address(mut self, address: SocketAddr) -> Self {
+        self.addresses.push(address);
+        self
+    }
+
+    /// 对端设备 ID
+    pub fn target(&self) -> DeviceId {
+        DeviceId::new(self.protocol.clone(), &self.fingerprint)
+    }
+
+    /// 编码为配对链接（用于生成二维码）
+    pub fn to_uri(&self) -> String {
+        let mut uri = format!(
+            "{}protocol={}&fingerprint={}&code={}",
+            INVITE_PREFIX, self.protocol, self.fingerprint, self.code
+        );
+        for address in &self.addresses {
+            uri.push_str(&format!("&addr={}", address));
+        }
+        uri
+    }
+
+    /// 解析配对链接
+    pub fn parse(uri: &str) -> Result<Self> {
+        let invalid = || Error::Protocol(format!("Invalid pairing link: {}", uri));
+        let query = uri.trim().strip_prefix(INVITE_PREFIX).ok_or_else(invalid)?;
+
+        let (mut protocol, mut fingerprint, mut code) = (None, None, None);
+        let mut addresses = Vec::new();
+        for pair in query.split('&') {
+            let (key, value) = pair.split_once('=').ok_or_else(invalid)?;
+            match key {
+                "protocol" => protocol = Some(ProtocolId::new(value)),
+                "fingerprint" => fingerprint = Some(value.to_string()),
+                "code" => code = Some(value.to_string()),
+                "addr" => addresses.push(value.parse().map_err(|_| invalid())?),
+                _ => {}
+            }
+        }
+
+        Ok(Self {
+            protocol: protocol.ok_or_else(invalid)?,
+            fingerprint: fingerprint.filter(|f| !f.is_empty()).ok_or_else(invalid)?,
+            addresses,
+            code: code.ok_or_else(invalid)?,
+        })
+    }
+}
+
+/// 一次性随机数
+pub fn nonce() -> String {
+    format!(
+        "{}{}",
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    )
+}
+
+/// 配对方的承诺
+pub fn initiator_commitment(code: &str, nonce: &str, initiator: &str, responder: &str) -> String {
+    commitment("initiator", code, nonce, initiator, responder)
+}
+
+/// 被配对方的承诺
+pub fn responder_commitment(code: &str, nonce: &str, initiator: &str, responder: &str) -> String {
+    commitment("responder", code, nonce, initiator, responder)
+}
+
+fn commitment(role: &str, code: &str, nonce: &str, initiator: &str, responder: &str) -> String {
+    let mut hasher = Sha256::new();
+    for part in ["unidrop-pair", role, code, nonce, initiator, responder] {
+        hasher.update(part.as_bytes());
+        hasher.update([0]);
+    }
+    hex::encode(hasher.finalize())
+}
+
+/// 比较承诺，耗时与内容无关
+pub fn proofs_match(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |acc, (x, y)| acc | (x ^ y))
+            == 0
+}
+
+/// 配对方的一次配对
+pub struct PairingAttempt {
+    code: String,
+    nonce: String,
+    initiator: String,
+    responder: String,
+    commitment: Option<String>,
+}
+
+impl PairingAttempt {
+    pub fn new(code: &str, initiator: &str, responder: &str) -> Self {
+        Self {
+            code: code.to_string(),
+            nonce: nonce(),
+            initiator: initiator.to_string(),
+            responder: responder.to_string(),
+            commitment: None,
+        }
+    }
+
+    /// 记录对端的承诺，返回本机的承诺
+    pub fn commit(&mut self, responder_commitment: &str) -> String {
+        self.commitment = Some(responder_commitment.to_string());
+        initiator_commitment(&self.code, &self.nonce, &self.initiator, &self.responder)
+    }
+
+    /// 校验对端揭示的随机数，通过后返回本机的随机数
+    ///
+    /// 对端的承诺与短码不符时返回 `PairingFailed`，本机随机数不会发出。
+    pub fn reveal(&self, responder_nonce: &str) -> Result<String> {
+        let expected = responder_commitment(
+            &self.code,
+            responder_nonce,
+            &self.initiator,
+            &self.responder,
+        );
+        match &self.commitment {
+            Some(commitment) if proofs_match(commitment, &expected) => Ok(self.nonce.clone()),
+            _ => Err(Error::PairingFailed("Pairing code does not match".into())),
+        }
+    }
+}
+
+/// 被配对方进行中的配对
+enum Stage {
+    /// 已发出承诺，等待配对方的承诺
+    Committed { initiator: String, nonce: String },
+    /// 已揭示随机数，等待配对方揭示
+    Revealed {
+        initiator: String,
+        commitment: String,
+    },
+}
+
+struct ActiveCode {
+    code: String,
+    expires_at: Instant,
+    stage: Option<Stage>,
+}
+
+/// 被配对方的短码：同时只有一个，揭示随机数后作废
+#[derive(Default)]
+pub struct PairingGuard {
+    active: Mutex<Option<ActiveCode>>,
+}
+
+impl PairingGuard {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 生成新的短码，替换之前的短码
+    pub fn start(&self) -> String {
+        let n = uuid::Uuid::new_v4().as_u128() % 10u128.pow(PAIRING_CODE_LEN as u32);
+        let code = format!("{:0width$}", n, width = PAIRING_CODE_LEN);
+        *self.lock() = Some(ActiveCode {
+            code: code.clone(),
+            expires_at: Instant::now() + PAIRING_TIMEOUT,
+            stage: None,
+        });
+        code
+    }
+
+    /// 作废短码，返回之前是否在配对
+    pub fn stop(&self) -> bool {
+        self.lock().take().is_some()
+    }
+
+    /// 第一步：返回本机的承诺，替换尚未揭示的配对
+    ///
+    /// 没有有效短码或短码已用过时返回 `InvalidSession`。
+    pub fn commit(&self, initiator: &str, responder: &str) -> Result<String> {
+        let mut active = self.lock();
+        let current = Self::current(&mut active)?;
+        if matches!(current.stage, Some(Stage::Revealed { .. })) {
+            return Err(Error::InvalidSession("Pairing code already used".into()));
+        }
+
+        let nonce = nonce();
+        let commitment = responder_commitment(&current.code, &nonce, initiator, responder);
+        current.stage = Some(Stage::Committed {
+            initiator: initiator.to_string(),
+            nonce,
+        });
+        Ok(commitment)
+    }
+
+    /// 第二步：记录配对方的承诺并揭示本机随机数，之后短码不能再用于其他配对
+    pub fn reveal(&self, initiator: &str, commitment: &str) -> Result<String> {
+        let mut active = self.lock();
+        let current = Self::current(&mut active)?;
+        let nonce = match current.stage.take() {
+            Some(Stage::Committed {
+                initiator: from,
+                nonce,
+            }) if from == initiator => nonce,
+            stage => {
+                current.stage = stage;
+                return Err(Error::InvalidSession("No pairing commitment".into()));
+            }
+        };
+
+        current.stage = Some(Stage::Revealed {
+            initiator: initiator.to_string(),
+            commitment: commitment.to_string(),
+        });
+        Ok(nonce)
+    }
+
+    /// 第三步：校验配对方揭示的随机数，无论成败短码都作废
+    ///
+    /// 随机数与承诺不符时返回 `PairingFailed`。
+    pub fn verify(&self, initiator: &str, responder: &str, nonce: &str) -> Result<()> {
+        let mut active = self.lock();
+        let current = Self::current(&mut active)?;
+        let commitment = match &current.stage {
+            Some(Stage::Revealed {
+                initiator: from,
+                commitment,
+            }) if from == initiator => commitment,
+            _ => return Err(Error::InvalidSession("No pairing commitment".into())),
+        };
+
+        let expected = initiator_commitment(&current.code, nonce, initiator, responder);
+        let matched = proofs_match(commitment, &expected);
+        *active = None;
+        if !matched {
+            tracing::warn!("Wrong pairing code, pairing stopped");
+            return Err(Error::PairingFailed("Wrong pairing code".into()));
+        }
+        Ok(())
+    }
+
+    /// 未过期的短码
+    fn current(active: &mut Option<ActiveCode>) -> Result<&mut ActiveCode> {
+        if active
+            .as_ref()
+            .is_some_and(|c| c.expires_at <= Instant::now())
+        {
+            *active = None;
+        }
+        active
+            .as_mut()
+            .ok_or_else(|| Error::InvalidSession("No pairing in progress".into()))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Option<ActiveCode>> {
+        self.active.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按协议完成一次配对
+    fn pair(guard: &PairingGuard, code: &str) -> Result<()> {
+        let mut attempt = PairingAttempt::new(code, "a", "b");
+        let commitment = attempt.commit(&guard.commit("a", "b")?);
+        let nonce = attempt.reveal(&guard.reveal("a", &commitment)?)?;
+        guard.verify("a", "b", &nonce)
+    }
+
+    #[test]
+    fn test_pairing_guard() {
+        let guard = PairingGuard::new();
+        assert!(matches!(
+            guard.commit("a", "b"),
+            Err(Error::InvalidSession(_))
+        ));
+
+        let code = guard.start();
+        assert_eq!(code.len(), PAIRING_CODE_LEN);
+
+        // 绑定了指纹的承诺不能用于其他设备
+        let mut attempt = PairingAttempt::new(&code, "a", "b");
+        let commitment = attempt.commit(&guard.commit("a", "b").unwrap());
+        assert!(guard.reveal("c", &commitment).is_err());
+        let nonce = attempt
+            .reveal(&guard.reveal("a", &commitment).unwrap())
+            .unwrap();
+        assert!(guard.verify("c", "b", &nonce).is_err());
+        guard.verify("a", "b", &nonce).unwrap();
+
+        // 短码只能使用一次
+        assert!(matches!(pair(&guard, &code), Err(Error::InvalidSession(_))));
+
+        // 输错一次后作废
+        let code = guard.start();
+        let wrong = if code == "000000" { "111111" } else { "000000" };
+        assert!(matches!(pair(&guard, wrong), Err(Error::PairingFailed(_))));
+        assert!(matches!(pair(&guard, &code), Err(Error::InvalidSession(_))));
+    }
+
+    #[test]
+    fn test_malicious_responder_cannot_recover_code() {
+        let code = "482913";
+        let mut attempt = PairingAttempt::new(code, "a", "evil");
+
+        // 冒充者不知道短码，只能先对猜测的短码作出承诺
+        let evil_nonce = nonce();
+        let evil_commitment = responder_commitment("000000", &evil_nonce, "a", "evil");
+        let commitment = attempt.commit(&evil_commitment);
+
+        // 配对方的承诺含有未公开的随机数，连正确的短码也无法离线验证，穷举无从下手
+        assert_ne!(
+            initiator_commitment(code, &evil_nonce, "a", "evil"),
+            commitment
+        );
+        assert_ne!(initiator_commitment(code, "", "a", "evil"), commitment);
+
+        // 揭示的随机数与短码不符，配对方不会发出自己的随机数
+        assert!(matches!(
+            attempt.reveal(&evil_nonce),
+            Err(Error::PairingFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_malicious_initiator_cannot_reuse_code() {
+        let guard = PairingGuard::new();
+        let code = guard.start();
+        let guess = if code == "000000" { "111111" } else { "000000" };
+
+        // 冒充者提交猜测的承诺后拿到随机数，可以离线算出短码
+        let responder = guard.commit("evil", "b").unwrap();
+        let mut attempt = PairingAttempt::new(guess, "evil", "b");
+        let nonce = guard.reveal("evil", &attempt.commit(&responder)).unwrap();
+        assert_eq!(responder_commitment(&code, &nonce, "evil", "b"), responder);
+
+        // 但承诺已经发出，短码也不能再用于新的配对
+        assert!(attempt.reveal(&nonce).is_err());
+        assert!(guard.commit("evil", "b").is_err());
+        assert!(matches!(
+            guard.verify("evil", "b", &nonce),
+            Err(Error::PairingFailed(_))
+        ));
+        assert!(matches!(pair(&guard, &code), Err(Error::InvalidSession(_))));
+    }
+
+    #[test]
+    fn test_invite_uri() {
+        let target = DeviceId::new(ProtocolId::new("localsend"), "ABCD");
+        let invite = PairingInvite::new(target.clone(), "012345")
+            .with_address("192.168.1.5:53317".parse().unwrap());
+
+        let parsed = PairingInvite::parse(&invite.to_uri()).unwrap();
+        assert_eq!(parsed, invite);
+        assert_eq!(parsed.target(), target);
+        assert!(PairingInvite::parse("https://example.com").is_err());
+    }
+}
@@ -0,0 +1,124 @@
+//! PIN 校验 - 接收方要求发送方提供 PIN，并限制猜测
+//!
+//! 按来源（IP 或对端 ID）记录连续输错的次数，达到 [`MAX_PIN_ATTEMPTS`] 后
+//! 锁定 [`PIN_LOCKOUT`]，锁定期间即使 PIN 正确也拒绝。未提供 PIN 不计入次数。
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use crate::{Error, Result};
+
+/// 锁定前允许连续输错的次数
+pub const MAX_PIN_ATTEMPTS: u32 = 5;
+
+/// 输错过多后的锁定时长
+pub const PIN_LOCKOUT: Duration = Duration::from_secs(5 * 60);
+
+/// PIN 校验结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PinStatus {
+    /// 无需 PIN 或 PIN 正确
+    Accepted,
+    /// 未提供 PIN 或 PIN 不正确
+    Required,
+    /// 输错次数过多，暂时锁定
+    LockedOut,
+}
+
+impl PinStatus {
+    /// 发送方看到的错误，`device` 为接收方名称
+    pub fn into_result(self, device: &str) -> Result<()> {
+        match self {
+            Self::Accepted => Ok(()),
+            Self::Required => Err(Error::PinRequired(device.to_string())),
+            Self::LockedOut => Err(Error::PinLockedOut(device.to_string())),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Attempts {
+    failures: u32,
+    locked_until: Option<Instant>,
+}
+
+/// 接收方的 PIN 校验与猜测限制
+pub struct PinGuard {
+    pin: Option<String>,
+    attempts: Mutex<HashMap<String, Attempts>>,
+}
+
+impl PinGuard {
+    /// `pin` 为 None 时不要求 PIN
+    pub fn new(pin: Option<String>) -> Self {
+        Self {
+            pin,
+            attempts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 是否要求 PIN
+    pub fn is_required(&self) -> bool {
+        self.pin.is_some()
+    }
+
+    /// 校验 `source` 提供的 PIN
+    pub fn check(&self, source: &str, pin: Option<&str>) -> PinStatus {
+        let Some(required) = &self.pin else {
+            return PinStatus::Accepted;
+        };
+
+        let mut attempts = self.attempts.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = Instant::now();
+        if let Some(entry) = attempts.get(source) {
+            if entry.locked_until.is_some_and(|until| until > now) {
+                return PinStatus::LockedOut;
+            }
+        }
+
+        match pin {
+            Some(pin) if pin == required => {
+                attempts.remove(source);
+                PinStatus::Accepted
+            }
+            None => PinStatus::Required,
+            Some(_) => {
+                let entry = attempts.entry(source.to_string()).or_default();
+                entry.failures += 1;
+                if entry.failures < MAX_PIN_ATTEMPTS {
+                    return PinStatus::Required;
+                }
+                tracing::warn!("Too many wrong PINs from {}, locking out", source);
+                entry.failures = 0;
+                entry.locked_until = Some(now + PIN_LOCKOUT);
+                PinStatus::LockedOut
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pin_guard_locks_out() {
+        assert_eq!(PinGuard::new(None).check("a", None), PinStatus::Accepted);
+
+        let guard = PinGuard::new(Some("1234".into()));
+        assert_eq!(guard.check("a", None), PinStatus::Required);
+        assert_eq!(guard.check("a", Some("1234")), PinStatus::Accepted);
+
+        for _ in 1..MAX_PIN_ATTEMPTS {
+            assert_eq!(guard.check("a", Some("0000")), PinStatus::Required);
+        }
+        assert_eq!(guard.check("a", Some("0000")), PinStatus::LockedOut);
+
+        // 锁定期间正确的 PIN 也被拒绝，其他来源不受影响
+        assert_eq!(guard.check("a", Some("1234")), PinStatus::LockedOut);
+        assert_eq!(guard.check("b", Some("1234")), PinStatus::Accepted);
+    }
+}
@@ -8,9 +8,14 @@ use serde::{Deserialize, Serialize};
 use std::fmt;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
 
-use crate::{Device, DeviceId, Event, Result, TransferIntent, TransferProgress, TransferRequest};
+use crate::identity::IDENTITY_DIR;
+use crate::{
+    CollisionPolicy, Device, DeviceId, Event, IdentityStore, PairingInvite, Result, TransferIntent,
+    TransferProgress, TransferRequest, DEFAULT_CONCURRENT_FILES, DEVICE_TTL,
+};
 
 /// 协议标识符
 #[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
@@ -64,10 +69,22 @@ pub struct ProtocolConfig {
     pub port: u16,
     /// 文件保存目录
     pub save_dir: PathBuf,
+    /// 配置目录（存放设备身份等持久化数据）
+    pub config_dir: PathBuf,
     /// 是否启用加密
     pub encryption: bool,
     /// 可选 PIN 码
     pub pin: Option<String>,
+    /// 接收文件重名时的处理策略
+    pub collision_policy: CollisionPolicy,
+    /// 是否保留对端提供的子目录结构
+    pub keep_subdirs: bool,
+    /// 设备多久未出现后视为离线
+    pub device_ttl: Duration,
+    /// 判定离线前是否先主动探测设备
+    pub liveness_check: bool,
+    /// 支持并行传输的协议同时发送的文件数
+    pub concurrent_files: usize,
 }
 
 impl Default for ProtocolConfig {
@@ -76,12 +93,25 @@ impl Default for ProtocolConfig {
             device_name: "UniDrop".to_string(),
             port: 0,
             save_dir: std::env::temp_dir(),
+            config_dir: std::env::temp_dir().join("unidrop"),
             encryption: true,
             pin: None,
+            collision_policy: CollisionPolicy::default(),
+            keep_subdirs: true,
+            device_ttl: DEVICE_TTL,
+            liveness_check: true,
+            concurrent_files: DEFAULT_CONCURRENT_FILES,
         }
     }
 }
 
+impl ProtocolConfig {
+    /// 设备身份存储
+    pub fn identity_store(&self) -> IdentityStore {
+        IdentityStore::new(self.config_dir.join(IDENTITY_DIR))
+    }
+}
+
 /// 协议 trait - 所有协议实现必须实现此 trait
 ///
 /// 设计原则：
@@ -138,20 +168,105 @@ pub trait Protocol: Send + Sync {
 
     /// 使用 QUIC 发送文件（可选实现）
     ///
-    /// 默认返回不支持错误，协议可以覆盖此方法提供 QUIC 支持
+    /// 默认返回不支持错误，协议可以覆盖此方法提供 QUIC 支持；
+    /// 本机或对端不支持 QUIC 时同样返回 `ProtocolNotSupported`
     async fn send_quic(&self, _intent: TransferIntent) -> Result<String> {
-        Err(crate::Error::Protocol("QUIC transport not supported by this protocol".into()))
+        Err(crate::Error::ProtocolNotSupported(
+            "QUIC transport not supported by this protocol".into(),
+        ))
     }
 
     /// 接受传输请求
     async fn accept(&self, request_id: &str, save_dir: PathBuf) -> Result<()>;
 
+    /// 只接受传输请求中的部分文件（可选实现）
+    ///
+    /// 默认返回不支持错误
+    async fn accept_files(
+        &self,
+        _request_id: &str,
+        _file_ids: Vec<String>,
+        _save_dir: PathBuf,
+    ) -> Result<()> {
+        Err(crate::Error::ProtocolNotSupported(
+            "Partial accept not supported by this protocol".into(),
+        ))
+    }
+
     /// 拒绝传输请求
     async fn reject(&self, request_id: &str) -> Result<()>;
 
     /// 取消传输
     async fn cancel(&self, transfer_id: &str) -> Result<()>;
 
+    /// 续传失败的发送，返回续传的传输 ID（可选实现）
+    ///
+    /// 接收方保留的未完成文件从已有的偏移继续，其余文件重新发送。
+    /// 传输 ID 不属于本协议的失败发送时返回 `InvalidSession`。
+    async fn resume(&self, _transfer_id: &str) -> Result<String> {
+        Err(crate::Error::ProtocolNotSupported(
+            "Resume not supported by this protocol".into(),
+        ))
+    }
+
+    // === 分享（反向传输） ===
+
+    /// 发布一组文件供其他设备下载，返回分享 ID（可选实现）
+    ///
+    /// 同时只有一个分享，再次调用会替换之前的分享。
+    async fn share(&self, _files: Vec<PathBuf>, _pin: Option<String>) -> Result<String> {
+        Err(crate::Error::ProtocolNotSupported(
+            "Sharing not supported by this protocol".into(),
+        ))
+    }
+
+    /// 停止分享
+    async fn stop_share(&self) -> Result<()> {
+        Err(crate::Error::ProtocolNotSupported(
+            "Sharing not supported by this protocol".into(),
+        ))
+    }
+
+    /// 下载设备分享的全部文件，返回传输 ID（可选实现）
+    async fn download(
+        &self,
+        _target: &DeviceId,
+        _pin: Option<String>,
+        _save_dir: PathBuf,
+    ) -> Result<String> {
+        Err(crate::Error::ProtocolNotSupported(
+            "Sharing not supported by this protocol".into(),
+        ))
+    }
+
+    // === 配对 ===
+
+    /// 开始接受配对，返回包含一次性短码的本机邀请（可选实现）
+    ///
+    /// 再次调用会生成新的短码；短码只能尝试一次，输错后需要重新生成。
+    /// 配对方验证成功后发出 `DevicePaired` 事件。
+    async fn start_pairing(&self) -> Result<PairingInvite> {
+        Err(crate::Error::ProtocolNotSupported(
+            "Pairing not supported by this protocol".into(),
+        ))
+    }
+
+    /// 停止接受配对，作废当前短码
+    async fn stop_pairing(&self) -> Result<()> {
+        Err(crate::Error::ProtocolNotSupported(
+            "Pairing not supported by this protocol".into(),
+        ))
+    }
+
+    /// 使用对端的短码与其配对，成功时返回对端设备（可选实现）
+    ///
+    /// 邀请带有地址时，对端不必已被发现。
+    async fn pair(&self, _invite: &PairingInvite) -> Result<Device> {
+        Err(crate::Error::ProtocolNotSupported(
+            "Pairing not supported by this protocol".into(),
+        ))
+    }
+
     // === 事件订阅 ===
 
     /// 订阅事件流
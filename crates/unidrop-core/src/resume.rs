@@ -0,0 +1,202 @@
+//! 断点续传 - 未完成文件的保留与恢复
+//!
+//! 接收中的文件写入 `.part` 临时文件，旁边的 `.part.json` 清单记录会话、
+//! 文件 ID、大小、哈希和已接收字节数。传输中断时两者保留；发送方以同一
+//! 会话续传时，接收方据此返回可以继续的偏移，已有部分重新计入哈希。
+
+use serde::{Deserialize, Serialize};
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use tokio::fs::{File, OpenOptions};
+use tokio::io::{AsyncReadExt, AsyncSeekExt};
+
+use crate::{sanitize_file_name, FileHasher};
+
+/// 未完成文件的清单
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartManifest {
+    /// 接收方保存未完成文件所用的会话 ID
+    pub session_id: String,
+    pub file_id: String,
+    pub size: u64,
+    pub sha256: Option<String>,
+    /// 已接收字节数（中断时记录）
+    pub received: u64,
+}
+
+/// 接收中的临时文件及其清单
+#[derive(Debug, Clone)]
+pub struct PartFile {
+    path: PathBuf,
+    manifest: PartManifest,
+}
+
+impl PartFile {
+    /// 目标子目录下的 `.{session_id}-{file_id}.part`
+    pub fn new(
+        save_dir: &Path,
+        file_name: &str,
+        keep_subdirs: bool,
+        session_id: &str,
+        file_id: &str,
+        size: u64,
+        sha256: Option<&str>,
+    ) -> Self {
+        // 会话 ID 和文件 ID 来自对端，同样需要清理
+        let target = save_dir.join(sanitize_file_name(file_name, keep_subdirs));
+        let part_name = sanitize_file_name(&format!(".{}-{}.part", session_id, file_id), false);
+        let path = target.parent().unwrap_or(save_dir).join(part_name);
+
+        Self {
+            path,
+            manifest: PartManifest {
+                session_id: session_id.to_string(),
+                file_id: file_id.to_string(),
+                size,
+                sha256: sha256.map(str::to_string),
+                received: 0,
+            },
+        }
+    }
+
+    /// 临时文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn manifest_path(&self) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(".json");
+        PathBuf::from(name)
+    }
+
+    /// 可以续传的偏移
+    ///
+    /// 已保存的清单与本次的文件一致（会话、ID、大小、哈希）时取已写入磁盘的长度，
+    /// 否则为 0。
+    pub async fn resume_offset(&self) -> u64 {
+        let Ok(data) = tokio::fs::read(self.manifest_path()).await else {
+            return 0;
+        };
+        let Ok(saved) = serde_json::from_slice::<PartManifest>(&data) else {
+            return 0;
+        };
+        let current = &self.manifest;
+        if saved.session_id != current.session_id
+            || saved.file_id != current.file_id
+            || saved.size != current.size
+            || saved.sha256 != current.sha256
+        {
+            return 0;
+        }
+
+        match tokio::fs::metadata(&self.path).await {
+            Ok(metadata) => metadata.len().min(current.size),
+            Err(_) => 0,
+        }
+    }
+
+    /// 打开临时文件从 `offset` 继续写入，同时写入清单
+    ///
+    /// 已有的前 `offset` 字节计入哈希，之后的内容截断；`offset` 为 0 时新建文件。
+    pub async fn open(&mut self, offset: u64) -> std::io::Result<(File, FileHasher)> {
+        let mut hasher = FileHasher::new();
+        let file = if offset == 0 {
+            File::create(&self.path).await?
+        } else {
+            let mut file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .open(&self.path)
+                .await?;
+            let mut buffer = vec![0u8; 64 * 1024];
+            let mut remaining = offset;
+            while remaining > 0 {
+                let len = remaining.min(buffer.len() as u64) as usize;
+                file.read_exact(&mut buffer[..len]).await?;
+                hasher.update(&buffer[..len]);
+                remaining -= len as u64;
+            }
+            file.set_len(offset).await?;
+            file.seek(SeekFrom::Start(offset)).await?;
+            file
+        };
+
+        self.manifest.received = offset;
+        self.save_manifest().await?;
+        Ok((file, hasher))
+    }
+
+    /// 传输中断，保留临时文件供续传，清单记录已写入的字节数
+    pub async fn keep(&mut self) {
+        let Ok(metadata) = tokio::fs::metadata(&self.path).await else {
+            return;
+        };
+        self.manifest.received = metadata.len();
+        if let Err(e) = self.save_manifest().await {
+            tracing::warn!("Failed to save manifest for {:?}: {}", self.path, e);
+        }
+    }
+
+    /// 接收完成，移动到最终路径并删除清单
+    pub async fn finish(&self, save_path: &Path) -> std::io::Result<()> {
+        tokio::fs::rename(&self.path, save_path).await?;
+        let _ = tokio::fs::remove_file(self.manifest_path()).await;
+        Ok(())
+    }
+
+    /// 删除临时文件和清单
+    pub async fn remove(&self) {
+        let _ = tokio::fs::remove_file(&self.path).await;
+        let _ = tokio::fs::remove_file(self.manifest_path()).await;
+    }
+
+    async fn save_manifest(&self) -> std::io::Result<()> {
+        let data = serde_json::to_vec(&self.manifest)?;
+        tokio::fs::write(self.manifest_path(), data).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+
+    #[tokio::test]
+    async fn test_resume_part() {
+        let dir = std::env::temp_dir().join(format!("unidrop-resume-{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let data = b"0123456789";
+        let mut expected = FileHasher::new();
+        expected.update(data);
+        let sha256 = expected.finish();
+
+        let mut part = PartFile::new(&dir, "a.txt", true, "s1", "file_0", 10, Some(&sha256));
+        assert_eq!(part.resume_offset().await, 0);
+
+        let (mut file, _) = part.open(0).await.unwrap();
+        file.write_all(&data[..6]).await.unwrap();
+        file.flush().await.unwrap();
+        drop(file);
+        part.keep().await;
+
+        // 清单一致时从已写入的长度继续，大小不同则从头开始
+        let mut resumed = PartFile::new(&dir, "a.txt", true, "s1", "file_0", 10, Some(&sha256));
+        assert_eq!(resumed.resume_offset().await, 6);
+        let other = PartFile::new(&dir, "a.txt", true, "s1", "file_0", 11, Some(&sha256));
+        assert_eq!(other.resume_offset().await, 0);
+
+        let (mut file, mut hasher) = resumed.open(6).await.unwrap();
+        file.write_all(&data[6..]).await.unwrap();
+        file.flush().await.unwrap();
+        drop(file);
+        hasher.update(&data[6..]);
+        hasher.verify("a.txt", Some(&sha256)).unwrap();
+
+        resumed.finish(&dir.join("a.txt")).await.unwrap();
+        assert_eq!(std::fs::read(dir.join("a.txt")).unwrap(), data);
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
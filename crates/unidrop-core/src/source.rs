@@ -0,0 +1,437 @@
+//! 传输来源 - 协议无关
+//!
+//! 发送端只通过 [`TransferSource`] 读取数据，来源可以是文件、内存数据、
+//! 剪贴板内容或任意异步流（如 stdin、即时生成的压缩包）。
+
+use bytes::Bytes;
+use std::fmt;
+use std::io::{Cursor, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt};
+
+use crate::{Error, FileHasher, Result};
+
+/// 发送目录时默认忽略的文件和目录
+pub const DEFAULT_IGNORE: &[&str] = &[".git", ".DS_Store", "Thumbs.db", "desktop.ini"];
+
+/// 来源数据的读取端
+pub type SourceReader = Pin<Box<dyn AsyncRead + Send + Sync>>;
+
+#[derive(Clone)]
+enum SourceData {
+    File(PathBuf),
+    Bytes(Bytes),
+    /// 流只能读取一次
+    Reader(Arc<Mutex<Option<SourceReader>>>),
+    /// 长度未知的流暂存到的临时文件
+    Spooled(Arc<SpoolFile>),
+}
+
+/// 暂存流数据的临时文件，最后一个引用释放时删除
+struct SpoolFile(PathBuf);
+
+impl Drop for SpoolFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// 传输来源
+#[derive(Clone)]
+pub struct TransferSource {
+    /// 对端看到的文件名
+    pub name: String,
+    /// MIME 类型
+    pub mime_type: String,
+    size: Option<u64>,
+    hash: Option<String>,
+    data: SourceData,
+}
+
+impl TransferSource {
+    /// 本地文件，大小在 [`sized`](Self::sized) 时读取
+    pub fn file(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown")
+            .to_string();
+        Self {
+            mime_type: mime_from_name(&name),
+            name,
+            size: None,
+            hash: None,
+            data: SourceData::File(path),
+        }
+    }
+
+    /// 内存数据
+    pub fn bytes(name: impl Into<String>, data: impl Into<Bytes>) -> Self {
+        let name = name.into();
+        let data = data.into();
+        Self {
+            mime_type: mime_from_name(&name),
+            name,
+            size: Some(data.len() as u64),
+            hash: None,
+            data: SourceData::Bytes(data),
+        }
+    }
+
+    /// 异步流，`size` 未知时发送前暂存到临时文件
+    pub fn reader(
+        name: impl Into<String>,
+        reader: impl AsyncRead + Send + Sync + 'static,
+        size: Option<u64>,
+    ) -> Self {
+        let name = name.into();
+        Self {
+            mime_type: mime_from_name(&name),
+            name,
+            size,
+            hash: None,
+            data: SourceData::Reader(Arc::new(Mutex::new(Some(Box::pin(reader))))),
+        }
+    }
+
+    /// 剪贴板内容，文件名按 MIME 类型生成
+    pub fn clipboard(data: impl Into<Bytes>, mime_type: impl Into<String>) -> Self {
+        let mime_type = mime_type.into();
+        let ext = match mime_type.as_str() {
+            "image/png" => "png",
+            "image/jpeg" => "jpg",
+            "text/html" => "html",
+            m if m.starts_with("text/") => "txt",
+            _ => "bin",
+        };
+        Self::bytes(format!("clipboard.{}", ext), data).with_mime(mime_type)
+    }
+
+    pub fn with_name(mut self, name: impl Into<String>) -> Self {
+        self.name = name.into();
+        self
+    }
+
+    pub fn with_mime(mut self, mime: impl Into<String>) -> Self {
+        self.mime_type = mime.into();
+        self
+    }
+
+    /// 使用已知的 SHA-256（如构建产物附带的校验和）
+    pub fn with_hash(mut self, hash: impl Into<String>) -> Self {
+        self.hash = Some(hash.into());
+        self
+    }
+
+    /// 数据大小，[`sized`](Self::sized) 之后总是已知
+    pub fn size(&self) -> Option<u64> {
+        self.size
+    }
+
+    /// SHA-256（小写十六进制），[`hashed`](Self::hashed) 之后可用
+    pub fn hash(&self) -> Option<&str> {
+        self.hash.as_deref()
+    }
+
+    /// 文件来源的路径
+    pub fn path(&self) -> Option<&Path> {
+        match &self.data {
+            SourceData::File(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    /// 确定数据大小：文件读取元数据，长度未知的流暂存到临时文件
+    ///
+    /// 暂存时边读边写，不会把整个流读入内存；临时文件随来源释放删除。
+    pub async fn sized(mut self) -> Result<Self> {
+        if self.size.is_some() {
+            return Ok(self);
+        }
+        match &self.data {
+            SourceData::File(path) => {
+                let metadata = tokio::fs::metadata(path)
+                    .await
+                    .ok()
+                    .filter(|m| m.is_file())
+                    .ok_or_else(|| Error::FileNotFound(path.display().to_string()))?;
+                self.size = Some(metadata.len());
+            }
+            SourceData::Bytes(data) => self.size = Some(data.len() as u64),
+            SourceData::Reader(_) => {
+                let mut reader = self.open().await?;
+                let spool = SpoolFile(
+                    std::env::temp_dir().join(format!("unidrop-spool-{}", uuid::Uuid::new_v4())),
+                );
+                let mut file = tokio::fs::File::create(&spool.0).await?;
+                let size = tokio::io::copy(&mut reader, &mut file).await?;
+                file.sync_all().await?;
+                self.size = Some(size);
+                self.data = SourceData::Spooled(Arc::new(spool));
+            }
+            SourceData::Spooled(spool) => {
+                self.size = Some(tokio::fs::metadata(&spool.0).await?.len());
+            }
+        }
+        Ok(self)
+    }
+
+    /// 计算 SHA-256，超过 `limit` 字节的数据和只能读取一次的流跳过
+    pub async fn hashed(self, limit: Option<u64>) -> Result<Self> {
+        let mut source = self.sized().await?;
+        let too_large = limit.is_some_and(|limit| source.size.unwrap_or(0) > limit);
+        if source.hash.is_some() || too_large || matches!(source.data, SourceData::Reader(_)) {
+            return Ok(source);
+        }
+
+        let mut hasher = FileHasher::new();
+        let mut reader = source.open().await?;
+        let mut buffer = vec![0u8; 64 * 1024];
+        loop {
+            let n = reader.read(&mut buffer).await?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buffer[..n]);
+        }
+        source.hash = Some(hasher.finish());
+        Ok(source)
+    }
+
+    /// 打开数据读取端，流来源只能打开一次
+    pub async fn open(&self) -> Result<SourceReader> {
+        self.open_at(0).await
+    }
+
+    /// 从 `offset` 处打开读取端（续传），流来源只能从头读取
+    pub async fn open_at(&self, offset: u64) -> Result<SourceReader> {
+        match &self.data {
+            SourceData::File(path) => open_file_at(path, offset).await,
+            SourceData::Spooled(spool) => open_file_at(&spool.0, offset).await,
+            SourceData::Bytes(data) => {
+                let start = (offset as usize).min(data.len());
+                Ok(Box::pin(Cursor::new(data.slice(start..))))
+            }
+            SourceData::Reader(_) if offset > 0 => Err(Error::Internal(format!(
+                "Source {} cannot be resumed",
+                self.name
+            ))),
+            SourceData::Reader(reader) => {
+                reader.lock().unwrap().take().ok_or_else(|| {
+                    Error::Internal(format!("Source {} already consumed", self.name))
+                })
+            }
+        }
+    }
+
+    /// 目录来源展开为其中的所有文件，文件名为带目录名的相对路径
+    ///
+    /// `ignore` 中的模式与每一级名称匹配，支持 `*` 和 `?` 通配符。
+    /// 不跟随指向目录的符号链接；其他来源原样返回。
+    pub async fn expand(self, ignore: &[String]) -> Result<Vec<Self>> {
+        let Some(root) = self.path().filter(|p| p.is_dir()).map(Path::to_path_buf) else {
+            return Ok(vec![self]);
+        };
+
+        let mut files = Vec::new();
+        let mut pending = vec![(root, self.name)];
+        while let Some((dir, prefix)) = pending.pop() {
+            let mut entries = tokio::fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let name = entry.file_name().to_string_lossy().to_string();
+                if ignore.iter().any(|pattern| matches_pattern(pattern, &name)) {
+                    continue;
+                }
+
+                let relative = format!("{}/{}", prefix, name);
+                let path = entry.path();
+                if entry.file_type().await?.is_dir() {
+                    pending.push((path, relative));
+                } else if path.is_file() {
+                    files.push(Self::file(path).with_name(relative));
+                }
+            }
+        }
+
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(files)
+    }
+}
+
+impl From<PathBuf> for TransferSource {
+    fn from(path: PathBuf) -> Self {
+        Self::file(path)
+    }
+}
+
+impl fmt::Debug for TransferSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match &self.data {
+            SourceData::File(path) => format!("File({})", path.display()),
+            SourceData::Bytes(_) => "Bytes".to_string(),
+            SourceData::Reader(_) => "Reader".to_string(),
+            SourceData::Spooled(spool) => format!("Spooled({})", spool.0.display()),
+        };
+        f.debug_struct("TransferSource")
+            .field("name", &self.name)
+            .field("mime_type", &self.mime_type)
+            .field("size", &self.size)
+            .field("hash", &self.hash)
+            .field("data", &kind)
+            .finish()
+    }
+}
+
+async fn open_file_at(path: &Path, offset: u64) -> Result<SourceReader> {
+    let mut file = tokio::fs::File::open(path).await?;
+    file.seek(SeekFrom::Start(offset)).await?;
+    Ok(Box::pin(file))
+}
+
+/// 简单通配符匹配，`*` 匹配任意长度，`?` 匹配单个字符
+fn matches_pattern(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+
+    // 回溯到最近一个 `*` 的位置重新匹配
+    let (mut p, mut n) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((sp, sn)) => {
+                    p = sp + 1;
+                    n = sn + 1;
+                    star = Some((sp, sn + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// 根据文件扩展名推断 MIME 类型
+pub fn mime_from_name(name: &str) -> String {
+    let ext = Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+
+    match ext.as_str() {
+        "txt" => "text/plain",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" => "application/javascript",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "tar" => "application/x-tar",
+        "gz" => "application/gzip",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "svg" => "image/svg+xml",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        _ => "application/octet-stream",
+    }
+    .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reader_source() {
+        let source = TransferSource::reader("build.tar", &b"artifact"[..], None);
+        assert_eq!(source.mime_type, "application/x-tar");
+        assert_eq!(source.size(), None);
+
+        let source = source.sized().await.unwrap();
+        assert_eq!(source.size(), Some(8));
+        assert_eq!(
+            source
+                .clone()
+                .hashed(None)
+                .await
+                .unwrap()
+                .hash()
+                .map(str::len),
+            Some(64)
+        );
+        assert!(source
+            .clone()
+            .hashed(Some(4))
+            .await
+            .unwrap()
+            .hash()
+            .is_none());
+
+        // 暂存后可以重复打开和续传
+        for _ in 0..2 {
+            let mut data = Vec::new();
+            source
+                .open()
+                .await
+                .unwrap()
+                .read_to_end(&mut data)
+                .await
+                .unwrap();
+            assert_eq!(data, b"artifact");
+        }
+        let mut data = Vec::new();
+        let mut reader = source.open_at(4).await.unwrap();
+        reader.read_to_end(&mut data).await.unwrap();
+        assert_eq!(data, b"fact");
+
+        // 临时文件随最后一个来源释放删除
+        let SourceData::Spooled(spool) = &source.data else {
+            panic!("reader source was not spooled");
+        };
+        let spool_path = spool.0.clone();
+        assert!(spool_path.is_file());
+        drop(source);
+        assert!(!spool_path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_expand_dir() {
+        let dir = std::env::temp_dir().join(format!("unidrop-expand-{}", uuid::Uuid::new_v4()));
+        let root = dir.join("project");
+        std::fs::create_dir_all(root.join("src/nested")).unwrap();
+        std::fs::create_dir_all(root.join(".git")).unwrap();
+        std::fs::write(root.join("README.md"), b"readme").unwrap();
+        std::fs::write(root.join("src/main.rs"), b"fn main() {}").unwrap();
+        std::fs::write(root.join("src/nested/debug.log"), b"log").unwrap();
+        std::fs::write(root.join(".git/HEAD"), b"ref").unwrap();
+
+        let ignore = vec![".git".to_string(), "*.log".to_string()];
+        let sources = TransferSource::file(&root).expand(&ignore).await.unwrap();
+        let names: Vec<_> = sources.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["project/README.md", "project/src/main.rs"]);
+
+        // 普通文件原样返回
+        let file = TransferSource::file(root.join("README.md"));
+        assert_eq!(file.expand(&ignore).await.unwrap().len(), 1);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -1,9 +1,14 @@
 //! 传输相关类型 - 协议无关
 
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
 use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use crate::{Device, DeviceId};
+use crate::{Device, DeviceId, TransferSource};
+
+pub use tokio_util::sync::CancellationToken;
 
 /// 文件信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,6 +60,9 @@ pub struct TransferRequest {
     pub message: Option<String>,
     /// 协议特定数据（用于协议层处理）
     pub protocol_data: Option<Vec<u8>>,
+    /// 发送方身份已由协议验证（如 TLS 客户端证书与其设备 ID 一致），
+    /// 否则 `from` 只是对端自报的信息
+    pub verified: bool,
 }
 
 impl TransferRequest {
@@ -67,31 +75,69 @@ impl TransferRequest {
             total_size,
             message: None,
             protocol_data: None,
+            verified: false,
         }
     }
 
+    /// 标记发送方身份已验证
+    pub fn with_verified(mut self, verified: bool) -> Self {
+        self.verified = verified;
+        self
+    }
+
     pub fn file_count(&self) -> usize {
         self.files.len()
     }
 }
 
+/// 作为消息收发的文本上限（字节），接收时更长的文本按文件处理
+pub const MAX_MESSAGE_LEN: usize = 64 * 1024;
+
+/// 支持并行传输的协议默认同时发送的文件数
+pub const DEFAULT_CONCURRENT_FILES: usize = 4;
+
 /// 传输意图 - 发起的出站传输
 #[derive(Debug, Clone)]
 pub struct TransferIntent {
     /// 目标设备 ID
     pub target: DeviceId,
-    /// 要发送的文件路径
-    pub files: Vec<PathBuf>,
+    /// 要发送的数据来源
+    pub sources: Vec<TransferSource>,
     /// 附加消息
     pub message: Option<String>,
+    /// 文本消息（文本或链接），设置时不发送文件
+    pub text: Option<String>,
+    /// 接收方要求的 PIN
+    pub pin: Option<String>,
 }
 
 impl TransferIntent {
     pub fn new(target: DeviceId, files: Vec<PathBuf>) -> Self {
+        Self::from_sources(
+            target,
+            files.into_iter().map(TransferSource::file).collect(),
+        )
+    }
+
+    /// 发送任意来源（内存数据、流等）
+    pub fn from_sources(target: DeviceId, sources: Vec<TransferSource>) -> Self {
         Self {
             target,
-            files,
+            sources,
             message: None,
+            text: None,
+            pin: None,
+        }
+    }
+
+    /// 发送文本消息
+    pub fn text(target: DeviceId, text: impl Into<String>) -> Self {
+        Self {
+            target,
+            sources: Vec::new(),
+            message: None,
+            text: Some(text.into()),
+            pin: None,
         }
     }
 
@@ -99,6 +145,12 @@ impl TransferIntent {
         self.message = Some(msg.into());
         self
     }
+
+    /// 设置 PIN（接收方返回 `Error::PinRequired` 后重试时使用）
+    pub fn with_pin(mut self, pin: impl Into<String>) -> Self {
+        self.pin = Some(pin.into());
+        self
+    }
 }
 
 /// 传输状态
@@ -137,6 +189,9 @@ pub struct TransferProgress {
     pub state: TransferState,
     /// 当前文件 ID
     pub current_file: Option<String>,
+    /// 并行传输中正在传输的文件
+    #[serde(default)]
+    pub active_files: Vec<FileProgress>,
     /// 已传输字节数
     pub bytes_transferred: u64,
     /// 总字节数
@@ -157,6 +212,7 @@ impl TransferProgress {
             transfer_id: transfer_id.into(),
             state: TransferState::Pending,
             current_file: None,
+            active_files: Vec::new(),
             bytes_transferred: 0,
             bytes_total,
             files_completed: 0,
@@ -175,6 +231,267 @@ impl TransferProgress {
     }
 }
 
+/// 单个文件的进度
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileProgress {
+    /// 文件 ID
+    pub file_id: String,
+    /// 已传输字节数（含续传前对端已有的部分）
+    pub bytes_transferred: u64,
+    /// 文件大小
+    pub bytes_total: u64,
+}
+
+/// 进度事件的最小发送间隔
+pub const PROGRESS_INTERVAL: Duration = Duration::from_millis(250);
+
+/// 速度估算的滑动窗口
+const SPEED_WINDOW: Duration = Duration::from_secs(3);
+
+/// 进度跟踪器
+///
+/// 累计已传输字节数，按 [`PROGRESS_INTERVAL`] 节流输出进度快照，
+/// 并用最近 [`SPEED_WINDOW`] 内的采样估算速度。
+#[derive(Debug)]
+pub struct ProgressTracker {
+    progress: TransferProgress,
+    samples: VecDeque<(Instant, u64)>,
+    last_emit: Option<Instant>,
+}
+
+impl ProgressTracker {
+    pub fn new(transfer_id: impl Into<String>, bytes_total: u64, files_total: usize) -> Self {
+        let mut progress = TransferProgress::new(transfer_id, bytes_total, files_total);
+        progress.state = TransferState::Transferring;
+
+        Self {
+            progress,
+            samples: VecDeque::new(),
+            last_emit: None,
+        }
+    }
+
+    pub fn transfer_id(&self) -> &str {
+        &self.progress.transfer_id
+    }
+
+    /// 开始传输某个文件
+    pub fn start_file(&mut self, file_id: impl Into<String>) {
+        self.progress.current_file = Some(file_id.into());
+    }
+
+    /// 记录新传输的字节，到达节流间隔时返回进度快照
+    pub fn advance(&mut self, bytes: u64) -> Option<TransferProgress> {
+        self.progress.bytes_transferred += bytes;
+
+        let now = Instant::now();
+        self.samples
+            .push_back((now, self.progress.bytes_transferred));
+        while let Some(&(t, _)) = self.samples.front() {
+            if now.duration_since(t) > SPEED_WINDOW && self.samples.len() > 2 {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        match self.last_emit {
+            Some(last) if now.duration_since(last) < PROGRESS_INTERVAL => None,
+            _ => {
+                self.last_emit = Some(now);
+                Some(self.snapshot())
+            }
+        }
+    }
+
+    /// 续传时计入对端已有的字节，不影响速度估算
+    pub fn resume(&mut self, bytes: u64) {
+        self.progress.bytes_transferred += bytes;
+        for sample in &mut self.samples {
+            sample.1 += bytes;
+        }
+    }
+
+    /// 当前文件传输完毕，总是返回进度快照
+    pub fn finish_file(&mut self) -> TransferProgress {
+        self.progress.files_completed += 1;
+        self.last_emit = Some(Instant::now());
+        self.snapshot()
+    }
+
+    /// 开始并行传输中的某个文件，`offset` 为续传时对端已有的字节
+    pub fn begin_file(&mut self, file_id: impl Into<String>, size: u64, offset: u64) {
+        let file_id = file_id.into();
+        self.progress.active_files.push(FileProgress {
+            file_id: file_id.clone(),
+            bytes_transferred: offset,
+            bytes_total: size,
+        });
+        self.progress.current_file = Some(file_id);
+        self.resume(offset);
+    }
+
+    /// 记录并行传输中某个文件新传输的字节，到达节流间隔时返回进度快照
+    pub fn advance_file(&mut self, file_id: &str, bytes: u64) -> Option<TransferProgress> {
+        if let Some(file) = self
+            .progress
+            .active_files
+            .iter_mut()
+            .find(|f| f.file_id == file_id)
+        {
+            file.bytes_transferred += bytes;
+        }
+        self.advance(bytes)
+    }
+
+    /// 并行传输中的某个文件传输完毕，总是返回进度快照
+    pub fn end_file(&mut self, file_id: &str) -> TransferProgress {
+        self.progress.active_files.retain(|f| f.file_id != file_id);
+        if self.progress.current_file.as_deref() == Some(file_id) {
+            self.progress.current_file =
+                self.progress.active_files.last().map(|f| f.file_id.clone());
+        }
+        self.finish_file()
+    }
+
+    /// 跳过某个文件，从总量中扣除
+    pub fn skip_file(&mut self, size: u64) {
+        self.progress.bytes_total = self.progress.bytes_total.saturating_sub(size);
+        self.progress.files_total = self.progress.files_total.saturating_sub(1);
+    }
+
+    /// 标记整个传输完成
+    pub fn complete(&mut self) -> TransferProgress {
+        self.progress.state = TransferState::Completed;
+        self.progress.current_file = None;
+        self.progress.active_files.clear();
+        self.snapshot()
+    }
+
+    /// 标记整个传输失败
+    pub fn fail(&mut self, error: impl Into<String>) -> TransferProgress {
+        self.progress.state = TransferState::Failed;
+        self.progress.error = Some(error.into());
+        self.snapshot()
+    }
+
+    /// 标记整个传输已取消
+    pub fn cancel(&mut self) -> TransferProgress {
+        self.progress.state = TransferState::Cancelled;
+        self.progress.current_file = None;
+        self.progress.active_files.clear();
+        self.snapshot()
+    }
+
+    /// 所有文件是否都已完成
+    pub fn is_done(&self) -> bool {
+        self.progress.files_completed >= self.progress.files_total
+    }
+
+    /// 获取当前进度快照
+    pub fn snapshot(&self) -> TransferProgress {
+        let mut progress = self.progress.clone();
+        progress.speed_bps = self.speed_bps();
+        progress
+    }
+
+    /// 滑动窗口内的平均速度（字节/秒）
+    fn speed_bps(&self) -> Option<u64> {
+        let (first_t, first_bytes) = *self.samples.front()?;
+        let (last_t, last_bytes) = *self.samples.back()?;
+        let elapsed = last_t.duration_since(first_t).as_secs_f64();
+        if elapsed <= 0.0 {
+            return None;
+        }
+        Some(((last_bytes - first_bytes) as f64 / elapsed) as u64)
+    }
+}
+
+/// 进行中传输的取消令牌，按传输 ID 登记
+///
+/// 克隆后共享同一份登记表，便于发送端、接收端和协议入口共用。
+#[derive(Debug, Clone, Default)]
+pub struct CancelRegistry {
+    tokens: Arc<Mutex<HashMap<String, CancellationToken>>>,
+}
+
+impl CancelRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记传输，返回其取消令牌（已登记时返回原令牌）
+    pub fn register(&self, transfer_id: &str) -> CancellationToken {
+        self.tokens
+            .lock()
+            .unwrap()
+            .entry(transfer_id.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// 取消传输，返回传输是否存在
+    pub fn cancel(&self, transfer_id: &str) -> bool {
+        match self.tokens.lock().unwrap().get(transfer_id) {
+            Some(token) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 传输结束后移除
+    pub fn remove(&self, transfer_id: &str) {
+        self.tokens.lock().unwrap().remove(transfer_id);
+    }
+
+    /// 传输是否已登记
+    pub fn contains(&self, transfer_id: &str) -> bool {
+        self.tokens.lock().unwrap().contains_key(transfer_id)
+    }
+}
+
+/// 失败后可以续传的发送
+#[derive(Debug, Clone)]
+pub struct ResumableSend {
+    /// 接收方保存未完成文件所用的会话 ID
+    pub session_id: String,
+    /// 目标设备和已确定大小、哈希的来源
+    pub intent: TransferIntent,
+}
+
+/// 失败的发送，按传输 ID 登记，供续传使用
+///
+/// 克隆后共享同一份登记表。
+#[derive(Debug, Clone, Default)]
+pub struct ResumeRegistry {
+    sends: Arc<Mutex<HashMap<String, ResumableSend>>>,
+}
+
+impl ResumeRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记失败的发送
+    pub fn insert(&self, transfer_id: &str, send: ResumableSend) {
+        self.sends
+            .lock()
+            .unwrap()
+            .insert(transfer_id.to_string(), send);
+    }
+
+    pub fn get(&self, transfer_id: &str) -> Option<ResumableSend> {
+        self.sends.lock().unwrap().get(transfer_id).cloned()
+    }
+
+    /// 续传成功后移除
+    pub fn remove(&self, transfer_id: &str) {
+        self.sends.lock().unwrap().remove(transfer_id);
+    }
+}
+
 /// 接收策略
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
 #[serde(rename_all = "snake_case")]
@@ -182,8 +499,66 @@ pub enum AcceptPolicy {
     /// 每次都询问
     #[default]
     AlwaysAsk,
-    /// 可信设备自动接收
+    /// 可信设备自动接收（仅限发送方身份已验证的请求）
     AutoAcceptTrusted,
     /// 自动接收所有
     AutoAcceptAll,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_tracker_throttles() {
+        let mut tracker = ProgressTracker::new("t1", 300, 1);
+        tracker.start_file("file_0");
+
+        // 第一次总是输出，之后在节流间隔内不输出
+        assert!(tracker.advance(100).is_some());
+        assert!(tracker.advance(100).is_none());
+
+        let progress = tracker.finish_file();
+        assert_eq!(progress.bytes_transferred, 200);
+        assert_eq!(progress.files_completed, 1);
+        assert_eq!(progress.current_file.as_deref(), Some("file_0"));
+        assert!(tracker.is_done());
+
+        let progress = tracker.complete();
+        assert_eq!(progress.state, TransferState::Completed);
+    }
+
+    #[test]
+    fn test_progress_tracker_parallel_files() {
+        let mut tracker = ProgressTracker::new("t1", 300, 2);
+        tracker.begin_file("file_0", 100, 40);
+        tracker.begin_file("file_1", 200, 0);
+
+        tracker.advance_file("file_0", 60);
+        let progress = tracker
+            .advance_file("file_1", 50)
+            .unwrap_or_else(|| tracker.snapshot());
+        assert_eq!(progress.bytes_transferred, 150);
+        assert_eq!(progress.active_files[0].bytes_transferred, 100);
+        assert_eq!(progress.active_files[1].bytes_transferred, 50);
+
+        // 结束的文件移出活动列表，当前文件切换到仍在传输的文件
+        let progress = tracker.end_file("file_1");
+        assert_eq!(progress.files_completed, 1);
+        assert_eq!(progress.current_file.as_deref(), Some("file_0"));
+        assert_eq!(progress.active_files.len(), 1);
+    }
+
+    #[test]
+    fn test_cancel_registry() {
+        let registry = CancelRegistry::new();
+        let token = registry.register("t1");
+
+        assert!(!registry.cancel("t2"));
+        assert!(registry.cancel("t1"));
+        assert!(token.is_cancelled());
+
+        registry.remove("t1");
+        assert!(!registry.contains("t1"));
+    }
+}
@@ -4,10 +4,25 @@ use anyhow::Result;
 use tracing::{info, Level};
 use tracing_subscriber::FmtSubscriber;
 
+use unidrop_core::AcceptPolicy;
 use unidrop_engine::{Engine, EngineConfig};
 use unidrop_protocol_localsend::LocalSendFactory;
 use unidrop_protocol_p2p::P2pFactory;
 
+/// 接收策略的环境变量：`auto_accept_trusted`（默认）、`auto_accept_all` 或 `always_ask`
+///
+/// 守护进程没有交互界面，`always_ask` 下的请求只能由其他前端确认，否则超时拒绝。
+const ACCEPT_POLICY_ENV: &str = "UNIDROP_ACCEPT_POLICY";
+
+fn accept_policy() -> Result<AcceptPolicy> {
+    match std::env::var(ACCEPT_POLICY_ENV).as_deref() {
+        Err(_) | Ok("auto_accept_trusted") => Ok(AcceptPolicy::AutoAcceptTrusted),
+        Ok("auto_accept_all") => Ok(AcceptPolicy::AutoAcceptAll),
+        Ok("always_ask") => Ok(AcceptPolicy::AlwaysAsk),
+        Ok(other) => anyhow::bail!("Invalid {}: {}", ACCEPT_POLICY_ENV, other),
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // 初始化日志
@@ -31,10 +46,14 @@ async fn main() -> Result<()> {
             .join("UniDrop"),
         encryption: true,
         pin: None,
+        // 默认只自动接收可信设备
+        accept_policy: accept_policy()?,
+        ..Default::default()
     };
 
     info!("Device name: {}", config.device_name);
     info!("Save directory: {:?}", config.save_dir);
+    info!("Accept policy: {:?}", config.accept_policy);
 
     // 确保保存目录存在
     std::fs::create_dir_all(&config.save_dir)?;
@@ -43,7 +62,7 @@ async fn main() -> Result<()> {
     let engine = Engine::builder()
         .config(config)
         .with_protocol(LocalSendFactory::new())
-        .with_protocol(P2pFactory::new())  // 添加 P2P 协议
+        .with_protocol(P2pFactory::new()) // 添加 P2P 协议
         .build();
 
     // 订阅事件
@@ -64,13 +83,16 @@ async fn main() -> Result<()> {
                 unidrop_core::EventKind::DeviceLost(id) => {
                     info!("Device lost: {}", id);
                 }
-                unidrop_core::EventKind::TransferRequested(request) => {
+                unidrop_core::EventKind::TransferAccepted(request) => {
                     info!(
-                        "Transfer request from {}: {} files",
+                        "Transfer accepted from {}: {} files",
                         request.from.name(),
                         request.file_count()
                     );
                 }
+                unidrop_core::EventKind::MessageReceived { from, text } => {
+                    info!("Message from {}: {}", from.name(), text);
+                }
                 unidrop_core::EventKind::TransferCompleted { transfer_id } => {
                     info!("Transfer completed: {}", transfer_id);
                 }
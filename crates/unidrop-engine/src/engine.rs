@@ -4,17 +4,21 @@
 
 use futures::StreamExt;
 use parking_lot::RwLock;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::{broadcast, mpsc};
 use tracing::{debug, error, info, warn};
 
 use unidrop_core::{
-    Device, DeviceId, Event, EventKind, Protocol, ProtocolConfig, ProtocolFactory, ProtocolId,
-    ProtocolInfo, Result, TransferIntent, TransferRequest,
+    AcceptPolicy, CollisionPolicy, Device, DeviceId, Event, EventKind, IdentityStore,
+    PairingInvite, Protocol, ProtocolConfig, ProtocolFactory, ProtocolId, ProtocolInfo, Result,
+    TransferIntent, TransferRequest, DEFAULT_CONCURRENT_FILES, DEFAULT_IGNORE, DEVICE_TTL,
 };
 
+use crate::pending::{PendingRequests, PENDING_REQUEST_TTL};
+use crate::trust::{TrustStore, TrustedDevice, TRUSTED_DEVICES_FILE};
 use crate::{ProtocolRegistry, TransferRouter};
 
 /// Engine 配置
@@ -26,10 +30,28 @@ pub struct EngineConfig {
     pub port: u16,
     /// 文件保存目录
     pub save_dir: PathBuf,
+    /// 配置目录（存放设备身份等持久化数据）
+    pub config_dir: PathBuf,
     /// 是否启用加密
     pub encryption: bool,
     /// 可选 PIN 码
     pub pin: Option<String>,
+    /// 接收策略
+    pub accept_policy: AcceptPolicy,
+    /// 接收文件重名时的处理策略
+    pub collision_policy: CollisionPolicy,
+    /// 是否保留对端提供的子目录结构
+    pub keep_subdirs: bool,
+    /// 设备多久未出现后视为离线
+    pub device_ttl: Duration,
+    /// 判定离线前是否先主动探测设备
+    pub liveness_check: bool,
+    /// 发送目录时忽略的名称模式（支持 `*` 和 `?`）
+    pub ignore: Vec<String>,
+    /// 超过该大小的文件发送时不计算 SHA-256（对端只校验声明了哈希的文件），None 表示总是计算
+    pub hash_limit: Option<u64>,
+    /// 支持并行传输的协议（如 QUIC）同时发送的文件数
+    pub concurrent_files: usize,
 }
 
 impl Default for EngineConfig {
@@ -44,8 +66,19 @@ impl Default for EngineConfig {
                 .or_else(dirs::home_dir)
                 .unwrap_or_else(std::env::temp_dir)
                 .join("UniDrop"),
+            config_dir: dirs::config_dir()
+                .unwrap_or_else(std::env::temp_dir)
+                .join("unidrop"),
             encryption: true,
             pin: None,
+            accept_policy: AcceptPolicy::default(),
+            collision_policy: CollisionPolicy::default(),
+            keep_subdirs: true,
+            device_ttl: DEVICE_TTL,
+            liveness_check: true,
+            ignore: DEFAULT_IGNORE.iter().map(|s| s.to_string()).collect(),
+            hash_limit: None,
+            concurrent_files: DEFAULT_CONCURRENT_FILES,
         }
     }
 }
@@ -56,12 +89,27 @@ impl From<EngineConfig> for ProtocolConfig {
             device_name: config.device_name,
             port: config.port,
             save_dir: config.save_dir,
+            config_dir: config.config_dir,
             encryption: config.encryption,
             pin: config.pin,
+            collision_policy: config.collision_policy,
+            keep_subdirs: config.keep_subdirs,
+            device_ttl: config.device_ttl,
+            liveness_check: config.liveness_check,
+            concurrent_files: config.concurrent_files,
         }
     }
 }
 
+/// 发送实际使用的传输
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+    /// UniDrop 设备之间的 QUIC
+    Quic,
+    /// 协议默认的传输（LocalSend 为 HTTPS）
+    Default,
+}
+
 /// UniDrop Engine
 ///
 /// 上层业务的唯一入口点，提供：
@@ -74,6 +122,8 @@ pub struct Engine {
     registry: Arc<ProtocolRegistry>,
     router: Arc<TransferRouter>,
     devices: RwLock<HashMap<DeviceId, Device>>,
+    trusted: Arc<TrustStore>,
+    pending: Arc<PendingRequests>,
     event_tx: broadcast::Sender<Event>,
     running: RwLock<bool>,
 }
@@ -84,12 +134,17 @@ impl Engine {
         let (event_tx, _) = broadcast::channel(256);
         let registry = Arc::new(ProtocolRegistry::new());
         let router = Arc::new(TransferRouter::new(registry.clone()));
+        let trusted = Arc::new(TrustStore::load(
+            config.config_dir.join(TRUSTED_DEVICES_FILE),
+        ));
 
         Self {
             config: RwLock::new(config),
             registry,
             router,
             devices: RwLock::new(HashMap::new()),
+            trusted,
+            pending: Arc::new(PendingRequests::new()),
             event_tx,
             running: RwLock::new(false),
         }
@@ -175,6 +230,47 @@ impl Engine {
         *self.running.read()
     }
 
+    // === 设备身份 ===
+
+    /// 设备身份存储（证书、密钥等）
+    pub fn identity_store(&self) -> IdentityStore {
+        ProtocolConfig::from(self.config.read().clone()).identity_store()
+    }
+
+    /// 轮换设备身份：删除已保存的身份材料，重启后重新生成
+    ///
+    /// 轮换后设备指纹会改变，已信任本机的对端需要重新确认。
+    pub fn rotate_identity(&self) -> Result<()> {
+        self.identity_store().clear()
+    }
+
+    /// 导出设备身份到指定目录，返回导出的文件名
+    pub fn export_identity(&self, dest: impl AsRef<Path>) -> Result<Vec<String>> {
+        self.identity_store().export_to(dest.as_ref())
+    }
+
+    // === 可信设备 ===
+
+    /// 信任设备（`AcceptPolicy::AutoAcceptTrusted` 下自动接收其传输）
+    pub fn trust_device(&self, device: &Device) -> Result<()> {
+        self.trusted.trust(device)
+    }
+
+    /// 取消信任设备，返回设备之前是否可信
+    pub fn untrust_device(&self, id: &DeviceId) -> Result<bool> {
+        self.trusted.untrust(id)
+    }
+
+    /// 设备是否可信
+    pub fn is_trusted(&self, id: &DeviceId) -> bool {
+        self.trusted.is_trusted(id)
+    }
+
+    /// 所有可信设备
+    pub fn trusted_devices(&self) -> Vec<TrustedDevice> {
+        self.trusted.list()
+    }
+
     // === 设备发现 ===
 
     /// 获取所有在线设备（聚合所有协议）
@@ -224,54 +320,235 @@ impl Engine {
 
     // === 传输操作 ===
 
-    /// 发送文件到设备
+    /// 发送文件到设备，目录展开为其中的文件
+    ///
+    /// 对端声明支持 QUIC 且本机可用时使用 QUIC，否则回退到协议默认的传输（HTTPS）。
     pub async fn send(&self, intent: TransferIntent) -> Result<String> {
-        self.router.send(intent).await
+        self.send_with_transport(intent)
+            .await
+            .map(|(session_id, _)| session_id)
     }
 
-    /// 使用 QUIC 发送文件（仅 UniDrop 之间可用）
+    /// 发送文件到设备，同时返回实际使用的传输
+    ///
+    /// QUIC 无法建立连接（如 UDP 被拦截）时同样回退到协议默认的传输；
+    /// 对端拒绝、取消、证书不符或要求 PIN 时不回退。
+    pub async fn send_with_transport(&self, intent: TransferIntent) -> Result<(String, Transport)> {
+        let intent = self.prepare_sources(intent).await?;
+        let quic = self
+            .device(&intent.target)
+            .await
+            .is_some_and(|device| device.quic_port().is_some());
+        if quic {
+            match self.router.send_quic(intent.clone()).await {
+                Ok(session_id) => return Ok((session_id, Transport::Quic)),
+                Err(unidrop_core::Error::ProtocolNotSupported(reason)) => {
+                    debug!("QUIC unavailable, falling back: {}", reason);
+                }
+                Err(unidrop_core::Error::Connection(reason)) => {
+                    warn!("QUIC connection failed, falling back: {}", reason);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        let session_id = self.router.send(intent).await?;
+        Ok((session_id, Transport::Default))
+    }
+
+    /// 强制使用 QUIC 发送文件（仅 UniDrop 之间可用）
     pub async fn send_quic(&self, intent: TransferIntent) -> Result<String> {
+        let intent = self.prepare_sources(intent).await?;
         self.router.send_quic(intent).await
     }
 
+    /// 展开要发送的目录（跳过配置中忽略的文件），并计算各文件的 SHA-256
+    async fn prepare_sources(&self, mut intent: TransferIntent) -> Result<TransferIntent> {
+        if intent.text.is_some() {
+            return Ok(intent);
+        }
+
+        let (ignore, hash_limit) = {
+            let config = self.config.read();
+            (config.ignore.clone(), config.hash_limit)
+        };
+        let mut sources = Vec::with_capacity(intent.sources.len());
+        for source in intent.sources {
+            for file in source.expand(&ignore).await? {
+                sources.push(file.hashed(hash_limit).await?);
+            }
+        }
+        if sources.is_empty() {
+            return Err(unidrop_core::Error::FileNotFound("No files to send".into()));
+        }
+
+        intent.sources = sources;
+        Ok(intent)
+    }
+
     /// 发送文件（便捷方法）
     pub async fn send_files(&self, target: DeviceId, files: Vec<PathBuf>) -> Result<String> {
         self.send(TransferIntent::new(target, files)).await
     }
 
+    /// 发送文本消息（便捷方法）
+    pub async fn send_text(&self, target: DeviceId, text: impl Into<String>) -> Result<String> {
+        self.send(TransferIntent::text(target, text)).await
+    }
+
     /// 接受传输请求
     pub async fn accept(&self, request: &TransferRequest) -> Result<()> {
+        self.pending.remove(&request.id);
+        self.accept_request(request, None, None).await
+    }
+
+    /// 拒绝传输请求
+    pub async fn reject(&self, request: &TransferRequest) -> Result<()> {
+        self.pending.remove(&request.id);
+        self.protocol_for(request)?.reject(&request.id).await
+    }
+
+    /// 按 ID 接受待处理的传输请求
+    ///
+    /// `file_ids` 为 None 时接受全部文件，`save_dir` 为 None 时使用配置的保存目录。
+    /// `file_ids` 含有请求中不存在的文件时返回 `FileNotFound`，请求仍可再次确认。
+    pub async fn accept_by_id(
+        &self,
+        request_id: &str,
+        file_ids: Option<Vec<String>>,
+        save_dir: Option<PathBuf>,
+    ) -> Result<()> {
+        let not_found = || unidrop_core::Error::InvalidSession(request_id.to_string());
+        let request = self.pending.get(request_id).ok_or_else(not_found)?;
+        let file_ids = file_ids
+            .map(|ids| select_files(&request, ids))
+            .transpose()?;
+        let request = self.pending.take(request_id).ok_or_else(not_found)?;
+
+        self.accept_request(&request, file_ids, save_dir).await
+    }
+
+    /// 按 ID 拒绝待处理的传输请求
+    pub async fn reject_by_id(&self, request_id: &str) -> Result<()> {
+        let request = self
+            .pending
+            .take(request_id)
+            .ok_or_else(|| unidrop_core::Error::InvalidSession(request_id.to_string()))?;
+
+        self.protocol_for(&request)?.reject(&request.id).await
+    }
+
+    /// 等待确认的传输请求
+    pub fn pending_requests(&self) -> Vec<TransferRequest> {
+        self.pending.list()
+    }
+
+    /// 取消传输
+    pub async fn cancel(&self, transfer_id: &str, protocol_id: &ProtocolId) -> Result<()> {
+        let protocol = self
+            .registry
+            .get(protocol_id)
+            .ok_or_else(|| unidrop_core::Error::ProtocolNotFound(protocol_id.to_string()))?;
+
+        protocol.cancel(transfer_id).await
+    }
+
+    /// 续传失败的发送，返回续传的传输 ID
+    ///
+    /// 依次询问各协议，由记录了该传输的协议续传。
+    pub async fn resume(&self, transfer_id: &str) -> Result<String> {
+        for protocol in self.registry.instances() {
+            if !protocol.is_running() {
+                continue;
+            }
+            match protocol.resume(transfer_id).await {
+                Err(unidrop_core::Error::InvalidSession(_))
+                | Err(unidrop_core::Error::ProtocolNotSupported(_)) => continue,
+                result => return result,
+            }
+        }
+
+        Err(unidrop_core::Error::InvalidSession(transfer_id.to_string()))
+    }
+
+    // === 分享 ===
+
+    /// 通过指定协议发布一组文件供其他设备下载，返回分享 ID
+    pub async fn share(
+        &self,
+        protocol_id: &ProtocolId,
+        files: Vec<PathBuf>,
+        pin: Option<String>,
+    ) -> Result<String> {
+        let protocol = self
+            .registry
+            .get(protocol_id)
+            .ok_or_else(|| unidrop_core::Error::ProtocolNotFound(protocol_id.to_string()))?;
+
+        protocol.share(files, pin).await
+    }
+
+    /// 停止分享
+    pub async fn stop_share(&self, protocol_id: &ProtocolId) -> Result<()> {
+        let protocol = self
+            .registry
+            .get(protocol_id)
+            .ok_or_else(|| unidrop_core::Error::ProtocolNotFound(protocol_id.to_string()))?;
+
+        protocol.stop_share().await
+    }
+
+    /// 下载设备分享的全部文件到配置的保存目录，返回传输 ID
+    pub async fn download(&self, target: &DeviceId, pin: Option<String>) -> Result<String> {
         let protocol = self
             .registry
-            .get(request.from.protocol())
-            .ok_or_else(|| {
-                unidrop_core::Error::ProtocolNotFound(request.from.protocol().to_string())
-            })?;
+            .get(&target.protocol)
+            .ok_or_else(|| unidrop_core::Error::ProtocolNotFound(target.protocol.to_string()))?;
 
         let save_dir = self.config.read().save_dir.clone();
-        protocol.accept(&request.id, save_dir).await
+        protocol.download(target, pin, save_dir).await
     }
 
-    /// 拒绝传输请求
-    pub async fn reject(&self, request: &TransferRequest) -> Result<()> {
+    // === 配对 ===
+
+    /// 通过指定协议开始接受配对，返回包含短码的邀请（`to_uri()` 可用于生成二维码）
+    ///
+    /// 对端配对成功后自动信任，并发出 `DevicePaired` 事件。
+    pub async fn start_pairing(&self, protocol_id: &ProtocolId) -> Result<PairingInvite> {
         let protocol = self
             .registry
-            .get(request.from.protocol())
-            .ok_or_else(|| {
-                unidrop_core::Error::ProtocolNotFound(request.from.protocol().to_string())
-            })?;
+            .get(protocol_id)
+            .ok_or_else(|| unidrop_core::Error::ProtocolNotFound(protocol_id.to_string()))?;
 
-        protocol.reject(&request.id).await
+        protocol.start_pairing().await
     }
 
-    /// 取消传输
-    pub async fn cancel(&self, transfer_id: &str, protocol_id: &ProtocolId) -> Result<()> {
+    /// 停止接受配对
+    pub async fn stop_pairing(&self, protocol_id: &ProtocolId) -> Result<()> {
         let protocol = self
             .registry
             .get(protocol_id)
             .ok_or_else(|| unidrop_core::Error::ProtocolNotFound(protocol_id.to_string()))?;
 
-        protocol.cancel(transfer_id).await
+        protocol.stop_pairing().await
+    }
+
+    /// 输入对端显示的短码与其配对，成功后双方互相信任
+    pub async fn pair(&self, target: &DeviceId, code: &str) -> Result<Device> {
+        self.pair_with_invite(&PairingInvite::new(target.clone(), code))
+            .await
+    }
+
+    /// 使用扫描得到的配对邀请与对端配对，成功后双方互相信任
+    pub async fn pair_with_invite(&self, invite: &PairingInvite) -> Result<Device> {
+        let protocol = self
+            .registry
+            .get(&invite.protocol)
+            .ok_or_else(|| unidrop_core::Error::ProtocolNotFound(invite.protocol.to_string()))?;
+
+        let device = protocol.pair(invite).await?;
+        self.trust_device(&device)?;
+        self.emit(Event::device_paired(device.clone()));
+        Ok(device)
     }
 
     // === 事件订阅 ===
@@ -283,15 +560,48 @@ impl Engine {
 
     // === 内部方法 ===
 
+    fn protocol_for(&self, request: &TransferRequest) -> Result<Arc<dyn Protocol>> {
+        self.registry.get(request.from.protocol()).ok_or_else(|| {
+            unidrop_core::Error::ProtocolNotFound(request.from.protocol().to_string())
+        })
+    }
+
+    async fn accept_request(
+        &self,
+        request: &TransferRequest,
+        file_ids: Option<Vec<String>>,
+        save_dir: Option<PathBuf>,
+    ) -> Result<()> {
+        let protocol = self.protocol_for(request)?;
+        let save_dir = save_dir.unwrap_or_else(|| self.config.read().save_dir.clone());
+
+        match file_ids {
+            Some(ids) if ids.is_empty() => protocol.reject(&request.id).await,
+            Some(ids) if ids.len() < request.files.len() => {
+                protocol.accept_files(&request.id, ids, save_dir).await
+            }
+            _ => protocol.accept(&request.id, save_dir).await,
+        }
+    }
+
     fn emit(&self, event: Event) {
         let _ = self.event_tx.send(event);
     }
 
     /// 启动协议事件转发任务
+    ///
+    /// 传输请求在转发给上层之前先按接收策略处理，
+    /// 自动接受的请求以 `TransferAccepted` 事件通知上层。
     fn spawn_event_forwarder(&self, protocol: Arc<dyn Protocol>) {
         let event_tx = self.event_tx.clone();
         let devices = Arc::new(RwLock::new(HashMap::new())); // 独立的设备缓存
         let protocol_id = protocol.id().clone();
+        let (accept_policy, save_dir) = {
+            let config = self.config.read();
+            (config.accept_policy, config.save_dir.clone())
+        };
+        let trusted = self.trusted.clone();
+        let pending = self.pending.clone();
 
         tokio::spawn(async move {
             let mut rx = protocol.subscribe();
@@ -308,6 +618,52 @@ impl Engine {
                     EventKind::DeviceUpdated(device) => {
                         devices.write().insert(device.id().clone(), device.clone());
                     }
+                    // 对端用本机短码配对成功
+                    EventKind::DevicePaired(device) => {
+                        if let Err(e) = trusted.trust(device) {
+                            warn!("Failed to trust paired device {}: {}", device.name(), e);
+                        }
+                    }
+                    EventKind::TransferRequested(request) => {
+                        let auto_accept = match accept_policy {
+                            AcceptPolicy::AlwaysAsk => false,
+                            // 自报的设备信息可以伪造，只信任已验证的身份
+                            AcceptPolicy::AutoAcceptTrusted => {
+                                request.verified && trusted.is_trusted(request.from.id())
+                            }
+                            AcceptPolicy::AutoAcceptAll => true,
+                        };
+
+                        if auto_accept {
+                            match protocol.accept(&request.id, save_dir.clone()).await {
+                                Ok(()) => {
+                                    info!(
+                                        "Auto-accepted transfer {} from {}",
+                                        request.id,
+                                        request.from.name()
+                                    );
+                                    let _ =
+                                        event_tx.send(Event::transfer_accepted(request.clone()));
+                                    continue;
+                                }
+                                Err(e) => {
+                                    warn!("Failed to auto-accept transfer {}: {}", request.id, e);
+                                }
+                            }
+                        }
+
+                        pending.insert(request.clone(), PENDING_REQUEST_TTL);
+                        spawn_request_expiry(
+                            request.id.clone(),
+                            protocol.clone(),
+                            pending.clone(),
+                            event_tx.clone(),
+                        );
+                    }
+                    EventKind::TransferCompleted { transfer_id }
+                    | EventKind::TransferFailed { transfer_id, .. } => {
+                        pending.remove(transfer_id);
+                    }
                     _ => {}
                 }
 
@@ -320,6 +676,27 @@ impl Engine {
     }
 }
 
+/// 请求过期后从注册表移除，通知协议拒绝并上报失败事件
+fn spawn_request_expiry(
+    request_id: String,
+    protocol: Arc<dyn Protocol>,
+    pending: Arc<PendingRequests>,
+    event_tx: broadcast::Sender<Event>,
+) {
+    tokio::spawn(async move {
+        tokio::time::sleep(PENDING_REQUEST_TTL).await;
+
+        if pending.take_expired(&request_id).is_some() {
+            info!("Transfer request {} expired", request_id);
+            let _ = protocol.reject(&request_id).await;
+            let _ = event_tx.send(
+                Event::transfer_failed(&request_id, unidrop_core::Error::Timeout.to_string())
+                    .with_protocol(protocol.id().to_string()),
+            );
+        }
+    });
+}
+
 /// Engine Builder
 #[derive(Default)]
 pub struct EngineBuilder {
@@ -327,6 +704,27 @@ pub struct EngineBuilder {
     factories: Vec<Arc<dyn ProtocolFactory>>,
 }
 
+/// 校验选择的文件 ID 并去重，按请求中的文件顺序返回
+fn select_files(request: &TransferRequest, ids: Vec<String>) -> Result<Vec<String>> {
+    let ids: HashSet<String> = ids.into_iter().collect();
+    if let Some(unknown) = ids
+        .iter()
+        .find(|id| !request.files.iter().any(|f| &f.id == *id))
+    {
+        return Err(unidrop_core::Error::FileNotFound(format!(
+            "{} is not part of transfer {}",
+            unknown, request.id
+        )));
+    }
+
+    Ok(request
+        .files
+        .iter()
+        .filter(|f| ids.contains(&f.id))
+        .map(|f| f.id.clone())
+        .collect())
+}
+
 impl EngineBuilder {
     pub fn new() -> Self {
         Self::default()
@@ -347,6 +745,11 @@ impl EngineBuilder {
         self
     }
 
+    pub fn config_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.config.config_dir = dir.into();
+        self
+    }
+
     pub fn with_protocol<F: ProtocolFactory + 'static>(mut self, factory: F) -> Self {
         self.factories.push(Arc::new(factory));
         self
@@ -362,3 +765,155 @@ impl EngineBuilder {
         engine
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use std::net::{IpAddr, Ipv4Addr};
+    use unidrop_core::{Capability, FileInfo, Peer};
+
+    /// 声明支持 QUIC、但 QUIC 发送总是返回指定错误的协议
+    struct QuicFailing {
+        info: ProtocolInfo,
+        error: fn() -> unidrop_core::Error,
+    }
+
+    #[async_trait]
+    impl Protocol for QuicFailing {
+        fn info(&self) -> &ProtocolInfo {
+            &self.info
+        }
+
+        async fn start(&self, _config: ProtocolConfig) -> Result<()> {
+            Ok(())
+        }
+
+        async fn stop(&self) -> Result<()> {
+            Ok(())
+        }
+
+        fn is_running(&self) -> bool {
+            true
+        }
+
+        async fn devices(&self) -> Vec<Device> {
+            let peer = Peer::new(self.info.id.clone(), "ABCD".into(), "Laptop".into());
+            vec![Device::new(peer, IpAddr::V4(Ipv4Addr::LOCALHOST), 53317)
+                .with_capability(Capability::Quic { port: 9 })]
+        }
+
+        async fn scan(&self) -> Result<()> {
+            Ok(())
+        }
+
+        async fn send(&self, _intent: TransferIntent) -> Result<String> {
+            Ok("https".into())
+        }
+
+        async fn send_quic(&self, _intent: TransferIntent) -> Result<String> {
+            Err((self.error)())
+        }
+
+        async fn accept(&self, _request_id: &str, _save_dir: PathBuf) -> Result<()> {
+            Ok(())
+        }
+
+        async fn reject(&self, _request_id: &str) -> Result<()> {
+            Ok(())
+        }
+
+        async fn cancel(&self, _transfer_id: &str) -> Result<()> {
+            Ok(())
+        }
+
+        fn subscribe(&self) -> mpsc::Receiver<Event> {
+            mpsc::channel(1).1
+        }
+    }
+
+    struct QuicFailingFactory(fn() -> unidrop_core::Error);
+
+    impl ProtocolFactory for QuicFailingFactory {
+        fn create(&self) -> Arc<dyn Protocol> {
+            Arc::new(QuicFailing {
+                info: self.info(),
+                error: self.0,
+            })
+        }
+
+        fn info(&self) -> ProtocolInfo {
+            ProtocolInfo {
+                id: ProtocolId::new("mock"),
+                name: "Mock".into(),
+                version: "1".into(),
+                description: String::new(),
+                supported: true,
+                priority: 0,
+            }
+        }
+    }
+
+    async fn send_text(error: fn() -> unidrop_core::Error) -> Result<(String, Transport)> {
+        let dir = std::env::temp_dir().join(format!("unidrop-engine-{}", uuid::Uuid::new_v4()));
+        let engine = Engine::builder()
+            .config_dir(&dir)
+            .with_protocol(QuicFailingFactory(error))
+            .build();
+        engine.start().await.unwrap();
+
+        let target = DeviceId::new(ProtocolId::new("mock"), "ABCD");
+        let result = engine
+            .send_with_transport(TransferIntent::text(target, "hello"))
+            .await;
+        let _ = std::fs::remove_dir_all(&dir);
+        result
+    }
+
+    #[test]
+    fn test_select_files() {
+        let peer = Peer::new(ProtocolId::new("mock"), "ABCD".into(), "Laptop".into());
+        let device = Device::new(peer, IpAddr::V4(Ipv4Addr::LOCALHOST), 53317);
+        let files = vec![
+            FileInfo::new("a", "a.txt", 1),
+            FileInfo::new("b", "b.txt", 1),
+        ];
+        let request = TransferRequest::new("t1", device, files);
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+
+        // 重复的 ID 不能凑满文件数而接受全部文件
+        assert_eq!(
+            select_files(&request, ids(&["a", "a"])).unwrap(),
+            ids(&["a"])
+        );
+        assert_eq!(
+            select_files(&request, ids(&["b", "a"])).unwrap(),
+            ids(&["a", "b"])
+        );
+        assert!(matches!(
+            select_files(&request, ids(&["a", "c"])),
+            Err(unidrop_core::Error::FileNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_quic_falls_back_to_default_transport() {
+        // QUIC 端口不通时改用 HTTPS
+        let dead_port = || unidrop_core::Error::Connection("timed out".into());
+        let (session_id, transport) = send_text(dead_port).await.unwrap();
+        assert_eq!(session_id, "https");
+        assert_eq!(transport, Transport::Default);
+
+        // 对端已经收到请求时不回退
+        let rejected = || unidrop_core::Error::Rejected;
+        assert!(matches!(
+            send_text(rejected).await,
+            Err(unidrop_core::Error::Rejected)
+        ));
+        let pin = || unidrop_core::Error::PinRequired("Laptop".into());
+        assert!(matches!(
+            send_text(pin).await,
+            Err(unidrop_core::Error::PinRequired(_))
+        ));
+    }
+}
@@ -8,9 +8,13 @@
 //! - 事件聚合（统一分发各协议事件）
 
 mod engine;
+mod pending;
 mod registry;
 mod router;
+mod trust;
 
-pub use engine::{Engine, EngineBuilder, EngineConfig};
+pub use engine::{Engine, EngineBuilder, EngineConfig, Transport};
+pub use pending::PENDING_REQUEST_TTL;
 pub use registry::ProtocolRegistry;
 pub use router::TransferRouter;
+pub use trust::{TrustStore, TrustedDevice};
@@ -0,0 +1,106 @@
+//! 待处理请求 - 等待用户确认的入站传输请求
+
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use unidrop_core::TransferRequest;
+
+/// 请求等待确认的最长时间，与协议端的等待时间一致
+pub const PENDING_REQUEST_TTL: Duration = Duration::from_secs(60);
+
+struct PendingRequest {
+    request: TransferRequest,
+    expires_at: Instant,
+}
+
+/// 待处理请求注册表
+#[derive(Default)]
+pub struct PendingRequests {
+    requests: RwLock<HashMap<String, PendingRequest>>,
+}
+
+impl PendingRequests {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记请求
+    pub fn insert(&self, request: TransferRequest, ttl: Duration) {
+        self.requests.write().insert(
+            request.id.clone(),
+            PendingRequest {
+                request,
+                expires_at: Instant::now() + ttl,
+            },
+        );
+    }
+
+    /// 取出未过期的请求
+    pub fn take(&self, request_id: &str) -> Option<TransferRequest> {
+        let pending = self.requests.write().remove(request_id)?;
+        (pending.expires_at > Instant::now()).then_some(pending.request)
+    }
+
+    /// 查看未过期的请求（不取出）
+    pub fn get(&self, request_id: &str) -> Option<TransferRequest> {
+        let requests = self.requests.read();
+        let pending = requests.get(request_id)?;
+        (pending.expires_at > Instant::now()).then(|| pending.request.clone())
+    }
+
+    /// 取出已过期的请求（未过期时保留）
+    pub fn take_expired(&self, request_id: &str) -> Option<TransferRequest> {
+        let mut requests = self.requests.write();
+        if requests.get(request_id)?.expires_at > Instant::now() {
+            return None;
+        }
+        requests.remove(request_id).map(|p| p.request)
+    }
+
+    /// 移除请求（传输已在协议层结束时）
+    pub fn remove(&self, request_id: &str) {
+        self.requests.write().remove(request_id);
+    }
+
+    /// 所有未过期的请求
+    pub fn list(&self) -> Vec<TransferRequest> {
+        let now = Instant::now();
+        self.requests
+            .read()
+            .values()
+            .filter(|p| p.expires_at > now)
+            .map(|p| p.request.clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+    use unidrop_core::{Device, Peer, ProtocolId};
+
+    fn request(id: &str) -> TransferRequest {
+        let peer = Peer::new(
+            ProtocolId::new("localsend"),
+            "ABCD".to_string(),
+            "Laptop".to_string(),
+        );
+        let device = Device::new(peer, IpAddr::V4(Ipv4Addr::LOCALHOST), 53317);
+        TransferRequest::new(id, device, Vec::new())
+    }
+
+    #[test]
+    fn test_pending_expiry() {
+        let pending = PendingRequests::new();
+        pending.insert(request("live"), PENDING_REQUEST_TTL);
+        pending.insert(request("stale"), Duration::ZERO);
+
+        assert_eq!(pending.list().len(), 1);
+        assert!(pending.take_expired("live").is_none());
+        assert!(pending.take("stale").is_none());
+        assert!(pending.take("live").is_some());
+        assert!(pending.take("live").is_none());
+    }
+}
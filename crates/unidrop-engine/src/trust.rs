@@ -0,0 +1,149 @@
+//! 可信设备 - 持久化的可信设备列表
+//!
+//! 配合 `AcceptPolicy::AutoAcceptTrusted` 使用：来自可信设备且身份已验证的传输请求自动接收。
+
+use parking_lot::RwLock;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use tracing::{info, warn};
+
+use unidrop_core::{Device, DeviceId, Result};
+
+/// 配置目录下的可信设备文件
+pub const TRUSTED_DEVICES_FILE: &str = "trusted_devices.json";
+
+/// 可信设备记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrustedDevice {
+    /// 设备 ID（协议 + 指纹）
+    pub id: DeviceId,
+    /// 信任时的设备名称
+    pub name: String,
+    /// 信任时间（Unix 毫秒）
+    pub trusted_at: u64,
+}
+
+/// 可信设备存储
+pub struct TrustStore {
+    path: Option<PathBuf>,
+    devices: RwLock<HashMap<DeviceId, TrustedDevice>>,
+}
+
+impl TrustStore {
+    /// 仅保存在内存中
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            devices: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// 从文件加载，文件不存在时为空
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let devices = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice::<Vec<TrustedDevice>>(&data)
+                .unwrap_or_else(|e| {
+                    warn!("Ignoring invalid trusted devices file {:?}: {}", path, e);
+                    Vec::new()
+                })
+                .into_iter()
+                .map(|d| (d.id.clone(), d))
+                .collect(),
+            Err(_) => HashMap::new(),
+        };
+
+        Self {
+            path: Some(path),
+            devices: RwLock::new(devices),
+        }
+    }
+
+    /// 信任设备
+    pub fn trust(&self, device: &Device) -> Result<()> {
+        let trusted = TrustedDevice {
+            id: device.id().clone(),
+            name: device.name().to_string(),
+            trusted_at: now_ms(),
+        };
+        self.devices.write().insert(trusted.id.clone(), trusted);
+        info!("Trusted device: {} ({})", device.name(), device.id());
+        self.save()
+    }
+
+    /// 取消信任，返回设备之前是否可信
+    pub fn untrust(&self, id: &DeviceId) -> Result<bool> {
+        let removed = self.devices.write().remove(id).is_some();
+        if removed {
+            info!("Untrusted device: {}", id);
+            self.save()?;
+        }
+        Ok(removed)
+    }
+
+    /// 设备是否可信
+    pub fn is_trusted(&self, id: &DeviceId) -> bool {
+        self.devices.read().contains_key(id)
+    }
+
+    /// 所有可信设备
+    pub fn list(&self) -> Vec<TrustedDevice> {
+        let mut devices: Vec<_> = self.devices.read().values().cloned().collect();
+        devices.sort_by_key(|d| d.trusted_at);
+        devices
+    }
+
+    fn save(&self) -> Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_vec_pretty(&self.list())
+            .map_err(|e| unidrop_core::Error::Internal(e.to_string()))?;
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+}
+
+fn now_ms() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+    use unidrop_core::{Peer, ProtocolId};
+
+    #[test]
+    fn test_trust_persists() {
+        let dir = std::env::temp_dir().join(format!("unidrop-trust-{}", uuid::Uuid::new_v4()));
+        let path = dir.join(TRUSTED_DEVICES_FILE);
+
+        let peer = Peer::new(
+            ProtocolId::new("localsend"),
+            "ABCD".to_string(),
+            "Laptop".to_string(),
+        );
+        let device = Device::new(peer, IpAddr::V4(Ipv4Addr::LOCALHOST), 53317);
+
+        let store = TrustStore::load(&path);
+        store.trust(&device).unwrap();
+        assert!(store.is_trusted(device.id()));
+
+        let reloaded = TrustStore::load(&path);
+        assert!(reloaded.is_trusted(device.id()));
+        assert_eq!(reloaded.list()[0].name, "Laptop");
+
+        assert!(reloaded.untrust(device.id()).unwrap());
+        assert!(!TrustStore::load(&path).is_trusted(device.id()));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -62,6 +62,8 @@ pub enum FfiEvent {
     DeviceDiscovered { device: FfiDevice },
     /// 设备离线
     DeviceLost { device_id: String },
+    /// 配对成功，双方已互相信任
+    DevicePaired { device: FfiDevice },
     /// 收到传输请求
     TransferRequested { request: FfiTransferRequest },
     /// 传输进度
@@ -74,6 +76,13 @@ pub enum FfiEvent {
     Error { message: String },
 }
 
+/// 配对邀请：`uri` 用于生成二维码
+#[frb(dart_metadata=("freezed"))]
+pub struct FfiPairingInvite {
+    pub code: String,
+    pub uri: String,
+}
+
 /// 本机信息
 #[frb(dart_metadata=("freezed"))]
 pub struct FfiLocalInfo {
@@ -111,7 +120,10 @@ fn get_engine() -> Option<Arc<Engine>> {
 
 /// 初始化 UniDrop 引擎
 #[frb(sync)]
-pub fn init_engine(device_name: Option<String>, save_dir: Option<String>) -> Result<FfiLocalInfo, String> {
+pub fn init_engine(
+    device_name: Option<String>,
+    save_dir: Option<String>,
+) -> Result<FfiLocalInfo, String> {
     // 初始化 tracing
     let _ = tracing_subscriber::fmt()
         .with_max_level(tracing::Level::INFO)
@@ -142,6 +154,7 @@ pub fn init_engine(device_name: Option<String>, save_dir: Option<String>) -> Res
         save_dir: dir.clone(),
         encryption: true,
         pin: None,
+        ..Default::default()
     };
 
     let engine = Engine::builder()
@@ -213,19 +226,131 @@ pub async fn send_files(device_id: String, file_paths: Vec<String>) -> Result<St
     engine.send(intent).await.map_err(|e| e.to_string())
 }
 
+/// 携带 PIN 发送文件
+///
+/// 接收方要求 PIN 时 `send_files` 返回 "PIN required by ..."，界面提示输入后用此函数重试。
+pub async fn send_files_with_pin(
+    device_id: String,
+    file_paths: Vec<String>,
+    pin: String,
+) -> Result<String, String> {
+    let engine = get_engine().ok_or("Engine not initialized")?;
+
+    let devices = engine.devices().await;
+    let device = devices
+        .iter()
+        .find(|d| d.id().to_string() == device_id)
+        .ok_or("Device not found")?;
+
+    let files: Vec<PathBuf> = file_paths.into_iter().map(PathBuf::from).collect();
+    let intent = unidrop_core::TransferIntent::new(device.id().clone(), files).with_pin(pin);
+
+    engine.send(intent).await.map_err(|e| e.to_string())
+}
+
 /// 接受传输请求
+///
+/// 请求 ID 在引擎内唯一，`protocol` 仅为兼容保留。
 pub async fn accept_transfer(request_id: String, protocol: String) -> Result<(), String> {
     let engine = get_engine().ok_or("Engine not initialized")?;
 
-    // 这里需要找到对应的 TransferRequest
-    // 简化处理，实际需要维护 pending requests
-    Err("Not implemented yet".to_string())
+    engine
+        .accept_by_id(&request_id, None, None)
+        .await
+        .map_err(|e| e.to_string())
 }
 
 /// 拒绝传输请求
 pub async fn reject_transfer(request_id: String, protocol: String) -> Result<(), String> {
     let engine = get_engine().ok_or("Engine not initialized")?;
-    Err("Not implemented yet".to_string())
+
+    engine
+        .reject_by_id(&request_id)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 取消进行中的传输（发送或接收）
+pub async fn cancel_transfer(transfer_id: String, protocol: String) -> Result<(), String> {
+    let engine = get_engine().ok_or("Engine not initialized")?;
+
+    engine
+        .cancel(&transfer_id, &unidrop_core::ProtocolId::new(protocol))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 续传失败的发送，返回续传的传输 ID
+pub async fn resume_transfer(transfer_id: String) -> Result<String, String> {
+    let engine = get_engine().ok_or("Engine not initialized")?;
+
+    engine.resume(&transfer_id).await.map_err(|e| e.to_string())
+}
+
+/// 开始接受配对，返回本机的短码和配对链接
+pub async fn start_pairing() -> Result<FfiPairingInvite, String> {
+    let engine = get_engine().ok_or("Engine not initialized")?;
+
+    let protocol = unidrop_core::ProtocolId::new(unidrop_protocol_localsend::PROTOCOL_ID);
+    let invite = engine
+        .start_pairing(&protocol)
+        .await
+        .map_err(|e| e.to_string())?;
+
+    Ok(FfiPairingInvite {
+        uri: invite.to_uri(),
+        code: invite.code,
+    })
+}
+
+/// 停止接受配对
+pub async fn stop_pairing() -> Result<(), String> {
+    let engine = get_engine().ok_or("Engine not initialized")?;
+
+    let protocol = unidrop_core::ProtocolId::new(unidrop_protocol_localsend::PROTOCOL_ID);
+    engine
+        .stop_pairing(&protocol)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 输入对端显示的短码与其配对
+pub async fn pair_device(device_id: String, code: String) -> Result<FfiDevice, String> {
+    let engine = get_engine().ok_or("Engine not initialized")?;
+
+    let devices = engine.devices().await;
+    let device = devices
+        .iter()
+        .find(|d| d.id().to_string() == device_id)
+        .ok_or("Device not found")?;
+
+    let paired = engine
+        .pair(device.id(), &code)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(to_ffi_device(&paired))
+}
+
+/// 使用扫描二维码得到的配对链接与对端配对
+pub async fn pair_with_link(link: String) -> Result<FfiDevice, String> {
+    let engine = get_engine().ok_or("Engine not initialized")?;
+
+    let invite = unidrop_core::PairingInvite::parse(&link).map_err(|e| e.to_string())?;
+    let paired = engine
+        .pair_with_invite(&invite)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(to_ffi_device(&paired))
+}
+
+fn to_ffi_device(device: &unidrop_core::Device) -> FfiDevice {
+    FfiDevice {
+        id: device.id().to_string(),
+        name: device.name().to_string(),
+        device_type: format!("{:?}", device.peer.device_type),
+        address: device.address(),
+        protocol: device.protocol().to_string(),
+    }
 }
 
 /// 订阅事件流 - 通过回调函数接收事件
@@ -258,6 +383,9 @@ fn convert_event(event: Event) -> Option<FfiEvent> {
         EventKind::DeviceLost(id) => Some(FfiEvent::DeviceLost {
             device_id: id.to_string(),
         }),
+        EventKind::DevicePaired(device) => Some(FfiEvent::DevicePaired {
+            device: to_ffi_device(&device),
+        }),
         EventKind::TransferRequested(request) => Some(FfiEvent::TransferRequested {
             request: FfiTransferRequest {
                 request_id: request.id.clone(),
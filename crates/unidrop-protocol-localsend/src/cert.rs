@@ -1,7 +1,18 @@
-//! TLS 证书生成
+//! TLS 证书生成与持久化
 
 use rcgen::{CertificateParams, KeyPair};
 use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use unidrop_core::IdentityStore;
+
+/// 身份存储中的证书文件名
+const CERT_FILE: &str = "localsend.cert.der";
+/// 身份存储中的私钥文件名
+const KEY_FILE: &str = "localsend.key.der";
+
+/// 证书通用名
+const COMMON_NAME: &str = "UniDrop";
 
 /// 证书信息
 #[derive(Clone)]
@@ -27,26 +38,50 @@ pub fn generate_self_signed(common_name: &str) -> Result<CertInfo, rcgen::Error>
 
     let cert = params.self_signed(&key_pair)?;
 
-    let cert_der = cert.der().to_vec();
-    let key_der = key_pair.serialize_der();
-
-    // 计算指纹 (SHA-256)
-    let hash = Sha256::digest(&cert_der);
-    let fingerprint = hash
-        .iter()
-        .map(|b| format!("{:02X}", b))
-        .collect::<Vec<_>>()
-        .join(":");
-
-    // 设备 ID：取指纹前 32 个字符（不含冒号）
-    let device_id = fingerprint.replace(':', "").chars().take(32).collect();
-
-    Ok(CertInfo {
-        cert_der,
-        key_der,
-        fingerprint,
-        device_id,
-    })
+    Ok(CertInfo::from_der(
+        cert.der().to_vec(),
+        key_pair.serialize_der(),
+    ))
+}
+
+/// 从身份存储加载证书，不存在或损坏时生成新证书并保存
+pub fn load_or_create(store: &IdentityStore) -> unidrop_core::Result<CertInfo> {
+    if let (Some(cert_der), Some(key_der)) = (store.load(CERT_FILE)?, store.load(KEY_FILE)?) {
+        if KeyPair::try_from(key_der.as_slice()).is_ok() {
+            return Ok(CertInfo::from_der(cert_der, key_der));
+        }
+        warn!("Stored LocalSend key is invalid, generating a new certificate");
+    }
+
+    let info = generate_self_signed(COMMON_NAME).map_err(|e| {
+        unidrop_core::Error::Internal(format!("Certificate generation failed: {}", e))
+    })?;
+    store.save(KEY_FILE, &info.key_der)?;
+    store.save(CERT_FILE, &info.cert_der)?;
+    Ok(info)
+}
+
+impl CertInfo {
+    /// 由 DER 编码的证书和私钥构造，计算指纹和设备 ID
+    pub fn from_der(cert_der: Vec<u8>, key_der: Vec<u8>) -> Self {
+        // 计算指纹 (SHA-256)
+        let hash = Sha256::digest(&cert_der);
+        let fingerprint = hash
+            .iter()
+            .map(|b| format!("{:02X}", b))
+            .collect::<Vec<_>>()
+            .join(":");
+
+        // 设备 ID：取指纹前 32 个字符（不含冒号）
+        let device_id = fingerprint.replace(':', "").chars().take(32).collect();
+
+        Self {
+            cert_der,
+            key_der,
+            fingerprint,
+            device_id,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -61,4 +96,21 @@ mod tests {
         assert!(!info.fingerprint.is_empty());
         assert_eq!(info.device_id.len(), 32);
     }
+
+    #[test]
+    fn test_load_or_create_is_stable() {
+        let dir = std::env::temp_dir().join(format!("unidrop-cert-{}", uuid::Uuid::new_v4()));
+        let store = IdentityStore::new(&dir);
+
+        let first = load_or_create(&store).unwrap();
+        let second = load_or_create(&store).unwrap();
+        assert_eq!(first.fingerprint, second.fingerprint);
+        assert_eq!(first.device_id, second.device_id);
+
+        store.clear().unwrap();
+        let rotated = load_or_create(&store).unwrap();
+        assert_ne!(first.fingerprint, rotated.fingerprint);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
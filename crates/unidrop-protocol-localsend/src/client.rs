@@ -1,79 +1,460 @@
 //! LocalSend HTTP 客户端 - 发送文件
 
+use futures::StreamExt;
+use parking_lot::Mutex;
 use reqwest::Client;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
 use tokio::fs::File;
-use tokio::io::AsyncReadExt;
-use tracing::{debug, info};
+use tokio::io::AsyncWriteExt;
+use tokio::sync::mpsc;
+use tokio_util::io::ReaderStream;
+use tracing::{debug, info, warn};
+
+use unidrop_core::{
+    pairing, resolve_save_path, CancelRegistry, CancellationToken, CollisionPolicy, Device, Event,
+    FileHasher, ProgressTracker, Result, ResumableSend, ResumeRegistry, TransferIntent,
+    TransferProgress, TransferSource, MAX_MESSAGE_LEN,
+};
+
+use crate::cert::CertInfo;
+use crate::devices::DeviceTable;
+use crate::models::*;
+use crate::pinning::{PinStore, PinnedVerifier};
+use crate::server::{CANCELLED_STATUS, HASH_MISMATCH_STATUS};
 
-use unidrop_core::{Device, Result};
+/// 上传时每次读取的块大小
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
 
-use crate::models::*;
+/// 通知对端取消的超时时间
+const CANCEL_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 探测设备信息的超时时间
+const INFO_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// 扫描时注册请求的超时时间
+const REGISTER_TIMEOUT: Duration = Duration::from_secs(2);
 
 /// HTTP 客户端
 #[derive(Clone)]
 pub struct HttpClient {
-    http: Client,
     local_info: DeviceInfo,
+    pins: Arc<PinStore>,
+    /// 已发现设备表，用于查询对端使用 http 还是 https
+    devices: Arc<DeviceTable>,
+    transfers: CancelRegistry,
+    resumable: ResumeRegistry,
+    /// 出示给对端的客户端证书
+    identity: Option<CertInfo>,
+    event_tx: mpsc::Sender<Event>,
 }
 
 impl HttpClient {
-    pub fn new(local_info: DeviceInfo) -> Self {
+    pub fn new(local_info: DeviceInfo, pins: Arc<PinStore>, event_tx: mpsc::Sender<Event>) -> Self {
+        Self {
+            local_info,
+            pins,
+            devices: Arc::new(DeviceTable::new()),
+            transfers: CancelRegistry::new(),
+            resumable: ResumeRegistry::new(),
+            identity: None,
+            event_tx,
+        }
+    }
+
+    /// 设置客户端证书（本机的持久化证书），供对端验证发送方身份
+    pub fn with_identity(mut self, cert: CertInfo) -> Self {
+        self.identity = Some(cert);
+        self
+    }
+
+    /// 设置取消令牌登记表（与协议层共享，用于取消进行中的发送）
+    pub fn with_cancel_registry(mut self, transfers: CancelRegistry) -> Self {
+        self.transfers = transfers;
+        self
+    }
+
+    /// 设置失败发送的登记表（与协议层共享，用于续传）
+    pub fn with_resume_registry(mut self, resumable: ResumeRegistry) -> Self {
+        self.resumable = resumable;
+        self
+    }
+
+    /// 设置设备表（与发现服务共享），按对端声明选择 http 或 https
+    pub fn with_devices(mut self, devices: Arc<DeviceTable>) -> Self {
+        self.devices = devices;
+        self
+    }
+
+    /// 目标设备的 API 地址
+    fn base_url(&self, target: &Device) -> String {
+        let scheme = self.devices.scheme(&target.peer.id.fingerprint);
+        format!(
+            "{}://{}:{}/api/localsend/v2",
+            scheme.as_str(),
+            target.ip,
+            target.port
+        )
+    }
+
+    /// 为目标设备创建 HTTP 客户端
+    ///
+    /// LocalSend 使用自签名证书，因此不走 CA 校验，
+    /// 而是校验证书与设备指纹一致（首次连接时固定）。同时出示本机证书。
+    fn client_for(&self, target: &Device) -> Result<(Client, Arc<PinnedVerifier>)> {
+        let fingerprint = &target.peer.id.fingerprint;
+        let verifier = PinnedVerifier::new(fingerprint, self.pins.get(fingerprint));
+
         let http = Client::builder()
-            .danger_accept_invalid_certs(true) // LocalSend 使用自签名证书
+            .use_preconfigured_tls(verifier.client_config(self.identity.as_ref())?)
             .build()
-            .expect("Failed to create HTTP client");
+            .map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
 
-        Self { http, local_info }
+        Ok((http, verifier))
+    }
+
+    /// 获取设备信息（也用于判断设备是否在线）
+    pub async fn info(&self, target: &Device) -> Result<DeviceInfo> {
+        let url = format!("{}/info", self.base_url(target));
+
+        let (http, verifier) = self.client_for(target)?;
+        let response = http
+            .get(&url)
+            .timeout(INFO_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| {
+                verifier
+                    .mismatch_error()
+                    .unwrap_or_else(|| unidrop_core::Error::Network(e.to_string()))
+            })?;
+
+        if !response.status().is_success() {
+            return Err(unidrop_core::Error::Network(format!(
+                "Info failed: {}",
+                response.status()
+            )));
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| unidrop_core::Error::Protocol(e.to_string()))
+    }
+
+    /// 向指定地址注册自己，返回对方的设备信息（用于主动扫描）
+    ///
+    /// 对方指纹事先未知，因此握手时接受任意证书，拿到设备信息后再按指纹校验。
+    pub async fn register(&self, ip: IpAddr, port: u16, scheme: Scheme) -> Result<DeviceInfo> {
+        let url = format!(
+            "{}://{}:{}/api/localsend/v2/register",
+            scheme.as_str(),
+            ip,
+            port
+        );
+
+        let verifier = PinnedVerifier::new("", None);
+        let http = Client::builder()
+            .use_preconfigured_tls(verifier.client_config(self.identity.as_ref())?)
+            .build()
+            .map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
+
+        let response = http
+            .post(&url)
+            .json(&self.local_info)
+            .timeout(REGISTER_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
+
+        if !response.status().is_success() {
+            return Err(unidrop_core::Error::Network(format!(
+                "Register failed: {}",
+                response.status()
+            )));
+        }
+
+        let info: DeviceInfo = response
+            .json()
+            .await
+            .map_err(|e| unidrop_core::Error::Protocol(e.to_string()))?;
+
+        if scheme == Scheme::Http {
+            return Ok(info);
+        }
+
+        let presented = verifier.presented().unwrap_or_default();
+        let expected = PinnedVerifier::new(&info.fingerprint, self.pins.get(&info.fingerprint));
+        if !expected.is_trusted(&presented) {
+            return Err(unidrop_core::Error::CertificateMismatch(format!(
+                "{} presented {}",
+                info.fingerprint, presented
+            )));
+        }
+
+        Ok(info)
     }
 
     /// 发送文件到设备
-    pub async fn send_files(&self, target: &Device, files: Vec<PathBuf>) -> Result<String> {
-        let base_url = format!("https://{}:{}/api/localsend/v2", target.ip, target.port);
+    ///
+    /// 对端要求 PIN 时返回 `Error::PinRequired`，带上 `pin` 重试即可。
+    pub async fn send_files(
+        &self,
+        target: &Device,
+        sources: Vec<TransferSource>,
+        pin: Option<&str>,
+    ) -> Result<String> {
+        self.upload_session(target, sources, None, pin).await
+    }
+
+    /// 续传失败的发送（UniDrop 扩展），返回新的会话 ID
+    ///
+    /// `part_key` 为接收方保存未完成文件所用的会话 ID。
+    pub async fn resume(
+        &self,
+        target: &Device,
+        part_key: &str,
+        sources: Vec<TransferSource>,
+        pin: Option<&str>,
+    ) -> Result<String> {
+        self.upload_session(target, sources, Some(part_key), pin)
+            .await
+    }
+
+    async fn upload_session(
+        &self,
+        target: &Device,
+        sources: Vec<TransferSource>,
+        part_key: Option<&str>,
+        pin: Option<&str>,
+    ) -> Result<String> {
+        let base_url = self.base_url(target);
+        let (http, verifier) = self.client_for(target)?;
 
         // 1. 构建文件信息
+        let sources =
+            futures::future::try_join_all(sources.into_iter().map(TransferSource::sized)).await?;
         let mut file_infos = HashMap::new();
-        for (idx, path) in files.iter().enumerate() {
-            let metadata = tokio::fs::metadata(path).await?;
-            let file_name = path
-                .file_name()
-                .and_then(|n| n.to_str())
-                .unwrap_or("unknown")
-                .to_string();
-
+        for (idx, source) in sources.iter().enumerate() {
             let file_id = format!("file_{}", idx);
             file_infos.insert(
                 file_id.clone(),
                 FileInfo {
                     id: file_id,
-                    file_name,
-                    size: metadata.len(),
-                    file_type: guess_mime_type(path),
-                    sha256: None,
+                    file_name: source.name.clone(),
+                    size: source.size().unwrap_or(0),
+                    file_type: source.mime_type.clone(),
+                    sha256: source.hash().map(str::to_string),
                     preview: None,
                 },
             );
         }
 
         // 2. 准备上传
+        let prepare_response = self
+            .prepare_upload(
+                &http,
+                &verifier,
+                &base_url,
+                target,
+                file_infos.clone(),
+                (part_key, pin),
+            )
+            .await?
+            .ok_or_else(|| {
+                unidrop_core::Error::Protocol("Unexpected empty prepare response".into())
+            })?;
+
+        let session_id = prepare_response.session_id.clone();
+        info!("Upload session created: {}", session_id);
+
+        // 3. 上传每个文件
+        let total_size = file_infos.values().map(|f| f.size).sum();
+        let tracker = Arc::new(Mutex::new(ProgressTracker::new(
+            &session_id,
+            total_size,
+            sources.len(),
+        )));
+
+        let cancel = self.transfers.register(&session_id);
+        let upload = self.upload_files(&http, &base_url, &prepare_response, &sources, &tracker);
+        tokio::pin!(upload);
+
+        let result = tokio::select! {
+            result = &mut upload => result,
+            _ = cancel.cancelled() => {
+                // 上传暂停期间先通知对端，让接收方按取消而不是失败处理
+                if let Err(e) = self.cancel(target, &session_id).await {
+                    warn!("Failed to notify {} of cancellation: {}", target.name(), e);
+                }
+                Err(unidrop_core::Error::Cancelled)
+            }
+        };
+        self.transfers.remove(&session_id);
+
+        match &result {
+            Ok(()) => {
+                info!("All files sent successfully");
+                let progress = tracker.lock().complete();
+                self.emit(Event::transfer_progress(progress)).await;
+                self.emit(Event::transfer_completed(&session_id)).await;
+            }
+            Err(e @ unidrop_core::Error::Cancelled) => {
+                info!("Upload session {} cancelled", session_id);
+                let progress = tracker.lock().cancel();
+                self.emit(Event::transfer_progress(progress)).await;
+                self.emit(Event::transfer_failed(&session_id, e.to_string()))
+                    .await;
+            }
+            Err(e) => {
+                let progress = tracker.lock().fail(e.to_string());
+                self.emit(Event::transfer_progress(progress)).await;
+                self.emit(Event::transfer_failed(&session_id, e.to_string()))
+                    .await;
+
+                // 中断的传输登记后可以续传，接收方的未完成文件始终属于最初的会话
+                let mut intent = TransferIntent::from_sources(target.id().clone(), sources.clone());
+                intent.pin = pin.map(str::to_string);
+                let send = ResumableSend {
+                    session_id: part_key.unwrap_or(&session_id).to_string(),
+                    intent,
+                };
+                self.resumable.insert(&session_id, send);
+            }
+        }
+
+        result.map(|_| session_id)
+    }
+
+    /// 发送文本消息
+    ///
+    /// 与官方 LocalSend 一致，以带预览的纯文本文件发送。对端按消息处理时
+    /// 不需要上传，否则照常上传文本内容。
+    pub async fn send_text(
+        &self,
+        target: &Device,
+        text: &str,
+        pin: Option<&str>,
+    ) -> Result<String> {
+        if text.len() > MAX_MESSAGE_LEN {
+            return Err(unidrop_core::Error::Protocol(format!(
+                "Text message exceeds {} bytes",
+                MAX_MESSAGE_LEN
+            )));
+        }
+
+        let base_url = self.base_url(target);
+        let (http, verifier) = self.client_for(target)?;
+
+        let file_id = "file_0".to_string();
+        let file = FileInfo {
+            id: file_id.clone(),
+            file_name: format!("{}.txt", uuid::Uuid::new_v4()),
+            size: text.len() as u64,
+            file_type: "text/plain".to_string(),
+            sha256: None,
+            preview: Some(text.to_string()),
+        };
+
+        let Some(prepare_response) = self
+            .prepare_upload(
+                &http,
+                &verifier,
+                &base_url,
+                target,
+                HashMap::from([(file_id.clone(), file)]),
+                (None, pin),
+            )
+            .await?
+        else {
+            let transfer_id = uuid::Uuid::new_v4().to_string();
+            info!("Message delivered to {}", target.name());
+            self.emit(Event::transfer_completed(&transfer_id)).await;
+            return Ok(transfer_id);
+        };
+
+        // 对端把文本当作文件接收
+        let session_id = prepare_response.session_id;
+        if let Some(token) = prepare_response.files.get(&file_id) {
+            let response = http
+                .post(format!("{}/upload", base_url))
+                .query(&[
+                    ("sessionId", session_id.as_str()),
+                    ("fileId", file_id.as_str()),
+                    ("token", token.as_str()),
+                ])
+                .header(reqwest::header::CONTENT_TYPE, "text/plain")
+                .body(text.to_string())
+                .send()
+                .await
+                .map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
+
+            if !response.status().is_success() {
+                let e = unidrop_core::Error::TransferFailed(format!(
+                    "Upload failed: {}",
+                    response.status()
+                ));
+                self.emit(Event::transfer_failed(&session_id, e.to_string()))
+                    .await;
+                return Err(e);
+            }
+        }
+
+        info!("Text sent to {} as a file", target.name());
+        self.emit(Event::transfer_completed(&session_id)).await;
+        Ok(session_id)
+    }
+
+    /// 发起上传请求，对端无需上传（204，如文本消息）时返回 None
+    ///
+    /// 续传时通过 [`RESUME_HEADER`] 携带最初的会话 ID，对端要求 PIN 时以 `?pin=` 提供。
+    async fn prepare_upload(
+        &self,
+        http: &Client,
+        verifier: &PinnedVerifier,
+        base_url: &str,
+        target: &Device,
+        files: HashMap<String, FileInfo>,
+        (part_key, pin): (Option<&str>, Option<&str>),
+    ) -> Result<Option<PrepareUploadResponse>> {
         let prepare_request = PrepareUploadRequest {
             info: self.local_info.clone(),
-            files: file_infos.clone(),
+            files,
         };
 
         info!("Preparing upload to {}", target.name());
 
-        let response = self
-            .http
+        let mut request = http
             .post(format!("{}/prepare-upload", base_url))
-            .json(&prepare_request)
-            .send()
-            .await
-            .map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
+            .json(&prepare_request);
+        if let Some(part_key) = part_key {
+            request = request.header(RESUME_HEADER, part_key);
+        }
+        if let Some(pin) = pin {
+            request = request.query(&[("pin", pin)]);
+        }
+        let response = request.send().await.map_err(|e| {
+            verifier
+                .mismatch_error()
+                .unwrap_or_else(|| unidrop_core::Error::Network(e.to_string()))
+        })?;
+
+        if let Err(e) = verifier.pin_on_first_use(&self.pins) {
+            warn!("Failed to pin certificate of {}: {}", target.name(), e);
+        }
 
-        if response.status() == reqwest::StatusCode::FORBIDDEN {
-            return Err(unidrop_core::Error::Rejected);
+        match response.status() {
+            reqwest::StatusCode::FORBIDDEN => return Err(unidrop_core::Error::Rejected),
+            reqwest::StatusCode::UNAUTHORIZED => {
+                return Err(unidrop_core::Error::PinRequired(target.name().to_string()));
+            }
+            reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                return Err(unidrop_core::Error::PinLockedOut(target.name().to_string()));
+            }
+            _ => {}
         }
 
         if !response.status().is_success() {
@@ -83,92 +464,420 @@ impl HttpClient {
             )));
         }
 
-        let prepare_response: PrepareUploadResponse = response
+        if response.status() == reqwest::StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+
+        response
             .json()
             .await
-            .map_err(|e| unidrop_core::Error::Protocol(e.to_string()))?;
-
-        let session_id = prepare_response.session_id.clone();
-        info!("Upload session created: {}", session_id);
+            .map(Some)
+            .map_err(|e| unidrop_core::Error::Protocol(e.to_string()))
+    }
 
-        // 3. 上传每个文件
-        for (idx, path) in files.iter().enumerate() {
+    /// 依次上传会话中的所有文件
+    async fn upload_files(
+        &self,
+        http: &Client,
+        base_url: &str,
+        prepare_response: &PrepareUploadResponse,
+        sources: &[TransferSource],
+        tracker: &Arc<Mutex<ProgressTracker>>,
+    ) -> Result<()> {
+        for (idx, source) in sources.iter().enumerate() {
             let file_id = format!("file_{}", idx);
-            let token = prepare_response
-                .files
-                .get(&file_id)
-                .ok_or_else(|| unidrop_core::Error::Protocol("Missing token".into()))?;
+            // 接收方未返回 token 的文件不需要上传（例如已存在被跳过）
+            let Some(token) = prepare_response.files.get(&file_id) else {
+                debug!("Receiver skipped {}", source.name);
+                tracker.lock().skip_file(source.size().unwrap_or(0));
+                continue;
+            };
+
+            let url = format!(
+                "{}/upload?sessionId={}&fileId={}&token={}",
+                base_url, prepare_response.session_id, file_id, token
+            );
 
-            self.upload_file(&base_url, &session_id, &file_id, token, path)
+            let size = source.size().unwrap_or(0);
+            let offset = prepare_response
+                .offsets
+                .get(&file_id)
+                .copied()
+                .unwrap_or(0)
+                .min(size);
+            {
+                let mut tracker = tracker.lock();
+                tracker.start_file(&file_id);
+                tracker.resume(offset);
+            }
+            self.upload_file(http, &url, source, offset, tracker)
                 .await?;
+
+            let progress = tracker.lock().finish_file();
+            self.emit(Event::transfer_progress(progress)).await;
         }
 
-        info!("All files sent successfully");
-        Ok(session_id)
+        Ok(())
     }
 
-    /// 上传单个文件
+    /// 上传单个文件，从 `offset` 处开始（续传）
     async fn upload_file(
         &self,
-        base_url: &str,
-        session_id: &str,
-        file_id: &str,
-        token: &str,
-        path: &PathBuf,
+        http: &Client,
+        url: &str,
+        source: &TransferSource,
+        offset: u64,
+        tracker: &Arc<Mutex<ProgressTracker>>,
     ) -> Result<()> {
-        let url = format!(
-            "{}/upload?sessionId={}&fileId={}&token={}",
-            base_url, session_id, file_id, token
+        debug!("Uploading file: {} from {}", source.name, offset);
+
+        // 以原始请求体流式上传（与官方 LocalSend 一致），不把整个文件读入内存
+        let reader = source.open_at(offset).await?;
+        let size = source.size().unwrap_or(0) - offset;
+
+        let tracker = tracker.clone();
+        let event_tx = self.event_tx.clone();
+        let stream = ReaderStream::with_capacity(reader, UPLOAD_CHUNK_SIZE).inspect(move |chunk| {
+            if let Ok(chunk) = chunk {
+                if let Some(progress) = tracker.lock().advance(chunk.len() as u64) {
+                    let _ = event_tx.try_send(
+                        Event::transfer_progress(progress).with_protocol(crate::PROTOCOL_ID),
+                    );
+                }
+            }
+        });
+        let body = reqwest::Body::wrap_stream(stream);
+
+        let mut request = http
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .header(reqwest::header::CONTENT_LENGTH, size);
+        if offset > 0 {
+            request = request.header(OFFSET_HEADER, offset);
+        }
+        let response = request
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
+
+        // 接收方取消了会话
+        if response.status() == CANCELLED_STATUS {
+            return Err(unidrop_core::Error::Cancelled);
+        }
+
+        if response.status() == HASH_MISMATCH_STATUS {
+            return Err(unidrop_core::Error::HashMismatch(source.name.clone()));
+        }
+
+        if !response.status().is_success() {
+            return Err(unidrop_core::Error::TransferFailed(format!(
+                "Upload failed: {}",
+                response.status()
+            )));
+        }
+
+        debug!("File uploaded successfully");
+        Ok(())
+    }
+
+    /// 使用对端显示的短码与其配对，返回对端的设备信息
+    ///
+    /// 先收到对端的承诺再提交本机的承诺，对端揭示的随机数与短码相符后才揭示本机的随机数，
+    /// 短码本身不经过网络，冒充的对端也无法离线穷举短码。
+    pub async fn pair(&self, target: &Device, code: &str) -> Result<DeviceInfo> {
+        let fingerprint = &target.peer.id.fingerprint;
+        if self.devices.scheme(fingerprint) == Scheme::Http {
+            return Err(unidrop_core::Error::ProtocolNotSupported(format!(
+                "{} does not use HTTPS, pairing needs a verified identity",
+                target.name()
+            )));
+        }
+
+        let info = self.local_info.clone();
+        let mut attempt = pairing::PairingAttempt::new(code, &info.fingerprint, fingerprint);
+
+        let request = PairCommitRequest { info: info.clone() };
+        let response: PairCommitResponse = self.pair_step(target, "pair/commit", &request).await?;
+        let commitment = attempt.commit(&response.commitment);
+
+        let request = PairRevealRequest {
+            info: info.clone(),
+            commitment,
+        };
+        let response: PairRevealResponse = self.pair_step(target, "pair/reveal", &request).await?;
+        let nonce = attempt.reveal(&response.nonce).map_err(|_| {
+            unidrop_core::Error::PairingFailed(format!(
+                "Wrong pairing code or {} does not know it",
+                target.name()
+            ))
+        })?;
+
+        let request = PairRequest { info, nonce };
+        let response: PairResponse = self.pair_step(target, "pair", &request).await?;
+
+        info!("Paired with {}", target.name());
+        Ok(response.info)
+    }
+
+    /// 发送一步配对请求
+    async fn pair_step<T: serde::de::DeserializeOwned>(
+        &self,
+        target: &Device,
+        path: &str,
+        request: &impl serde::Serialize,
+    ) -> Result<T> {
+        let url = format!("{}/{}", self.base_url(target), path);
+        let (http, verifier) = self.client_for(target)?;
+        let response = http.post(&url).json(request).send().await.map_err(|e| {
+            verifier
+                .mismatch_error()
+                .unwrap_or_else(|| unidrop_core::Error::Network(e.to_string()))
+        })?;
+
+        match response.status() {
+            status if status.is_success() => {}
+            reqwest::StatusCode::NOT_FOUND => {
+                return Err(unidrop_core::Error::PairingFailed(format!(
+                    "{} is not waiting for pairing",
+                    target.name()
+                )));
+            }
+            reqwest::StatusCode::FORBIDDEN => {
+                return Err(unidrop_core::Error::PairingFailed(
+                    "Wrong pairing code".into(),
+                ));
+            }
+            status => {
+                return Err(unidrop_core::Error::Network(format!(
+                    "Pair failed: {}",
+                    status
+                )));
+            }
+        }
+
+        response
+            .json()
+            .await
+            .map_err(|e| unidrop_core::Error::Protocol(e.to_string()))
+    }
+
+    /// 下载设备分享的全部文件（反向传输），返回会话 ID
+    pub async fn download(
+        &self,
+        target: &Device,
+        pin: Option<&str>,
+        save_dir: &Path,
+        collision_policy: CollisionPolicy,
+    ) -> Result<String> {
+        let base_url = self.base_url(target);
+        let (http, verifier) = self.client_for(target)?;
+
+        let mut request = http.post(format!("{}/prepare-download", base_url));
+        if let Some(pin) = pin {
+            request = request.query(&[("pin", pin)]);
+        }
+        let response = request.send().await.map_err(|e| {
+            verifier
+                .mismatch_error()
+                .unwrap_or_else(|| unidrop_core::Error::Network(e.to_string()))
+        })?;
+
+        if let Err(e) = verifier.pin_on_first_use(&self.pins) {
+            warn!("Failed to pin certificate of {}: {}", target.name(), e);
+        }
+
+        match response.status() {
+            status if status.is_success() => {}
+            reqwest::StatusCode::UNAUTHORIZED => {
+                return Err(unidrop_core::Error::PinRequired(target.name().to_string()));
+            }
+            reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                return Err(unidrop_core::Error::PinLockedOut(target.name().to_string()));
+            }
+            reqwest::StatusCode::FORBIDDEN => return Err(unidrop_core::Error::Rejected),
+            status => {
+                return Err(unidrop_core::Error::Network(format!(
+                    "Prepare download failed: {}",
+                    status
+                )))
+            }
+        }
+
+        let prepare_response: PrepareDownloadResponse = response
+            .json()
+            .await
+            .map_err(|e| unidrop_core::Error::Protocol(e.to_string()))?;
+
+        let session_id = prepare_response.session_id.clone();
+        info!(
+            "Downloading {} files from {}",
+            prepare_response.files.len(),
+            target.name()
         );
 
-        debug!("Uploading file: {:?}", path);
+        let mut files: Vec<_> = prepare_response.files.into_values().collect();
+        files.sort_by(|a, b| a.id.cmp(&b.id));
+        let total_size = files.iter().map(|f| f.size).sum();
+        let tracker = Arc::new(Mutex::new(ProgressTracker::new(
+            &session_id,
+            total_size,
+            files.len(),
+        )));
+
+        tokio::fs::create_dir_all(save_dir).await?;
+        let cancel = self.transfers.register(&session_id);
+        let mut result = Ok(());
+        for file in &files {
+            let Some(save_path) =
+                resolve_save_path(save_dir, &file.file_name, false, collision_policy)
+            else {
+                debug!("Skipping existing file {}", file.file_name);
+                tracker.lock().skip_file(file.size);
+                continue;
+            };
+            let url = format!(
+                "{}/download?sessionId={}&fileId={}",
+                base_url, session_id, file.id
+            );
+            result = self
+                .download_file(&http, &url, file, &save_path, &tracker, &cancel)
+                .await;
+            if result.is_err() {
+                break;
+            }
+        }
+        self.transfers.remove(&session_id);
+
+        match &result {
+            Ok(()) => {
+                info!("All shared files downloaded");
+                let progress = tracker.lock().complete();
+                self.emit(Event::transfer_progress(progress)).await;
+                self.emit(Event::transfer_completed(&session_id)).await;
+            }
+            Err(e @ unidrop_core::Error::Cancelled) => {
+                info!("Download session {} cancelled", session_id);
+                let progress = tracker.lock().cancel();
+                self.emit(Event::transfer_progress(progress)).await;
+                self.emit(Event::transfer_failed(&session_id, e.to_string()))
+                    .await;
+            }
+            Err(e) => {
+                let progress = tracker.lock().fail(e.to_string());
+                self.emit(Event::transfer_progress(progress)).await;
+                self.emit(Event::transfer_failed(&session_id, e.to_string()))
+                    .await;
+            }
+        }
+
+        result.map(|_| session_id)
+    }
 
-        // 读取文件
-        let mut file = File::open(path).await?;
-        let mut contents = Vec::new();
-        file.read_to_end(&mut contents).await?;
+    /// 下载单个分享文件，失败或取消时删除临时文件
+    async fn download_file(
+        &self,
+        http: &Client,
+        url: &str,
+        file: &FileInfo,
+        save_path: &Path,
+        tracker: &Arc<Mutex<ProgressTracker>>,
+        cancel: &CancellationToken,
+    ) -> Result<()> {
+        let temp_path = save_path.with_file_name(format!(".{}.part", uuid::Uuid::new_v4()));
 
-        // 构建 multipart
-        let file_name = path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("file")
-            .to_string();
+        tracker.lock().start_file(&file.id);
+        let result = match self
+            .download_to(http, url, &temp_path, tracker, cancel)
+            .await
+        {
+            Ok(hasher) => match hasher.verify(&file.file_name, file.sha256.as_deref()) {
+                Ok(()) => tokio::fs::rename(&temp_path, save_path)
+                    .await
+                    .map_err(Into::into),
+                Err(e) => Err(e),
+            },
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            let _ = tokio::fs::remove_file(&temp_path).await;
+            return result;
+        }
 
-        let part = reqwest::multipart::Part::bytes(contents).file_name(file_name);
-        let form = reqwest::multipart::Form::new().part("file", part);
+        info!("Downloaded file: {:?}", save_path);
+        let progress = tracker.lock().finish_file();
+        self.emit(Event::transfer_progress(progress)).await;
+        Ok(())
+    }
 
-        let response = self
-            .http
-            .post(&url)
-            .multipart(form)
+    /// 将下载的响应体写入文件，返回数据的哈希
+    async fn download_to(
+        &self,
+        http: &Client,
+        url: &str,
+        path: &Path,
+        tracker: &Arc<Mutex<ProgressTracker>>,
+        cancel: &CancellationToken,
+    ) -> Result<FileHasher> {
+        let response = http
+            .get(url)
             .send()
             .await
             .map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
 
         if !response.status().is_success() {
             return Err(unidrop_core::Error::TransferFailed(format!(
-                "Upload failed: {}",
+                "Download failed: {}",
                 response.status()
             )));
         }
 
-        debug!("File uploaded successfully");
-        Ok(())
+        let mut file = File::create(path).await?;
+        let mut hasher = FileHasher::new();
+        let mut stream = response.bytes_stream();
+        loop {
+            let chunk = tokio::select! {
+                _ = cancel.cancelled() => return Err(unidrop_core::Error::Cancelled),
+                chunk = stream.next() => chunk,
+            };
+            let Some(chunk) = chunk else {
+                break;
+            };
+            let chunk = chunk.map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
+            file.write_all(&chunk).await?;
+            hasher.update(&chunk);
+            if let Some(progress) = tracker.lock().advance(chunk.len() as u64) {
+                self.emit_progress(progress);
+            }
+        }
+        file.flush().await?;
+        Ok(hasher)
+    }
+
+    /// 发送事件，通道已满时等待，保证完成、失败等事件送达
+    async fn emit(&self, event: Event) {
+        let _ = self
+            .event_tx
+            .send(event.with_protocol(crate::PROTOCOL_ID))
+            .await;
+    }
+
+    /// 传输中的进度事件，通道已满时丢弃
+    fn emit_progress(&self, progress: TransferProgress) {
+        let _ = self
+            .event_tx
+            .try_send(Event::transfer_progress(progress).with_protocol(crate::PROTOCOL_ID));
     }
 
-    /// 取消传输
+    /// 通知对端取消传输
     pub async fn cancel(&self, target: &Device, session_id: &str) -> Result<()> {
-        let url = format!(
-            "https://{}:{}/api/localsend/v2/cancel",
-            target.ip, target.port
-        );
+        let url = format!("{}/cancel", self.base_url(target));
 
-        self.http
-            .post(&url)
-            .json(&CancelRequest {
-                session_id: session_id.to_string(),
-            })
+        // 与官方 LocalSend 一致，会话 ID 放在查询参数中
+        let (http, _) = self.client_for(target)?;
+        http.post(&url)
+            .query(&[("sessionId", session_id)])
+            .timeout(CANCEL_TIMEOUT)
             .send()
             .await
             .map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
@@ -176,36 +885,3 @@ impl HttpClient {
         Ok(())
     }
 }
-
-/// 猜测 MIME 类型
-fn guess_mime_type(path: &PathBuf) -> String {
-    let ext = path
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("")
-        .to_lowercase();
-
-    match ext.as_str() {
-        "txt" => "text/plain",
-        "html" | "htm" => "text/html",
-        "css" => "text/css",
-        "js" => "application/javascript",
-        "json" => "application/json",
-        "xml" => "application/xml",
-        "pdf" => "application/pdf",
-        "zip" => "application/zip",
-        "tar" => "application/x-tar",
-        "gz" => "application/gzip",
-        "png" => "image/png",
-        "jpg" | "jpeg" => "image/jpeg",
-        "gif" => "image/gif",
-        "webp" => "image/webp",
-        "svg" => "image/svg+xml",
-        "mp3" => "audio/mpeg",
-        "wav" => "audio/wav",
-        "mp4" => "video/mp4",
-        "webm" => "video/webm",
-        _ => "application/octet-stream",
-    }
-    .to_string()
-}
@@ -0,0 +1,133 @@
+//! 已发现设备表 - mDNS 与组播发现共享
+//!
+//! 设备每次被看到时刷新 `last_seen`，超过 TTL 未出现的设备由协议层
+//! 定期检查，确认离线后移除并发出 `DeviceLost`。
+
+use parking_lot::RwLock;
+use std::collections::HashMap;
+use std::time::Duration;
+
+use unidrop_core::Device;
+
+use crate::models::Scheme;
+
+struct Entry {
+    device: Device,
+    /// 设备声明的 HTTP 协议
+    scheme: Scheme,
+}
+
+/// 设备表：指纹 -> 设备
+#[derive(Default)]
+pub struct DeviceTable {
+    devices: RwLock<HashMap<String, Entry>>,
+}
+
+impl DeviceTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 记录看到的设备，返回是否为新设备
+    pub fn upsert(&self, mut device: Device, scheme: Scheme) -> bool {
+        device.touch();
+        self.devices
+            .write()
+            .insert(device.peer.id.fingerprint.clone(), Entry { device, scheme })
+            .is_none()
+    }
+
+    /// 根据指纹获取设备
+    pub fn get(&self, fingerprint: &str) -> Option<Device> {
+        self.devices
+            .read()
+            .get(fingerprint)
+            .map(|e| e.device.clone())
+    }
+
+    /// 设备声明的 HTTP 协议，未知设备按 https 处理
+    pub fn scheme(&self, fingerprint: &str) -> Scheme {
+        self.devices
+            .read()
+            .get(fingerprint)
+            .map(|e| e.scheme)
+            .unwrap_or_default()
+    }
+
+    /// 所有设备
+    pub fn list(&self) -> Vec<Device> {
+        self.devices
+            .read()
+            .values()
+            .map(|e| e.device.clone())
+            .collect()
+    }
+
+    /// 刷新设备的最后在线时间
+    pub fn touch(&self, fingerprint: &str) {
+        if let Some(entry) = self.devices.write().get_mut(fingerprint) {
+            entry.device.touch();
+        }
+    }
+
+    /// 标记设备可能已离线（如 mDNS 服务被移除），下次检查时确认
+    pub fn mark_stale(&self, fingerprint: &str) {
+        if let Some(entry) = self.devices.write().get_mut(fingerprint) {
+            entry.device.last_seen = 0;
+        }
+    }
+
+    /// 超过 `ttl` 未出现的设备
+    pub fn expired(&self, ttl: Duration) -> Vec<Device> {
+        self.devices
+            .read()
+            .values()
+            .filter(|e| e.device.is_expired(ttl))
+            .map(|e| e.device.clone())
+            .collect()
+    }
+
+    /// 设备仍然过期时移除（检查期间可能再次出现）
+    pub fn remove_expired(&self, fingerprint: &str, ttl: Duration) -> Option<Device> {
+        let mut devices = self.devices.write();
+        if !devices.get(fingerprint)?.device.is_expired(ttl) {
+            return None;
+        }
+        devices.remove(fingerprint).map(|e| e.device)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::{IpAddr, Ipv4Addr};
+    use unidrop_core::{Peer, ProtocolId};
+
+    #[test]
+    fn test_device_expiry() {
+        let peer = Peer::new(
+            ProtocolId::new(crate::PROTOCOL_ID),
+            "ABCD".to_string(),
+            "Laptop".to_string(),
+        );
+        let table = DeviceTable::new();
+        let device = Device::new(peer, IpAddr::V4(Ipv4Addr::LOCALHOST), 53317);
+        assert!(table.upsert(device.clone(), Scheme::Https));
+        assert!(!table.upsert(device, Scheme::Http));
+        assert_eq!(table.scheme("ABCD"), Scheme::Http);
+
+        let ttl = Duration::from_secs(60);
+        assert!(table.expired(ttl).is_empty());
+        assert!(table.remove_expired("ABCD", ttl).is_none());
+
+        table.mark_stale("ABCD");
+        assert_eq!(table.expired(ttl).len(), 1);
+
+        table.touch("ABCD");
+        assert!(table.remove_expired("ABCD", ttl).is_none());
+
+        table.mark_stale("ABCD");
+        assert!(table.remove_expired("ABCD", ttl).is_some());
+        assert!(table.list().is_empty());
+    }
+}
@@ -1,31 +1,39 @@
 //! LocalSend mDNS 设备发现
 
 use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
-use parking_lot::RwLock;
+use parking_lot::Mutex;
 use std::collections::HashMap;
 use std::net::{IpAddr, Ipv4Addr};
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
-use unidrop_core::{Device, DeviceId, DeviceType, Event, Peer, ProtocolId};
+use unidrop_core::{Capability, Device, DeviceId, DeviceType, Event, Peer, ProtocolId};
 
-use crate::models::DeviceInfo;
+use crate::devices::DeviceTable;
+use crate::models::{DeviceInfo, Scheme};
 use crate::{PROTOCOL_ID, SERVICE_TYPE};
 
 /// 发现服务
 pub struct DiscoveryService {
     local_info: DeviceInfo,
-    devices: Arc<RwLock<HashMap<String, Device>>>,
+    devices: Arc<DeviceTable>,
+    /// 服务全名 -> 设备指纹，用于处理服务移除
+    services: Arc<Mutex<HashMap<String, String>>>,
     mdns: Option<ServiceDaemon>,
     event_tx: mpsc::Sender<Event>,
 }
 
 impl DiscoveryService {
-    pub fn new(local_info: DeviceInfo, event_tx: mpsc::Sender<Event>) -> Self {
+    pub fn new(
+        local_info: DeviceInfo,
+        devices: Arc<DeviceTable>,
+        event_tx: mpsc::Sender<Event>,
+    ) -> Self {
         Self {
             local_info,
-            devices: Arc::new(RwLock::new(HashMap::new())),
+            devices,
+            services: Arc::new(Mutex::new(HashMap::new())),
             mdns: None,
             event_tx,
         }
@@ -49,6 +57,9 @@ impl DiscoveryService {
         properties.insert("version".to_string(), self.local_info.version.clone());
         properties.insert("protocol".to_string(), self.local_info.protocol.clone());
         properties.insert("deviceType".to_string(), "desktop".to_string());
+        if let Some(port) = self.local_info.quic_port {
+            properties.insert("unidropQuicPort".to_string(), port.to_string());
+        }
 
         let host = format!("{}.local.", service_name);
 
@@ -64,13 +75,40 @@ impl DiscoveryService {
 
         info!("Registered mDNS service: {}", service_name);
 
-        // 浏览其他设备
+        self.mdns = Some(mdns);
+        self.browse()
+    }
+
+    /// 主动发送一次 mDNS 查询
+    ///
+    /// 重新开始浏览，旧的浏览结束后其处理线程随之退出。
+    pub fn query(&self) -> unidrop_core::Result<()> {
+        let Some(mdns) = &self.mdns else {
+            return Err(unidrop_core::Error::Discovery(
+                "Discovery not started".into(),
+            ));
+        };
+        if let Err(e) = mdns.stop_browse(SERVICE_TYPE) {
+            debug!("Failed to stop mDNS browse: {}", e);
+        }
+        self.browse()
+    }
+
+    /// 浏览其他设备，并在后台线程中处理结果
+    fn browse(&self) -> unidrop_core::Result<()> {
+        let Some(mdns) = &self.mdns else {
+            return Err(unidrop_core::Error::Discovery(
+                "Discovery not started".into(),
+            ));
+        };
+
         let receiver = mdns
             .browse(SERVICE_TYPE)
             .map_err(|e| unidrop_core::Error::Discovery(e.to_string()))?;
 
         let devices = self.devices.clone();
         let event_tx = self.event_tx.clone();
+        let services = self.services.clone();
         let local_fingerprint = self.local_info.fingerprint.clone();
 
         // 启动事件处理
@@ -87,16 +125,20 @@ impl DiscoveryService {
                             info.get_addresses(),
                             info.get_port()
                         );
-                        if let Some(device) = parse_service_info(&info, &local_fingerprint) {
-                            let fingerprint = device.peer.id.fingerprint.clone();
-                            let is_new = !devices.read().contains_key(&fingerprint);
+                        if let Some((device, scheme)) =
+                            parse_service_info(&info, &local_fingerprint)
+                        {
+                            services.lock().insert(
+                                info.get_fullname().to_string(),
+                                device.peer.id.fingerprint.clone(),
+                            );
 
                             info!(
                                 "Discovered device: {} ({})",
                                 device.name(),
                                 device.address()
                             );
-                            devices.write().insert(fingerprint.clone(), device.clone());
+                            let is_new = devices.upsert(device.clone(), scheme);
 
                             let evt = if is_new {
                                 Event::device_discovered(device)
@@ -109,7 +151,11 @@ impl DiscoveryService {
                     }
                     ServiceEvent::ServiceRemoved(_, fullname) => {
                         debug!("Service removed: {}", fullname);
-                        // 尝试从 fullname 提取 fingerprint 并发送离线事件
+                        // 组播可能仍能看到该设备，交给离线检查确认
+                        let fingerprint = services.lock().remove(&fullname);
+                        if let Some(fingerprint) = fingerprint {
+                            devices.mark_stale(&fingerprint);
+                        }
                     }
                     ServiceEvent::SearchStarted(s) => {
                         debug!("mDNS search started: {}", s);
@@ -121,7 +167,6 @@ impl DiscoveryService {
             }
         });
 
-        self.mdns = Some(mdns);
         Ok(())
     }
 
@@ -130,20 +175,10 @@ impl DiscoveryService {
         self.mdns = None;
         info!("LocalSend discovery service stopped");
     }
-
-    /// 获取当前设备列表
-    pub fn devices(&self) -> Vec<Device> {
-        self.devices.read().values().cloned().collect()
-    }
-
-    /// 根据指纹获取设备
-    pub fn device(&self, fingerprint: &str) -> Option<Device> {
-        self.devices.read().get(fingerprint).cloned()
-    }
 }
 
 /// 解析 mDNS 服务信息
-fn parse_service_info(info: &ServiceInfo, local_fingerprint: &str) -> Option<Device> {
+fn parse_service_info(info: &ServiceInfo, local_fingerprint: &str) -> Option<(Device, Scheme)> {
     let properties = info.get_properties();
 
     let fingerprint = properties.get("fingerprint")?.val_str();
@@ -178,6 +213,11 @@ fn parse_service_info(info: &ServiceInfo, local_fingerprint: &str) -> Option<Dev
 
     let port = info.get_port();
 
+    let scheme = properties
+        .get("protocol")
+        .map(|v| Scheme::from_protocol(v.val_str()))
+        .unwrap_or_default();
+
     let peer = Peer::new(
         ProtocolId::new(PROTOCOL_ID),
         fingerprint.to_string(),
@@ -186,11 +226,20 @@ fn parse_service_info(info: &ServiceInfo, local_fingerprint: &str) -> Option<Dev
     .with_device_type(device_type)
     .with_version(version);
 
-    Some(Device::new(peer, ip, port))
+    let device = Device::new(peer, ip, port);
+    let device = match properties
+        .get("unidropQuicPort")
+        .and_then(|v| v.val_str().parse().ok())
+    {
+        Some(port) => device.with_capability(Capability::Quic { port }),
+        None => device,
+    };
+
+    Some((device, scheme))
 }
 
 /// 获取本机局域网 IP 地址
-fn get_local_ip() -> Option<String> {
+pub(crate) fn get_local_ip() -> Option<String> {
     use std::net::UdpSocket;
 
     // 首先尝试获取所有网络接口的 IP
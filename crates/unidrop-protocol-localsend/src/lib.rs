@@ -7,15 +7,22 @@
 //! - HTTPS REST API
 //! - 自签名证书
 //! - 支持文件和文本传输
+//! - 分享模式（反向传输，可用浏览器下载）
+//! - 短码配对（UniDrop 扩展）
 
 mod cert;
 mod client;
+mod devices;
 mod discovery;
 mod models;
 mod multicast;
+mod pairing;
+mod pinning;
 mod protocol;
 pub mod quic;
+mod scan;
 mod server;
+mod share;
 
 pub use protocol::{LocalSendProtocol, LocalSendFactory};
 
@@ -17,6 +17,13 @@ pub struct DeviceInfo {
     pub device_type: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub download: Option<bool>,
+    /// UniDrop 扩展：QUIC 服务端口，未启用 QUIC 时省略
+    #[serde(
+        rename = "unidropQuicPort",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub quic_port: Option<u16>,
 }
 
 impl DeviceInfo {
@@ -30,6 +37,50 @@ impl DeviceInfo {
             device_model: None,
             device_type: Some("desktop".to_string()),
             download: Some(false),
+            quic_port: None,
+        }
+    }
+
+    /// 声明 QUIC 服务端口
+    pub fn with_quic_port(mut self, port: u16) -> Self {
+        self.quic_port = Some(port);
+        self
+    }
+
+    /// 设置 HTTP 协议（关闭加密时为 http）
+    pub fn with_scheme(mut self, scheme: Scheme) -> Self {
+        self.protocol = scheme.as_str().to_string();
+        self
+    }
+
+    /// 设备声明的 HTTP 协议
+    pub fn scheme(&self) -> Scheme {
+        Scheme::from_protocol(&self.protocol)
+    }
+}
+
+/// HTTP 协议：LocalSend 关闭加密时使用明文 http
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Scheme {
+    Http,
+    #[default]
+    Https,
+}
+
+impl Scheme {
+    /// 解析设备声明的 `protocol` 字段，未知值按 https 处理
+    pub fn from_protocol(protocol: &str) -> Self {
+        if protocol.eq_ignore_ascii_case("http") {
+            Self::Http
+        } else {
+            Self::Https
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Http => "http",
+            Self::Https => "https",
         }
     }
 }
@@ -62,6 +113,62 @@ pub struct PrepareUploadResponse {
     #[serde(rename = "sessionId")]
     pub session_id: String,
     pub files: HashMap<String, String>, // file_id -> token
+    /// UniDrop 扩展：续传时每个文件已接收的字节数
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub offsets: HashMap<String, u64>,
+}
+
+/// UniDrop 扩展请求头：续传时携带接收方保存未完成文件所用的会话 ID
+pub const RESUME_HEADER: &str = "x-unidrop-resume";
+
+/// UniDrop 扩展请求头：上传内容在文件中的起始偏移
+pub const OFFSET_HEADER: &str = "x-unidrop-offset";
+
+/// 准备下载响应（反向传输）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrepareDownloadResponse {
+    pub info: DeviceInfo,
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    pub files: HashMap<String, FileInfo>,
+}
+
+/// UniDrop 扩展：配对第一步，请求被配对方的承诺
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairCommitRequest {
+    pub info: DeviceInfo,
+}
+
+/// UniDrop 扩展：被配对方绑定短码的承诺
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairCommitResponse {
+    pub commitment: String,
+}
+
+/// UniDrop 扩展：配对第二步，提交配对方的承诺
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairRevealRequest {
+    pub info: DeviceInfo,
+    pub commitment: String,
+}
+
+/// UniDrop 扩展：被配对方揭示的随机数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairRevealResponse {
+    pub nonce: String,
+}
+
+/// UniDrop 扩展：配对第三步，配对方揭示随机数
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairRequest {
+    pub info: DeviceInfo,
+    pub nonce: String,
+}
+
+/// UniDrop 扩展：配对完成
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PairResponse {
+    pub info: DeviceInfo,
 }
 
 /// 取消请求
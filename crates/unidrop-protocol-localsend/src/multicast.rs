@@ -5,16 +5,16 @@
 //! - 端口：53317（与 HTTPS 端口相同）
 //! - 消息格式：JSON (MulticastDto)
 
-use parking_lot::RwLock;
-use std::collections::HashMap;
 use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
 use std::sync::Arc;
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
-use unidrop_core::{Device, DeviceType, Event, Peer, ProtocolId};
+use unidrop_core::{CancellationToken, Capability, Device, DeviceType, Event, Peer, ProtocolId};
 
-use crate::models::DeviceInfo;
+use crate::client::HttpClient;
+use crate::devices::DeviceTable;
+use crate::models::{DeviceInfo, Scheme};
 use crate::{MULTICAST_ADDR, PROTOCOL_ID};
 
 /// Multicast 消息结构（与 LocalSend 兼容）
@@ -40,6 +40,13 @@ pub struct MulticastDto {
     /// v2 字段
     #[serde(skip_serializing_if = "Option::is_none")]
     pub announce: Option<bool>,
+    /// UniDrop 扩展：QUIC 服务端口
+    #[serde(
+        rename = "unidropQuicPort",
+        default,
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub quic_port: Option<u16>,
 }
 
 impl MulticastDto {
@@ -56,6 +63,7 @@ impl MulticastDto {
             download: Some(false),
             announcement: Some(true),
             announce: Some(true),
+            quic_port: info.quic_port,
         }
     }
 
@@ -72,6 +80,7 @@ impl MulticastDto {
             download: Some(false),
             announcement: Some(false),
             announce: Some(false),
+            quic_port: info.quic_port,
         }
     }
 
@@ -84,21 +93,37 @@ impl MulticastDto {
 /// UDP Multicast 发现服务
 pub struct MulticastDiscovery {
     local_info: DeviceInfo,
-    devices: Arc<RwLock<HashMap<String, Device>>>,
+    devices: Arc<DeviceTable>,
     event_tx: mpsc::Sender<Event>,
+    /// 用于通过 HTTP 注册回应公告，未设置时只用组播响应
+    client: Option<HttpClient>,
     socket: Option<UdpSocket>,
+    /// 接收线程及其停止令牌
+    receiver: Option<(std::thread::JoinHandle<()>, CancellationToken)>,
 }
 
 impl MulticastDiscovery {
-    pub fn new(local_info: DeviceInfo, event_tx: mpsc::Sender<Event>) -> Self {
+    pub fn new(
+        local_info: DeviceInfo,
+        devices: Arc<DeviceTable>,
+        event_tx: mpsc::Sender<Event>,
+    ) -> Self {
         Self {
             local_info,
-            devices: Arc::new(RwLock::new(HashMap::new())),
+            devices,
             event_tx,
+            client: None,
             socket: None,
+            receiver: None,
         }
     }
 
+    /// 设置 HTTP 客户端，收到公告时优先通过 `/register` 回应
+    pub fn with_client(mut self, client: HttpClient) -> Self {
+        self.client = Some(client);
+        self
+    }
+
     /// 启动 multicast 发现服务
     pub fn start(&mut self) -> unidrop_core::Result<()> {
         info!("Starting LocalSend multicast discovery service");
@@ -130,10 +155,14 @@ impl MulticastDiscovery {
         let local_info = self.local_info.clone();
         let devices = self.devices.clone();
         let event_tx = self.event_tx.clone();
+        let client = self.client.clone();
+        let runtime = tokio::runtime::Handle::try_current().ok();
+        let shutdown = CancellationToken::new();
+        let stopped = shutdown.clone();
 
-        std::thread::spawn(move || {
+        let thread = std::thread::spawn(move || {
             let mut buf = [0u8; 4096];
-            loop {
+            while !stopped.is_cancelled() {
                 match socket.recv_from(&mut buf) {
                     Ok((len, src)) => {
                         if let Ok(json_str) = std::str::from_utf8(&buf[..len]) {
@@ -161,16 +190,21 @@ impl MulticastDiscovery {
                                 .with_device_type(device_type)
                                 .with_version(dto.version.clone().unwrap_or_else(|| "2.0".to_string()));
 
-                                let device = Device::new(peer, ip, port);
-
-                                let fingerprint = dto.fingerprint.clone();
-                                let is_new = !devices.read().contains_key(&fingerprint);
+                                let mut device = Device::new(peer, ip, port);
+                                if let Some(port) = dto.quic_port {
+                                    device = device.with_capability(Capability::Quic { port });
+                                }
 
                                 info!(
                                     "Discovered device via multicast: {} ({}:{})",
                                     dto.alias, ip, port
                                 );
-                                devices.write().insert(fingerprint.clone(), device.clone());
+                                let scheme = dto
+                                    .protocol
+                                    .as_deref()
+                                    .map(Scheme::from_protocol)
+                                    .unwrap_or_default();
+                                let is_new = devices.upsert(device.clone(), scheme);
 
                                 let evt = if is_new {
                                     Event::device_discovered(device)
@@ -180,20 +214,21 @@ impl MulticastDiscovery {
 
                                 let _ = event_tx.blocking_send(evt);
 
-                                // 如果是公告消息，回复响应
+                                // 如果是公告消息，先通过 HTTP 注册回应，失败时再组播响应
                                 if dto.is_announcement() {
                                     debug!("Responding to announcement from {}", dto.alias);
-                                    let response = MulticastDto::response(&local_info);
-                                    if let Ok(response_json) = serde_json::to_string(&response) {
-                                        let target = SocketAddr::new(ip, port);
-                                        // 发送到组播地址
-                                        let multicast_target = SocketAddr::new(
-                                            IpAddr::V4(MULTICAST_ADDR.parse().unwrap()),
-                                            port,
-                                        );
-                                        if let Ok(sock) = UdpSocket::bind("0.0.0.0:0") {
-                                            let _ = sock.send_to(response_json.as_bytes(), multicast_target);
+                                    match (&client, &runtime) {
+                                        (Some(client), Some(runtime)) => {
+                                            let client = client.clone();
+                                            let local_info = local_info.clone();
+                                            runtime.spawn(async move {
+                                                if let Err(e) = client.register(ip, port, scheme).await {
+                                                    debug!("HTTP register to {} failed: {}, falling back to multicast", ip, e);
+                                                    send_response(&local_info, port);
+                                                }
+                                            });
                                         }
+                                        _ => send_response(&local_info, port),
                                     }
                                 }
                             }
@@ -210,6 +245,7 @@ impl MulticastDiscovery {
                 }
             }
         });
+        self.receiver = Some((thread, shutdown));
 
         // 发送公告
         self.send_announcement()?;
@@ -217,6 +253,16 @@ impl MulticastDiscovery {
         Ok(())
     }
 
+    /// 停止接收线程并释放组播端口
+    pub fn stop(&mut self) {
+        self.socket = None;
+        if let Some((thread, shutdown)) = self.receiver.take() {
+            shutdown.cancel();
+            let _ = thread.join();
+        }
+        info!("LocalSend multicast discovery service stopped");
+    }
+
     /// 发送公告消息
     pub fn send_announcement(&self) -> unidrop_core::Result<()> {
         let socket = match &self.socket {
@@ -250,14 +296,16 @@ impl MulticastDiscovery {
 
         Ok(())
     }
+}
 
-    /// 获取设备列表
-    pub fn devices(&self) -> Vec<Device> {
-        self.devices.read().values().cloned().collect()
-    }
-
-    /// 根据指纹获取设备
-    pub fn device(&self, fingerprint: &str) -> Option<Device> {
-        self.devices.read().get(fingerprint).cloned()
+/// 从临时 socket 向组播地址发送响应消息
+fn send_response(local_info: &DeviceInfo, port: u16) {
+    let response = MulticastDto::response(local_info);
+    let Ok(response_json) = serde_json::to_string(&response) else {
+        return;
+    };
+    let multicast_target = SocketAddr::new(IpAddr::V4(MULTICAST_ADDR.parse().unwrap()), port);
+    if let Ok(sock) = UdpSocket::bind("0.0.0.0:0") {
+        let _ = sock.send_to(response_json.as_bytes(), multicast_target);
     }
 }
@@ -0,0 +1,113 @@
+//! 设备配对 - UniDrop 扩展
+//!
+//! 被配对方显示短码，双方按 [`unidrop_core::pairing`] 的先承诺后揭示交换证明：
+//! `/pair/commit` 返回被配对方的承诺，`/pair/reveal` 提交配对方的承诺并揭示
+//! 随机数，`/pair` 校验配对方揭示的随机数。
+//! 请求必须来自出示了客户端证书的 HTTPS 连接，证书须与声明的指纹一致，
+//! 这样写入可信设备的身份就是经过验证的。
+
+use axum::{
+    extract::{ConnectInfo, Extension, State},
+    http::StatusCode,
+    Json,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use unidrop_core::Event;
+
+use crate::models::{
+    DeviceInfo, PairCommitRequest, PairCommitResponse, PairRequest, PairResponse,
+    PairRevealRequest, PairRevealResponse,
+};
+use crate::pinning::is_identity;
+use crate::server::{device_from_info, ClientIdentity, ServerState};
+
+/// POST /pair/commit - 配对第一步
+///
+/// 未在配对时返回 404，证书与指纹不符时返回 403。
+pub async fn commit(
+    State(state): State<Arc<ServerState>>,
+    ConnectInfo(remote): ConnectInfo<SocketAddr>,
+    Extension(identity): Extension<ClientIdentity>,
+    Json(request): Json<PairCommitRequest>,
+) -> Result<Json<PairCommitResponse>, StatusCode> {
+    verify_identity(&request.info, &identity, remote)?;
+    let commitment = state
+        .pairing
+        .commit(&request.info.fingerprint, &state.local_info.fingerprint)
+        .map_err(|e| status(e, remote))?;
+    Ok(Json(PairCommitResponse { commitment }))
+}
+
+/// POST /pair/reveal - 配对第二步，揭示随机数后短码不能再用于其他配对
+pub async fn reveal(
+    State(state): State<Arc<ServerState>>,
+    ConnectInfo(remote): ConnectInfo<SocketAddr>,
+    Extension(identity): Extension<ClientIdentity>,
+    Json(request): Json<PairRevealRequest>,
+) -> Result<Json<PairRevealResponse>, StatusCode> {
+    verify_identity(&request.info, &identity, remote)?;
+    let nonce = state
+        .pairing
+        .reveal(&request.info.fingerprint, &request.commitment)
+        .map_err(|e| status(e, remote))?;
+    Ok(Json(PairRevealResponse { nonce }))
+}
+
+/// POST /pair - 配对第三步
+///
+/// 随机数与配对方的承诺不符（短码错误）时返回 403。
+pub async fn pair(
+    State(state): State<Arc<ServerState>>,
+    ConnectInfo(remote): ConnectInfo<SocketAddr>,
+    Extension(identity): Extension<ClientIdentity>,
+    Json(request): Json<PairRequest>,
+) -> Result<Json<PairResponse>, StatusCode> {
+    verify_identity(&request.info, &identity, remote)?;
+    state
+        .pairing
+        .verify(
+            &request.info.fingerprint,
+            &state.local_info.fingerprint,
+            &request.nonce,
+        )
+        .map_err(|e| status(e, remote))?;
+
+    let device = device_from_info(&request.info, remote.ip());
+    info!("Paired with {} ({})", device.name(), device.address());
+    if state.devices.upsert(device.clone(), request.info.scheme()) {
+        state.emit(Event::device_discovered(device.clone())).await;
+    }
+    state.emit(Event::device_paired(device)).await;
+
+    Ok(Json(PairResponse {
+        info: state.device_info(),
+    }))
+}
+
+/// 请求须来自证书与声明指纹一致的连接
+fn verify_identity(
+    info: &DeviceInfo,
+    identity: &ClientIdentity,
+    remote: SocketAddr,
+) -> Result<(), StatusCode> {
+    match &identity.0 {
+        Some(hash) if is_identity(&info.fingerprint, hash) => Ok(()),
+        _ => {
+            warn!("Pairing from {} without a matching certificate", remote);
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+}
+
+fn status(error: unidrop_core::Error, remote: SocketAddr) -> StatusCode {
+    match error {
+        unidrop_core::Error::InvalidSession(_) => StatusCode::NOT_FOUND,
+        e => {
+            warn!("Pairing from {} failed: {}", remote, e);
+            StatusCode::FORBIDDEN
+        }
+    }
+}
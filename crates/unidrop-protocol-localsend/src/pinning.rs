@@ -0,0 +1,365 @@
+//! 证书固定 - 首次使用时信任 (TOFU)
+//!
+//! 连接对端时校验其证书的 SHA-256 与广播的指纹一致，
+//! 并记住首次连接时看到的证书；之后证书变化视为中间人攻击。
+//!
+//! UniDrop 客户端同时出示自己的持久化证书（双向 TLS），
+//! 服务端据此验证发送方声明的指纹。
+
+use parking_lot::{Mutex, RwLock};
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::CryptoProvider;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::server::danger::{ClientCertVerified, ClientCertVerifier};
+use rustls::{DigitallySignedStruct, DistinguishedName, SignatureScheme};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tracing::{info, warn};
+
+use crate::cert::CertInfo;
+
+/// 可用于校验证书的最短指纹长度（十六进制字符数）
+const MIN_FINGERPRINT_LEN: usize = 32;
+
+/// 计算证书的 SHA-256（大写十六进制，无分隔符）
+pub fn cert_sha256(cert_der: &[u8]) -> String {
+    Sha256::digest(cert_der)
+        .iter()
+        .map(|b| format!("{:02X}", b))
+        .collect()
+}
+
+/// 广播的指纹对应的证书哈希前缀，指纹不是证书哈希（如 HTTP 模式下的随机指纹）时为 None
+fn advertised_hash(fingerprint: &str) -> Option<String> {
+    let advertised = fingerprint.replace(':', "").to_uppercase();
+    (advertised.len() >= MIN_FINGERPRINT_LEN && advertised.chars().all(|c| c.is_ascii_hexdigit()))
+        .then_some(advertised)
+}
+
+/// 证书是否属于声明该指纹的设备
+pub fn is_identity(fingerprint: &str, cert_hash: &str) -> bool {
+    advertised_hash(fingerprint).is_some_and(|advertised| cert_hash.starts_with(&advertised))
+}
+
+/// 已固定的对端证书：设备指纹 -> 证书 SHA-256
+pub struct PinStore {
+    path: Option<PathBuf>,
+    pins: RwLock<HashMap<String, String>>,
+}
+
+impl PinStore {
+    /// 仅保存在内存中
+    pub fn in_memory() -> Self {
+        Self {
+            path: None,
+            pins: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// 从文件加载，文件不存在时为空
+    pub fn load(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        let pins = match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).unwrap_or_else(|e| {
+                warn!("Ignoring invalid pin store {:?}: {}", path, e);
+                HashMap::new()
+            }),
+            Err(_) => HashMap::new(),
+        };
+
+        Self {
+            path: Some(path),
+            pins: RwLock::new(pins),
+        }
+    }
+
+    /// 获取设备已固定的证书
+    pub fn get(&self, fingerprint: &str) -> Option<String> {
+        self.pins.read().get(fingerprint).cloned()
+    }
+
+    /// 固定设备证书
+    pub fn pin(&self, fingerprint: &str, cert_hash: &str) -> unidrop_core::Result<()> {
+        self.pins
+            .write()
+            .insert(fingerprint.to_string(), cert_hash.to_string());
+        info!("Pinned certificate for {}", fingerprint);
+        self.save()
+    }
+
+    /// 取消固定（例如对端重新生成了身份）
+    pub fn unpin(&self, fingerprint: &str) -> unidrop_core::Result<()> {
+        self.pins.write().remove(fingerprint);
+        self.save()
+    }
+
+    fn save(&self) -> unidrop_core::Result<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let data = serde_json::to_vec_pretty(&*self.pins.read())
+            .map_err(|e| unidrop_core::Error::Internal(e.to_string()))?;
+        std::fs::write(path, data)?;
+        Ok(())
+    }
+}
+
+/// 校验对端证书的 TLS verifier
+///
+/// 证书需与广播的指纹一致；已固定的设备还需与固定的证书一致。
+/// 握手签名仍按正常流程校验，保证对端持有证书私钥。
+#[derive(Debug)]
+pub struct PinnedVerifier {
+    fingerprint: String,
+    pinned: Option<String>,
+    provider: Arc<CryptoProvider>,
+    presented: Mutex<Option<String>>,
+}
+
+impl PinnedVerifier {
+    pub fn new(fingerprint: &str, pinned: Option<String>) -> Arc<Self> {
+        Arc::new(Self {
+            fingerprint: fingerprint.to_string(),
+            pinned,
+            provider: Arc::new(rustls::crypto::ring::default_provider()),
+            presented: Mutex::new(None),
+        })
+    }
+
+    /// 对端出示的证书 SHA-256
+    pub fn presented(&self) -> Option<String> {
+        self.presented.lock().clone()
+    }
+
+    /// 证书是否可信
+    pub fn is_trusted(&self, cert_hash: &str) -> bool {
+        if let Some(pinned) = &self.pinned {
+            return pinned == cert_hash;
+        }
+
+        // 指纹不是证书哈希时只能依赖固定
+        match advertised_hash(&self.fingerprint) {
+            Some(advertised) => cert_hash.starts_with(&advertised),
+            None => true,
+        }
+    }
+
+    /// 握手失败时，若原因是证书不符则返回对应错误
+    pub fn mismatch_error(&self) -> Option<unidrop_core::Error> {
+        let presented = self.presented()?;
+        if self.is_trusted(&presented) {
+            return None;
+        }
+        Some(unidrop_core::Error::CertificateMismatch(format!(
+            "{} presented {}",
+            self.fingerprint, presented
+        )))
+    }
+
+    /// 握手成功后，首次连接的设备记录其证书
+    pub fn pin_on_first_use(&self, pins: &PinStore) -> unidrop_core::Result<()> {
+        match (&self.pinned, self.presented()) {
+            (None, Some(presented)) => pins.pin(&self.fingerprint, &presented),
+            _ => Ok(()),
+        }
+    }
+
+    /// 使用该 verifier 的 rustls 客户端配置，`identity` 为出示给对端的客户端证书
+    pub fn client_config(
+        self: &Arc<Self>,
+        identity: Option<&CertInfo>,
+    ) -> unidrop_core::Result<rustls::ClientConfig> {
+        let builder = rustls::ClientConfig::builder_with_provider(self.provider.clone())
+            .with_safe_default_protocol_versions()
+            .expect("ring provider supports default protocol versions")
+            .dangerous()
+            .with_custom_certificate_verifier(self.clone());
+
+        let Some(identity) = identity else {
+            return Ok(builder.with_no_client_auth());
+        };
+        let cert = CertificateDer::from(identity.cert_der.clone());
+        let key = PrivateKeyDer::try_from(identity.key_der.clone())
+            .map_err(|e| unidrop_core::Error::Protocol(format!("Invalid key: {}", e)))?;
+        builder
+            .with_client_auth_cert(vec![cert], key)
+            .map_err(|e| unidrop_core::Error::Protocol(format!("TLS config error: {}", e)))
+    }
+}
+
+/// 服务端接受任意自签名客户端证书的 verifier
+///
+/// 客户端证书可选（LocalSend 客户端不出示证书）。出示时同样校验握手签名，
+/// 证书的 SHA-256 即为发送方经过验证的身份。
+#[derive(Debug)]
+pub struct ClientIdentityVerifier {
+    provider: Arc<CryptoProvider>,
+}
+
+impl ClientIdentityVerifier {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            provider: Arc::new(rustls::crypto::ring::default_provider()),
+        })
+    }
+
+    /// 使用该 verifier 的 rustls 服务端配置
+    pub fn server_config(
+        self: &Arc<Self>,
+        cert_info: &CertInfo,
+    ) -> unidrop_core::Result<rustls::ServerConfig> {
+        let cert = CertificateDer::from(cert_info.cert_der.clone());
+        let key = PrivateKeyDer::try_from(cert_info.key_der.clone())
+            .map_err(|e| unidrop_core::Error::Protocol(format!("Invalid key: {}", e)))?;
+
+        rustls::ServerConfig::builder_with_provider(self.provider.clone())
+            .with_safe_default_protocol_versions()
+            .expect("ring provider supports default protocol versions")
+            .with_client_cert_verifier(self.clone())
+            .with_single_cert(vec![cert], key)
+            .map_err(|e| unidrop_core::Error::Protocol(format!("TLS config error: {}", e)))
+    }
+}
+
+impl ClientCertVerifier for ClientIdentityVerifier {
+    fn offer_client_auth(&self) -> bool {
+        true
+    }
+
+    fn client_auth_mandatory(&self) -> bool {
+        false
+    }
+
+    fn root_hint_subjects(&self) -> &[DistinguishedName] {
+        &[]
+    }
+
+    fn verify_client_cert(
+        &self,
+        _end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _now: UnixTime,
+    ) -> Result<ClientCertVerified, rustls::Error> {
+        Ok(ClientCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+impl ServerCertVerifier for PinnedVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        let cert_hash = cert_sha256(end_entity);
+        let trusted = self.is_trusted(&cert_hash);
+        *self.presented.lock() = Some(cert_hash);
+
+        if trusted {
+            Ok(ServerCertVerified::assertion())
+        } else {
+            warn!("Certificate of {} does not match", self.fingerprint);
+            Err(rustls::Error::General("certificate mismatch".into()))
+        }
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verifier_checks_advertised_and_pinned() {
+        let hash = cert_sha256(b"cert");
+
+        // 指纹为证书哈希前缀
+        let verifier = PinnedVerifier::new(&hash[..32], None);
+        assert!(verifier.is_trusted(&hash));
+        assert!(!verifier.is_trusted(&cert_sha256(b"other")));
+
+        // 随机指纹只能依赖固定
+        let verifier = PinnedVerifier::new("random-fingerprint", Some(hash.clone()));
+        assert!(verifier.is_trusted(&hash));
+        assert!(!verifier.is_trusted(&cert_sha256(b"other")));
+
+        // 客户端身份必须与证书哈希一致，随机指纹无法验证
+        assert!(is_identity(&hash[..32], &hash));
+        assert!(!is_identity(&hash[..32], &cert_sha256(b"other")));
+        assert!(!is_identity("random-fingerprint", &hash));
+    }
+}
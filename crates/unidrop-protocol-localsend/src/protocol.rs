@@ -2,34 +2,70 @@
 
 use async_trait::async_trait;
 use parking_lot::RwLock;
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::mpsc;
-use tracing::{debug, info};
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
 
 use unidrop_core::{
-    Device, DeviceId, Event, Protocol, ProtocolBuilder, ProtocolConfig, ProtocolFactory,
-    ProtocolId, ProtocolInfo, Result, TransferIntent,
+    CancelRegistry, CancellationToken, Device, DeviceId, Event, PairingInvite, Protocol,
+    ProtocolBuilder, ProtocolConfig, ProtocolFactory, ProtocolId, ProtocolInfo, Result,
+    ResumeRegistry, TransferIntent,
 };
 
-use crate::cert::{generate_self_signed, CertInfo};
+use crate::cert::{self, CertInfo};
 use crate::client::HttpClient;
-use crate::discovery::DiscoveryService;
-use crate::models::DeviceInfo;
+use crate::devices::DeviceTable;
+use crate::discovery::{get_local_ip, DiscoveryService};
+use crate::models::{DeviceInfo, Scheme};
 use crate::multicast::MulticastDiscovery;
+use crate::pinning::PinStore;
 use crate::quic::{QuicClient, QuicServer, QUIC_PORT_OFFSET};
-use crate::server::HttpServer;
+use crate::scan::scan_subnet;
+use crate::server::{device_from_info, HttpServer, ServerState};
+use crate::share::Share;
 use crate::{DEFAULT_PORT, PROTOCOL_ID, PROTOCOL_VERSION};
 
+/// 配置目录下的证书固定记录文件
+const PINS_FILE: &str = "localsend_pins.json";
+
+/// 离线设备的检查间隔
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+/// 停止时等待服务器关闭端口的时间，超时后直接终止任务
+const STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// LocalSend 协议实现
 pub struct LocalSendProtocol {
     info: ProtocolInfo,
-    cert: CertInfo,
+    cert: RwLock<Option<CertInfo>>,
     running: RwLock<bool>,
     discovery: RwLock<Option<DiscoveryService>>,
     multicast: RwLock<Option<MulticastDiscovery>>,
+    /// mDNS 与组播共享的设备表
+    devices: Arc<DeviceTable>,
+    /// 离线设备检查任务
+    sweeper: RwLock<Option<JoinHandle<()>>>,
+    /// HTTPS 与 QUIC 服务器任务
+    servers: RwLock<Vec<JoinHandle<()>>>,
+    /// 取消后服务器关闭端口和已有连接
+    shutdown: RwLock<Option<CancellationToken>>,
+    /// 子网扫描是否进行中
+    scanning: Arc<AtomicBool>,
     client: RwLock<Option<HttpClient>>,
     quic_client: RwLock<Option<QuicClient>>,
+    server_state: RwLock<Option<Arc<ServerState>>>,
+    pins: RwLock<Option<Arc<PinStore>>>,
+    /// 进行中传输的取消令牌（HTTP 发送、QUIC 收发共用）
+    transfers: CancelRegistry,
+    /// 可续传的 HTTP 发送
+    http_resumable: ResumeRegistry,
+    /// 可续传的 QUIC 发送
+    quic_resumable: ResumeRegistry,
     event_tx: mpsc::Sender<Event>,
     event_rx: RwLock<Option<mpsc::Receiver<Event>>>,
     local_info: RwLock<Option<DeviceInfo>>,
@@ -44,46 +80,101 @@ impl LocalSendProtocol {
             .priority(100)
             .build_info();
 
-        let cert = generate_self_signed("UniDrop").expect("Failed to generate certificate");
         let (event_tx, event_rx) = mpsc::channel(256);
 
         Self {
             info,
-            cert,
+            cert: RwLock::new(None),
             running: RwLock::new(false),
             discovery: RwLock::new(None),
             multicast: RwLock::new(None),
+            devices: Arc::new(DeviceTable::new()),
+            sweeper: RwLock::new(None),
+            servers: RwLock::new(Vec::new()),
+            shutdown: RwLock::new(None),
+            scanning: Arc::new(AtomicBool::new(false)),
             client: RwLock::new(None),
             quic_client: RwLock::new(None),
+            server_state: RwLock::new(None),
+            pins: RwLock::new(None),
+            transfers: CancelRegistry::new(),
+            http_resumable: ResumeRegistry::new(),
+            quic_resumable: ResumeRegistry::new(),
             event_tx,
             event_rx: RwLock::new(Some(event_rx)),
             local_info: RwLock::new(None),
         }
     }
 
-    /// 使用 QUIC 发送文件
-    pub async fn send_quic(&self, intent: TransferIntent) -> Result<String> {
+    /// 对端声明了 QUIC 端口时返回 QUIC 客户端和对端的 QUIC 地址
+    fn quic_target(&self, device: &Device) -> Result<(QuicClient, SocketAddr)> {
         let quic_client = self
             .quic_client
             .read()
             .as_ref()
             .cloned()
             .ok_or_else(|| unidrop_core::Error::Protocol("Protocol not started".into()))?;
+        let port = device.quic_port().ok_or_else(|| {
+            unidrop_core::Error::ProtocolNotSupported(format!(
+                "{} does not advertise QUIC",
+                device.name()
+            ))
+        })?;
+        Ok((quic_client, SocketAddr::new(device.ip, port)))
+    }
 
-        let device = self
-            .device(&intent.target)
-            .await
-            .ok_or_else(|| unidrop_core::Error::DeviceNotFound(intent.target.to_string()))?;
+    /// 获取 HTTP 服务器共享状态
+    fn server_state(&self) -> Result<Arc<ServerState>> {
+        self.server_state
+            .read()
+            .clone()
+            .ok_or_else(|| unidrop_core::Error::Protocol("Protocol not started".into()))
+    }
 
-        // QUIC 端口 = HTTP 端口 + 1
-        let quic_addr = std::net::SocketAddr::new(device.ip, device.port + QUIC_PORT_OFFSET);
-        let session_id = quic_client.send_files(quic_addr, intent.files).await?;
-        Ok(session_id)
+    /// 忘记设备已固定的证书（对端重新生成身份后使用）
+    pub fn unpin_device(&self, device: &DeviceId) -> Result<()> {
+        match self.pins.read().as_ref() {
+            Some(pins) => pins.unpin(&device.fingerprint),
+            None => Err(unidrop_core::Error::Protocol("Protocol not started".into())),
+        }
     }
 
-    /// 获取证书信息（用于外部创建 QUIC 服务器）
-    pub fn cert(&self) -> &CertInfo {
-        &self.cert
+    /// 按配对邀请中的地址注册对端，对端指纹须与邀请一致
+    async fn register_invited(
+        &self,
+        client: &HttpClient,
+        invite: &PairingInvite,
+    ) -> Result<Device> {
+        for address in &invite.addresses {
+            match client
+                .register(address.ip(), address.port(), Scheme::Https)
+                .await
+            {
+                Ok(info) if info.fingerprint == invite.fingerprint => {
+                    let device = device_from_info(&info, address.ip());
+                    if self.devices.upsert(device.clone(), info.scheme()) {
+                        let _ = self
+                            .event_tx
+                            .send(Event::device_discovered(device.clone()))
+                            .await;
+                    }
+                    return Ok(device);
+                }
+                Ok(info) => warn!(
+                    "{} is {}, not the invited device",
+                    address, info.fingerprint
+                ),
+                Err(e) => debug!("Invited device not reachable at {}: {}", address, e),
+            }
+        }
+        Err(unidrop_core::Error::DeviceNotFound(
+            invite.target().to_string(),
+        ))
+    }
+
+    /// 获取证书信息（用于外部创建 QUIC 服务器），启动后可用
+    pub fn cert(&self) -> Option<CertInfo> {
+        self.cert.read().clone()
     }
 }
 
@@ -106,67 +197,143 @@ impl Protocol for LocalSendProtocol {
             config.port
         };
 
-        // 创建本地设备信息
-        let local_info = DeviceInfo::new(
-            config.device_name.clone(),
-            self.cert.device_id.clone(),
-            port,
-        );
+        // 加载持久化的证书，保证指纹在重启后不变
+        let cert = cert::load_or_create(&config.identity_store())?;
+        *self.cert.write() = Some(cert.clone());
+
+        // 创建 QUIC 服务器（可选，用于 UniDrop 之间的高速传输）
+        // 默认端口被占用时改用随机端口，实际端口通过设备信息声明
+        let quic_server = QuicServer::new(port + QUIC_PORT_OFFSET, &cert, config.save_dir.clone())
+            .or_else(|_| QuicServer::new(0, &cert, config.save_dir.clone()));
+        let quic_server = match quic_server {
+            Ok(server) => Some(server),
+            Err(e) => {
+                warn!("Failed to start QUIC server: {} (QUIC disabled)", e);
+                None
+            }
+        };
+        let quic_port = quic_server
+            .as_ref()
+            .and_then(|server| server.local_addr())
+            .map(|addr| addr.port());
+
+        // 创建本地设备信息，关闭加密时使用明文 http
+        let scheme = if config.encryption {
+            Scheme::Https
+        } else {
+            Scheme::Http
+        };
+        let mut local_info =
+            DeviceInfo::new(config.device_name.clone(), cert.device_id.clone(), port)
+                .with_scheme(scheme);
+        if let Some(quic_port) = quic_port {
+            local_info = local_info.with_quic_port(quic_port);
+        }
 
         *self.local_info.write() = Some(local_info.clone());
 
-        // 创建客户端
-        *self.client.write() = Some(HttpClient::new(local_info.clone()));
-        *self.quic_client.write() = Some(QuicClient::new()?);
+        // 创建客户端，两者共享证书固定记录，并出示本机证书供对端验证身份
+        let pins = Arc::new(PinStore::load(config.config_dir.join(PINS_FILE)));
+        *self.pins.write() = Some(pins.clone());
+        *self.client.write() = Some(
+            HttpClient::new(local_info.clone(), pins.clone(), self.event_tx.clone())
+                .with_devices(self.devices.clone())
+                .with_cancel_registry(self.transfers.clone())
+                .with_resume_registry(self.http_resumable.clone())
+                .with_identity(cert.clone()),
+        );
+        *self.quic_client.write() = Some(
+            QuicClient::new()?
+                .with_pins(pins)
+                .with_cancel_registry(self.transfers.clone())
+                .with_resume_registry(self.quic_resumable.clone())
+                .with_concurrency(config.concurrent_files)
+                .with_local_info(local_info.clone())
+                .with_identity(cert.clone())
+                .with_event_sender(self.event_tx.clone()),
+        );
 
         // 启动 mDNS 发现服务
-        let mut discovery = DiscoveryService::new(local_info.clone(), self.event_tx.clone());
+        let mut discovery = DiscoveryService::new(
+            local_info.clone(),
+            self.devices.clone(),
+            self.event_tx.clone(),
+        );
         discovery.start()?;
         *self.discovery.write() = Some(discovery);
 
         // 启动 UDP multicast 发现服务（LocalSend 主要发现机制）
-        let mut multicast = MulticastDiscovery::new(local_info.clone(), self.event_tx.clone());
+        let mut multicast = MulticastDiscovery::new(
+            local_info.clone(),
+            self.devices.clone(),
+            self.event_tx.clone(),
+        );
+        if let Some(client) = self.client.read().clone() {
+            multicast = multicast.with_client(client);
+        }
         if let Err(e) = multicast.start() {
             tracing::warn!("Failed to start multicast discovery: {} (mDNS only)", e);
         } else {
             *self.multicast.write() = Some(multicast);
         }
 
+        // 定期移除离线设备
+        let liveness = config
+            .liveness_check
+            .then(|| self.client.read().clone())
+            .flatten();
+        *self.sweeper.write() = Some(tokio::spawn(sweep_devices(
+            self.devices.clone(),
+            liveness,
+            config.device_ttl,
+            self.event_tx.clone(),
+        )));
+
         // 启动 HTTPS 服务器（在后台任务中）
+        let shutdown = CancellationToken::new();
+        let mut servers = Vec::new();
         let server = HttpServer::new(
             local_info,
-            config.save_dir.clone(),
+            self.devices.clone(),
             config.pin.clone(),
+            config.collision_policy,
+            config.keep_subdirs,
             self.event_tx.clone(),
-            &self.cert,
-        )?;
+            &cert,
+        )?
+        .with_shutdown(shutdown.clone());
+        let server_state = server.state();
+        *self.server_state.write() = Some(server_state.clone());
 
-        tokio::spawn(async move {
+        servers.push(tokio::spawn(async move {
             if let Err(e) = server.start().await {
                 tracing::error!("HTTPS server error: {}", e);
             }
-        });
-
-        // 启动 QUIC 服务器（可选，用于 UniDrop 之间的高速传输）
-        let quic_port = port + QUIC_PORT_OFFSET;
-        let cert_clone = self.cert.clone();
-        let quic_save_dir = config.save_dir.clone();
-
-        tokio::spawn(async move {
-            match QuicServer::new(quic_port, &cert_clone, quic_save_dir) {
-                Ok(quic_server) => {
-                    if let Err(e) = quic_server.run().await {
-                        tracing::error!("QUIC server error: {}", e);
-                    }
-                }
-                Err(e) => {
-                    tracing::warn!("Failed to start QUIC server: {} (QUIC disabled)", e);
+        }));
+
+        // 启动 QUIC 服务器
+        if let Some(quic_server) = quic_server {
+            let quic_server = quic_server
+                .with_collision_policy(config.collision_policy, config.keep_subdirs)
+                .with_cancel_registry(self.transfers.clone())
+                .with_pending(server_state.pending.clone())
+                .with_pin_guard(server_state.pin.clone())
+                .with_event_sender(self.event_tx.clone())
+                .with_shutdown(shutdown.clone());
+            servers.push(tokio::spawn(async move {
+                if let Err(e) = quic_server.run().await {
+                    tracing::error!("QUIC server error: {}", e);
                 }
-            }
-        });
+            }));
+        }
+        *self.servers.write() = servers;
+        *self.shutdown.write() = Some(shutdown);
 
         *self.running.write() = true;
-        info!("LocalSend protocol started on port {} (QUIC: {})", port, quic_port);
+        info!(
+            "LocalSend protocol started on port {} (QUIC: {:?})",
+            port, quic_port
+        );
 
         Ok(())
     }
@@ -181,8 +348,32 @@ impl Protocol for LocalSendProtocol {
         if let Some(mut discovery) = self.discovery.write().take() {
             discovery.stop();
         }
+        if let Some(sweeper) = self.sweeper.write().take() {
+            sweeper.abort();
+        }
+        let multicast = self.multicast.write().take();
+        if let Some(mut multicast) = multicast {
+            let _ = tokio::task::spawn_blocking(move || multicast.stop()).await;
+        }
+
+        // 服务器关闭监听端口后退出，之后可以重新启动
+        if let Some(shutdown) = self.shutdown.write().take() {
+            shutdown.cancel();
+        }
+        let servers = std::mem::take(&mut *self.servers.write());
+        for mut server in servers {
+            if tokio::time::timeout(STOP_TIMEOUT, &mut server)
+                .await
+                .is_err()
+            {
+                warn!("Server did not stop in time, aborting");
+                server.abort();
+            }
+        }
 
         *self.client.write() = None;
+        *self.quic_client.write() = None;
+        *self.server_state.write() = None;
         *self.running.write() = false;
 
         info!("LocalSend protocol stopped");
@@ -194,42 +385,70 @@ impl Protocol for LocalSendProtocol {
     }
 
     async fn devices(&self) -> Vec<Device> {
-        let mut devices = std::collections::HashMap::new();
+        self.devices.list()
+    }
 
-        // 从 mDNS 获取设备
-        if let Some(discovery) = self.discovery.read().as_ref() {
-            for device in discovery.devices() {
-                devices.insert(device.peer.id.fingerprint.clone(), device);
-            }
+    async fn device(&self, id: &DeviceId) -> Option<Device> {
+        if id.protocol.as_str() != PROTOCOL_ID {
+            return None;
         }
 
-        // 从 multicast 获取设备（可能有重复，用 fingerprint 去重）
+        self.devices.get(&id.fingerprint)
+    }
+
+    /// 主动扫描：重发组播公告、发起 mDNS 查询，并在后台扫描本机子网
+    ///
+    /// 子网扫描用于组播被屏蔽的网络，扫描进行中时不会重复启动。
+    async fn scan(&self) -> Result<()> {
+        let client = self
+            .client
+            .read()
+            .as_ref()
+            .cloned()
+            .ok_or_else(|| unidrop_core::Error::Protocol("Protocol not started".into()))?;
+        let local_info = self
+            .local_info
+            .read()
+            .clone()
+            .ok_or_else(|| unidrop_core::Error::Protocol("Protocol not started".into()))?;
+
         if let Some(multicast) = self.multicast.read().as_ref() {
-            for device in multicast.devices() {
-                devices.insert(device.peer.id.fingerprint.clone(), device);
+            if let Err(e) = multicast.send_announcement() {
+                warn!("Failed to send multicast announcement: {}", e);
             }
         }
-
-        devices.into_values().collect()
-    }
-
-    async fn device(&self, id: &DeviceId) -> Option<Device> {
-        if id.protocol.as_str() != PROTOCOL_ID {
-            return None;
+        if let Some(discovery) = self.discovery.read().as_ref() {
+            if let Err(e) = discovery.query() {
+                warn!("Failed to send mDNS query: {}", e);
+            }
         }
 
-        // 先从 mDNS 查找
-        if let Some(device) = self.discovery.read().as_ref().and_then(|d| d.device(&id.fingerprint)) {
-            return Some(device);
+        let Some(local_ip) = get_local_ip().and_then(|ip| ip.parse().ok()) else {
+            debug!("No local IPv4 address, skipping subnet scan");
+            return Ok(());
+        };
+        if self.scanning.swap(true, Ordering::SeqCst) {
+            debug!("Subnet scan already running");
+            return Ok(());
         }
 
-        // 再从 multicast 查找
-        self.multicast.read().as_ref().and_then(|m| m.device(&id.fingerprint))
-    }
+        let scanning = self.scanning.clone();
+        let devices = self.devices.clone();
+        let event_tx = self.event_tx.clone();
+        tokio::spawn(async move {
+            scan_subnet(
+                client,
+                local_ip,
+                local_info.port,
+                local_info.scheme(),
+                local_info.fingerprint,
+                devices,
+                event_tx,
+            )
+            .await;
+            scanning.store(false, Ordering::SeqCst);
+        });
 
-    async fn scan(&self) -> Result<()> {
-        // mDNS 是被动发现，这里可以触发一次主动查询
-        debug!("Scan requested (mDNS is passive, no action needed)");
         Ok(())
     }
 
@@ -246,47 +465,195 @@ impl Protocol for LocalSendProtocol {
             .await
             .ok_or_else(|| unidrop_core::Error::DeviceNotFound(intent.target.to_string()))?;
 
-        let session_id = client.send_files(&device, intent.files).await?;
+        let pin = intent.pin.as_deref();
+        let session_id = match &intent.text {
+            Some(text) => client.send_text(&device, text, pin).await?,
+            None => client.send_files(&device, intent.sources, pin).await?,
+        };
         Ok(session_id)
     }
 
     async fn send_quic(&self, intent: TransferIntent) -> Result<String> {
-        let quic_client = self
-            .quic_client
-            .read()
-            .as_ref()
-            .cloned()
-            .ok_or_else(|| unidrop_core::Error::Protocol("Protocol not started".into()))?;
-
         let device = self
             .device(&intent.target)
             .await
             .ok_or_else(|| unidrop_core::Error::DeviceNotFound(intent.target.to_string()))?;
 
-        // QUIC 端口 = HTTP 端口 + 1
-        let quic_addr = std::net::SocketAddr::new(device.ip, device.port + QUIC_PORT_OFFSET);
+        let (quic_client, quic_addr) = self.quic_target(&device)?;
         info!("Sending via QUIC to {}", quic_addr);
 
-        let session_id = quic_client.send_files(quic_addr, intent.files).await?;
+        let fingerprint = &device.peer.id.fingerprint;
+        let pin = intent.pin.as_deref();
+        let session_id = match &intent.text {
+            Some(text) => {
+                quic_client
+                    .send_text(quic_addr, fingerprint, text, pin)
+                    .await?
+            }
+            None => {
+                quic_client
+                    .send_files(quic_addr, fingerprint, intent.sources, pin)
+                    .await?
+            }
+        };
         Ok(session_id)
     }
 
-    async fn accept(&self, request_id: &str, _save_dir: PathBuf) -> Result<()> {
-        // HTTP 服务器自动处理接收
+    async fn accept(&self, request_id: &str, save_dir: PathBuf) -> Result<()> {
         debug!("Accept transfer: {}", request_id);
-        Ok(())
+        self.server_state()?.accept(request_id, save_dir)
+    }
+
+    async fn accept_files(
+        &self,
+        request_id: &str,
+        file_ids: Vec<String>,
+        save_dir: PathBuf,
+    ) -> Result<()> {
+        debug!(
+            "Accept {} files of transfer: {}",
+            file_ids.len(),
+            request_id
+        );
+        self.server_state()?
+            .accept_files(request_id, file_ids, save_dir)
     }
 
     async fn reject(&self, request_id: &str) -> Result<()> {
         debug!("Reject transfer: {}", request_id);
-        Ok(())
+        self.server_state()?.reject(request_id)
     }
 
     async fn cancel(&self, transfer_id: &str) -> Result<()> {
         debug!("Cancel transfer: {}", transfer_id);
+
+        // 发送中的传输和 QUIC 接收由各自的任务处理取消
+        if self.transfers.cancel(transfer_id) {
+            return Ok(());
+        }
+
+        // HTTP 接收会话
+        if self.server_state()?.cancel(transfer_id).await {
+            return Ok(());
+        }
+
+        Err(unidrop_core::Error::InvalidSession(transfer_id.to_string()))
+    }
+
+    /// 续传失败的发送，沿用原来的传输方式
+    ///
+    /// QUIC 续传沿用原会话 ID；HTTP 续传创建新会话并返回新的会话 ID。
+    async fn resume(&self, transfer_id: &str) -> Result<String> {
+        let (send, quic) = match self.quic_resumable.get(transfer_id) {
+            Some(send) => (send, true),
+            None => match self.http_resumable.get(transfer_id) {
+                Some(send) => (send, false),
+                None => return Err(unidrop_core::Error::InvalidSession(transfer_id.to_string())),
+            },
+        };
+
+        let device = self
+            .device(&send.intent.target)
+            .await
+            .ok_or_else(|| unidrop_core::Error::DeviceNotFound(send.intent.target.to_string()))?;
+        info!("Resuming transfer {} to {}", transfer_id, device.name());
+
+        let sources = send.intent.sources;
+        let pin = send.intent.pin.as_deref();
+        let session_id =
+            if quic {
+                let (quic_client, quic_addr) = self.quic_target(&device)?;
+                let fingerprint = &device.peer.id.fingerprint;
+                quic_client
+                    .resume(quic_addr, fingerprint, &send.session_id, sources, pin)
+                    .await?
+            } else {
+                let client =
+                    self.client.read().as_ref().cloned().ok_or_else(|| {
+                        unidrop_core::Error::Protocol("Protocol not started".into())
+                    })?;
+                client
+                    .resume(&device, &send.session_id, sources, pin)
+                    .await?
+            };
+
+        self.quic_resumable.remove(transfer_id);
+        self.http_resumable.remove(transfer_id);
+        Ok(session_id)
+    }
+
+    async fn share(&self, files: Vec<PathBuf>, pin: Option<String>) -> Result<String> {
+        let state = self.server_state()?;
+        let share = Share::new(files, pin).await?;
+        Ok(state.start_share(share))
+    }
+
+    async fn stop_share(&self) -> Result<()> {
+        if self.server_state()?.stop_share() {
+            info!("Sharing stopped");
+        }
         Ok(())
     }
 
+    async fn download(
+        &self,
+        target: &DeviceId,
+        pin: Option<String>,
+        save_dir: PathBuf,
+    ) -> Result<String> {
+        let client = self
+            .client
+            .read()
+            .as_ref()
+            .cloned()
+            .ok_or_else(|| unidrop_core::Error::Protocol("Protocol not started".into()))?;
+        let collision_policy = self.server_state()?.collision_policy;
+
+        let device = self
+            .device(target)
+            .await
+            .ok_or_else(|| unidrop_core::Error::DeviceNotFound(target.to_string()))?;
+
+        client
+            .download(&device, pin.as_deref(), &save_dir, collision_policy)
+            .await
+    }
+
+    async fn start_pairing(&self) -> Result<PairingInvite> {
+        let state = self.server_state()?;
+        let local_info = &state.local_info;
+        let target = DeviceId::new(ProtocolId::new(PROTOCOL_ID), &local_info.fingerprint);
+        let mut invite = PairingInvite::new(target, state.pairing.start());
+        if let Some(ip) = get_local_ip().and_then(|ip| ip.parse().ok()) {
+            invite = invite.with_address(SocketAddr::new(ip, local_info.port));
+        }
+        info!("Waiting for pairing");
+        Ok(invite)
+    }
+
+    async fn stop_pairing(&self) -> Result<()> {
+        if self.server_state()?.pairing.stop() {
+            info!("Pairing stopped");
+        }
+        Ok(())
+    }
+
+    async fn pair(&self, invite: &PairingInvite) -> Result<Device> {
+        let client = self
+            .client
+            .read()
+            .as_ref()
+            .cloned()
+            .ok_or_else(|| unidrop_core::Error::Protocol("Protocol not started".into()))?;
+
+        let device = match self.devices.get(&invite.fingerprint) {
+            Some(device) => device,
+            None => self.register_invited(&client, invite).await?,
+        };
+        client.pair(&device, &invite.code).await?;
+        Ok(device)
+    }
+
     fn subscribe(&self) -> mpsc::Receiver<Event> {
         // 取出 event_rx（只能取一次）
         self.event_rx
@@ -296,6 +663,45 @@ impl Protocol for LocalSendProtocol {
     }
 }
 
+/// 定期检查超过 TTL 未出现的设备
+///
+/// 提供客户端时先请求设备的 `/info`，仍能响应的设备刷新在线时间；
+/// 其余设备移除并发出 `DeviceLost`。
+async fn sweep_devices(
+    devices: Arc<DeviceTable>,
+    client: Option<HttpClient>,
+    ttl: Duration,
+    event_tx: mpsc::Sender<Event>,
+) {
+    let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let checks = devices.expired(ttl).into_iter().map(|device| {
+            let client = client.clone();
+            async move {
+                let alive = match &client {
+                    Some(client) => client.info(&device).await.is_ok(),
+                    None => false,
+                };
+                (device, alive)
+            }
+        });
+
+        for (device, alive) in futures::future::join_all(checks).await {
+            let fingerprint = &device.peer.id.fingerprint;
+            if alive {
+                devices.touch(fingerprint);
+            } else if devices.remove_expired(fingerprint, ttl).is_some() {
+                info!("Device lost: {} ({})", device.name(), device.address());
+                let _ = event_tx
+                    .send(Event::device_lost(device.id().clone()).with_protocol(PROTOCOL_ID))
+                    .await;
+            }
+        }
+    }
+}
+
 impl Default for LocalSendProtocol {
     fn default() -> Self {
         Self::new()
@@ -333,3 +739,45 @@ impl Default for LocalSendFactory {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_stop_releases_ports() {
+        let dir = std::env::temp_dir().join(format!("unidrop-restart-{}", uuid::Uuid::new_v4()));
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let config = ProtocolConfig {
+            port,
+            save_dir: dir.join("inbox"),
+            config_dir: dir.clone(),
+            ..Default::default()
+        };
+
+        let protocol = LocalSendProtocol::new();
+        protocol.start(config.clone()).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .is_ok());
+
+        // 停止后不再接受连接，重新启动可以绑定同一端口
+        protocol.stop().await.unwrap();
+        assert!(tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .is_err());
+        protocol.start(config).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(tokio::net::TcpStream::connect(("127.0.0.1", port))
+            .await
+            .is_ok());
+
+        protocol.stop().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
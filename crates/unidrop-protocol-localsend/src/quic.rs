@@ -7,21 +7,54 @@
 //! - 更好的拥塞控制
 
 use bytes::Bytes;
+use futures::stream::FuturesUnordered;
+use futures::{StreamExt, TryStreamExt};
+use parking_lot::Mutex;
 use quinn::{ClientConfig, Endpoint, RecvStream, SendStream, ServerConfig};
-use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::pki_types::CertificateDer;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::fs::File;
+use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
+use unidrop_core::{
+    create_parent_dirs, resolve_save_path, CancelRegistry, CancellationToken, CollisionPolicy,
+    DeviceId, Event, FileInfo, PartFile, PinGuard, PinStatus, ProgressTracker, ProtocolId,
+    ResumableSend, ResumeRegistry, TransferIntent, TransferProgress, TransferRequest,
+    TransferSource, DEFAULT_CONCURRENT_FILES, MAX_MESSAGE_LEN,
+};
+
 use crate::cert::CertInfo;
+use crate::models::DeviceInfo;
+use crate::pinning::{cert_sha256, is_identity, ClientIdentityVerifier, PinStore, PinnedVerifier};
+use crate::server::{PendingUploads, UploadDecision, ACCEPT_TIMEOUT};
 
 /// QUIC 传输端口（与 HTTP 端口区分）
 pub const QUIC_PORT_OFFSET: u16 = 1; // 53318
 
+/// 取消传输时关闭连接使用的错误码
+const CANCELLED_CODE: u32 = 1;
+
+/// 发出取消消息后等待对端关闭连接的时间
+const CANCEL_GRACE: Duration = Duration::from_secs(2);
+
+/// 建立连接（含握手）的超时，UDP 被拦截时不会收到任何回应
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 接收方允许同时打开的流数（文件流加一个控制流），超出时发送方等待
+const MAX_CONCURRENT_STREAMS: u32 = 32;
+
+/// 单个流的接收窗口，写盘跟不上时发送方在此阻塞
+const STREAM_RECEIVE_WINDOW: u32 = 4 * 1024 * 1024;
+
+/// 整个连接的接收窗口（同时也是发送方未确认数据的上限）
+const CONNECTION_WINDOW: u32 = 32 * 1024 * 1024;
+
 /// 传输消息类型
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Message {
@@ -29,12 +62,30 @@ pub enum Message {
     TransferRequest {
         session_id: String,
         files: Vec<FileMetadata>,
+        /// 发送方设备信息，供接收方确认时识别
+        #[serde(default)]
+        sender: Option<DeviceInfo>,
+        /// 接收方要求的 PIN
+        #[serde(default)]
+        pin: Option<String>,
+    },
+    /// 续传请求，与之前失败的传输使用同一会话 ID 和文件列表
+    ResumeRequest {
+        session_id: String,
+        files: Vec<FileMetadata>,
+        #[serde(default)]
+        sender: Option<DeviceInfo>,
+        #[serde(default)]
+        pin: Option<String>,
     },
-    /// 传输响应
+    /// 传输响应，拒绝时 tokens 为空
     TransferResponse {
         session_id: String,
         accepted: bool,
-        tokens: Vec<String>, // 每个文件一个 token
+        tokens: Vec<String>, // 每个文件一个 token，空字符串表示跳过该文件
+        /// 每个文件已接收的字节数，发送方从该偏移继续
+        #[serde(default)]
+        offsets: Vec<u64>,
     },
     /// 文件头
     FileHeader {
@@ -42,9 +93,24 @@ pub enum Message {
         token: String,
         file_name: String,
         size: u64,
+        /// 文件流内容在文件中的起始偏移
+        #[serde(default)]
+        offset: u64,
     },
     /// 传输完成
     TransferComplete { session_id: String },
+    /// 取消传输（任一方均可发送，收到方关闭连接）
+    Cancel { session_id: String },
+    /// 文本消息，接收方回复 TransferComplete
+    Text {
+        session_id: String,
+        sender: Option<DeviceInfo>,
+        text: String,
+        #[serde(default)]
+        pin: Option<String>,
+    },
+    /// PIN 缺失、错误或尝试次数过多，接收方随后关闭连接
+    PinRejected { status: PinStatus },
     /// 错误
     Error { message: String },
 }
@@ -56,12 +122,22 @@ pub struct FileMetadata {
     pub name: String,
     pub size: u64,
     pub mime_type: Option<String>,
+    /// SHA-256（小写十六进制），接收方据此校验
+    #[serde(default)]
+    pub sha256: Option<String>,
 }
 
 /// QUIC 服务器
 pub struct QuicServer {
     endpoint: Endpoint,
     save_dir: PathBuf,
+    collision_policy: CollisionPolicy,
+    keep_subdirs: bool,
+    transfers: CancelRegistry,
+    pending: Option<Arc<PendingUploads>>,
+    pin: Arc<PinGuard>,
+    event_tx: Option<mpsc::Sender<Event>>,
+    shutdown: CancellationToken,
 }
 
 impl QuicServer {
@@ -75,15 +151,81 @@ impl QuicServer {
 
         info!("QUIC server listening on {}", addr);
 
-        Ok(Self { endpoint, save_dir })
+        Ok(Self {
+            endpoint,
+            save_dir,
+            collision_policy: CollisionPolicy::default(),
+            keep_subdirs: true,
+            transfers: CancelRegistry::new(),
+            pending: None,
+            pin: Arc::new(PinGuard::new(None)),
+            event_tx: None,
+            shutdown: CancellationToken::new(),
+        })
+    }
+
+    /// 设置重名文件处理策略
+    pub fn with_collision_policy(mut self, policy: CollisionPolicy, keep_subdirs: bool) -> Self {
+        self.collision_policy = policy;
+        self.keep_subdirs = keep_subdirs;
+        self
+    }
+
+    /// 设置取消令牌登记表（与协议层共享，用于取消进行中的接收）
+    pub fn with_cancel_registry(mut self, transfers: CancelRegistry) -> Self {
+        self.transfers = transfers;
+        self
+    }
+
+    /// 设置等待确认的请求表（与 HTTP 服务器共享），传输请求经上层接受后才开始接收
+    ///
+    /// 未设置时自动接受所有请求。
+    pub fn with_pending(mut self, pending: Arc<PendingUploads>) -> Self {
+        self.pending = Some(pending);
+        self
+    }
+
+    /// 设置 PIN 校验（与 HTTP 服务器共享，失败次数合并计算）
+    pub fn with_pin_guard(mut self, pin: Arc<PinGuard>) -> Self {
+        self.pin = pin;
+        self
+    }
+
+    /// 设置事件通道（用于上报传输请求和进度）
+    pub fn with_event_sender(mut self, event_tx: mpsc::Sender<Event>) -> Self {
+        self.event_tx = Some(event_tx);
+        self
+    }
+
+    /// 设置停止令牌，取消后关闭端点和所有连接
+    pub fn with_shutdown(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = shutdown;
+        self
     }
 
     /// 启动服务器
     pub async fn run(&self) -> unidrop_core::Result<()> {
         info!("QUIC server started, waiting for connections...");
 
-        while let Some(conn) = self.endpoint.accept().await {
+        loop {
+            let conn = tokio::select! {
+                _ = self.shutdown.cancelled() => {
+                    self.endpoint.close(0u32.into(), b"stopped");
+                    self.endpoint.wait_idle().await;
+                    info!("QUIC server stopped");
+                    break;
+                }
+                conn = self.endpoint.accept() => conn,
+            };
+            let Some(conn) = conn else {
+                break;
+            };
             let save_dir = self.save_dir.clone();
+            let naming = (self.collision_policy, self.keep_subdirs);
+            let transfers = self.transfers.clone();
+            let pending = self.pending.clone();
+            let pin = self.pin.clone();
+            let event_tx = self.event_tx.clone();
 
             tokio::spawn(async move {
                 match conn.await {
@@ -91,8 +233,16 @@ impl QuicServer {
                         let remote = connection.remote_address();
                         info!("QUIC connection from {}", remote);
 
-                        if let Err(e) = handle_connection(connection, save_dir).await {
-                            error!("Connection error from {}: {}", remote, e);
+                        match handle_connection(
+                            connection, save_dir, naming, transfers, pending, pin, event_tx,
+                        )
+                        .await
+                        {
+                            Ok(()) => {}
+                            Err(unidrop_core::Error::Cancelled) => {
+                                info!("Transfer from {} cancelled", remote);
+                            }
+                            Err(e) => error!("Connection error from {}: {}", remote, e),
                         }
                     }
                     Err(e) => {
@@ -115,58 +265,231 @@ impl QuicServer {
 #[derive(Clone)]
 pub struct QuicClient {
     endpoint: Endpoint,
+    pins: Arc<PinStore>,
+    transfers: CancelRegistry,
+    resumable: ResumeRegistry,
+    concurrency: usize,
+    local_info: Option<DeviceInfo>,
+    /// 出示给对端的客户端证书
+    identity: Option<CertInfo>,
+    event_tx: Option<mpsc::Sender<Event>>,
 }
 
 impl QuicClient {
     /// 创建 QUIC 客户端
     pub fn new() -> unidrop_core::Result<Self> {
-        let client_config = create_client_config()?;
-
-        let mut endpoint = Endpoint::client("0.0.0.0:0".parse().unwrap())
+        let endpoint = Endpoint::client("0.0.0.0:0".parse().unwrap())
             .map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
 
-        endpoint.set_default_client_config(client_config);
+        Ok(Self {
+            endpoint,
+            pins: Arc::new(PinStore::in_memory()),
+            transfers: CancelRegistry::new(),
+            resumable: ResumeRegistry::new(),
+            concurrency: DEFAULT_CONCURRENT_FILES,
+            local_info: None,
+            identity: None,
+            event_tx: None,
+        })
+    }
 
-        Ok(Self { endpoint })
+    /// 设置证书固定存储（与 HTTPS 客户端共享）
+    pub fn with_pins(mut self, pins: Arc<PinStore>) -> Self {
+        self.pins = pins;
+        self
     }
 
-    /// 发送文件到目标
-    pub async fn send_files(
+    /// 设置取消令牌登记表（与协议层共享，用于取消进行中的发送）
+    pub fn with_cancel_registry(mut self, transfers: CancelRegistry) -> Self {
+        self.transfers = transfers;
+        self
+    }
+
+    /// 设置失败发送的登记表（与协议层共享，用于续传）
+    pub fn with_resume_registry(mut self, resumable: ResumeRegistry) -> Self {
+        self.resumable = resumable;
+        self
+    }
+
+    /// 设置同时发送的文件数（每个文件占用一个流）
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// 设置本机设备信息（随文本消息发送，供对端识别发送方）
+    pub fn with_local_info(mut self, local_info: DeviceInfo) -> Self {
+        self.local_info = Some(local_info);
+        self
+    }
+
+    /// 设置客户端证书（本机的持久化证书），供对端验证发送方身份
+    pub fn with_identity(mut self, cert: CertInfo) -> Self {
+        self.identity = Some(cert);
+        self
+    }
+
+    /// 设置事件通道（用于上报传输进度）
+    pub fn with_event_sender(mut self, event_tx: mpsc::Sender<Event>) -> Self {
+        self.event_tx = Some(event_tx);
+        self
+    }
+
+    /// 连接目标并校验其证书
+    async fn connect(
         &self,
         target: SocketAddr,
-        files: Vec<PathBuf>,
-    ) -> unidrop_core::Result<String> {
+        fingerprint: &str,
+    ) -> unidrop_core::Result<quinn::Connection> {
         let server_name = "unidrop"; // 自签名证书的名称
 
         info!("Connecting to {} via QUIC...", target);
 
-        let connection = self
+        let verifier = PinnedVerifier::new(fingerprint, self.pins.get(fingerprint));
+        let client_config = create_client_config(&verifier, self.identity.as_ref())?;
+
+        // 建立连接失败时对端没有收到任何请求，返回 `Connection` 以便换用其他传输
+        let connecting = self
             .endpoint
-            .connect(target, server_name)
-            .map_err(|e| unidrop_core::Error::Network(e.to_string()))?
+            .connect_with(client_config, target, server_name)
+            .map_err(|e| unidrop_core::Error::Connection(e.to_string()))?;
+        let connection = match tokio::time::timeout(CONNECT_TIMEOUT, connecting).await {
+            Ok(Ok(connection)) => connection,
+            Ok(Err(e)) => {
+                return Err(verifier
+                    .mismatch_error()
+                    .unwrap_or_else(|| unidrop_core::Error::Connection(e.to_string())))
+            }
+            Err(_) => {
+                return Err(unidrop_core::Error::Connection(format!(
+                    "QUIC handshake with {} timed out",
+                    target
+                )))
+            }
+        };
+
+        if let Err(e) = verifier.pin_on_first_use(&self.pins) {
+            warn!("Failed to pin certificate of {}: {}", fingerprint, e);
+        }
+
+        info!("QUIC connection established");
+        Ok(connection)
+    }
+
+    /// 发送文本消息到目标
+    pub async fn send_text(
+        &self,
+        target: SocketAddr,
+        fingerprint: &str,
+        text: &str,
+        pin: Option<&str>,
+    ) -> unidrop_core::Result<String> {
+        if text.len() > MAX_MESSAGE_LEN {
+            return Err(unidrop_core::Error::Protocol(format!(
+                "Text message exceeds {} bytes",
+                MAX_MESSAGE_LEN
+            )));
+        }
+
+        let connection = self.connect(target, fingerprint).await?;
+        let session_id = uuid::Uuid::new_v4().to_string();
+
+        let (mut send, mut recv) = connection
+            .open_bi()
             .await
             .map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
 
-        info!("QUIC connection established");
+        let message = Message::Text {
+            session_id: session_id.clone(),
+            sender: self.local_info.clone(),
+            text: text.to_string(),
+            pin: pin.map(str::to_string),
+        };
+        send_message(&mut send, &message).await?;
+
+        match recv_message::<Message>(&mut recv).await? {
+            Message::TransferComplete { .. } => {}
+            Message::PinRejected { status } => {
+                connection.close(0u32.into(), b"pin");
+                status.into_result(&target.to_string())?;
+            }
+            Message::Error { message } => {
+                return Err(unidrop_core::Error::Protocol(message));
+            }
+            _ => {
+                return Err(unidrop_core::Error::Protocol(
+                    "Unexpected response".to_string(),
+                ));
+            }
+        }
+        connection.close(0u32.into(), b"done");
+
+        emit(&self.event_tx, Event::transfer_completed(&session_id)).await;
+        info!("Text message sent: {}", session_id);
+        Ok(session_id)
+    }
 
-        // 生成 session ID
+    /// 发送文件到目标
+    ///
+    /// `fingerprint` 为对端广播的设备指纹，用于校验其证书；`pin` 为接收方要求的 PIN。
+    pub async fn send_files(
+        &self,
+        target: SocketAddr,
+        fingerprint: &str,
+        files: Vec<TransferSource>,
+        pin: Option<&str>,
+    ) -> unidrop_core::Result<String> {
         let session_id = uuid::Uuid::new_v4().to_string();
+        self.send_session(target, fingerprint, session_id, files, false, pin)
+            .await
+    }
+
+    /// 续传失败的发送，接收方保留的未完成文件从已接收的偏移继续
+    pub async fn resume(
+        &self,
+        target: SocketAddr,
+        fingerprint: &str,
+        session_id: &str,
+        files: Vec<TransferSource>,
+        pin: Option<&str>,
+    ) -> unidrop_core::Result<String> {
+        self.send_session(
+            target,
+            fingerprint,
+            session_id.to_string(),
+            files,
+            true,
+            pin,
+        )
+        .await
+    }
+
+    async fn send_session(
+        &self,
+        target: SocketAddr,
+        fingerprint: &str,
+        session_id: String,
+        files: Vec<TransferSource>,
+        resume: bool,
+        pin: Option<&str>,
+    ) -> unidrop_core::Result<String> {
+        // 长度未知的流需要先读完，放在建立连接之前
+        let files =
+            futures::future::try_join_all(files.into_iter().map(TransferSource::sized)).await?;
+        let connection = self.connect(target, fingerprint).await?;
 
         // 收集文件元数据
-        let mut file_metas = Vec::new();
-        for (i, path) in files.iter().enumerate() {
-            let metadata = tokio::fs::metadata(path).await?;
-            file_metas.push(FileMetadata {
+        let file_metas: Vec<FileMetadata> = files
+            .iter()
+            .enumerate()
+            .map(|(i, source)| FileMetadata {
                 id: format!("file_{}", i),
-                name: path
-                    .file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or("unknown")
-                    .to_string(),
-                size: metadata.len(),
-                mime_type: None,
-            });
-        }
+                name: source.name.clone(),
+                size: source.size().unwrap_or(0),
+                mime_type: Some(source.mime_type.clone()),
+                sha256: source.hash().map(str::to_string),
+            })
+            .collect();
 
         // 打开控制流
         let (mut send, mut recv) = connection
@@ -175,24 +498,42 @@ impl QuicClient {
             .map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
 
         // 发送传输请求
-        let request = Message::TransferRequest {
-            session_id: session_id.clone(),
-            files: file_metas.clone(),
+        let request = if resume {
+            Message::ResumeRequest {
+                session_id: session_id.clone(),
+                files: file_metas.clone(),
+                sender: self.local_info.clone(),
+                pin: pin.map(str::to_string),
+            }
+        } else {
+            Message::TransferRequest {
+                session_id: session_id.clone(),
+                files: file_metas.clone(),
+                sender: self.local_info.clone(),
+                pin: pin.map(str::to_string),
+            }
         };
         send_message(&mut send, &request).await?;
 
         // 等待响应
         let response: Message = recv_message(&mut recv).await?;
-        let tokens = match response {
+        let (tokens, offsets) = match response {
             Message::TransferResponse {
                 accepted: true,
                 tokens,
+                offsets,
                 ..
-            } => tokens,
+            } => (tokens, offsets),
             Message::TransferResponse {
                 accepted: false, ..
             } => {
-                return Err(unidrop_core::Error::Protocol("Transfer rejected".to_string()));
+                connection.close(0u32.into(), b"rejected");
+                return Err(unidrop_core::Error::Rejected);
+            }
+            Message::PinRejected { status } => {
+                connection.close(0u32.into(), b"pin");
+                status.into_result(&target.to_string())?;
+                return Err(unidrop_core::Error::Rejected);
             }
             Message::Error { message } => {
                 return Err(unidrop_core::Error::Protocol(message));
@@ -205,63 +546,192 @@ impl QuicClient {
         };
 
         // 发送每个文件（使用独立的流）
-        for (i, path) in files.iter().enumerate() {
-            let meta = &file_metas[i];
-            let token = &tokens[i];
+        let total_size = file_metas.iter().map(|m| m.size).sum();
+        let tracker = Mutex::new(ProgressTracker::new(&session_id, total_size, files.len()));
+
+        let cancel = self.transfers.register(&session_id);
+        let result = {
+            let sending = self.send_file_streams(
+                &connection,
+                &files,
+                &file_metas,
+                (&tokens, &offsets),
+                &tracker,
+            );
+            tokio::pin!(sending);
+            watch_cancel(
+                sending,
+                &connection,
+                &mut send,
+                &mut recv,
+                &session_id,
+                &cancel,
+                false,
+            )
+            .await
+        };
+        self.transfers.remove(&session_id);
 
-            info!("Sending file: {} ({} bytes)", meta.name, meta.size);
+        if let Err(e) = result {
+            let progress = match e {
+                unidrop_core::Error::Cancelled => tracker.lock().cancel(),
+                _ => tracker.lock().fail(e.to_string()),
+            };
+            emit(&self.event_tx, Event::transfer_progress(progress)).await;
+            emit(
+                &self.event_tx,
+                Event::transfer_failed(&session_id, e.to_string()),
+            )
+            .await;
+
+            // 中断的传输登记后可以续传
+            if !e.is_cancelled() {
+                let target = DeviceId::new(ProtocolId::new(crate::PROTOCOL_ID), fingerprint);
+                let mut intent = TransferIntent::from_sources(target, files);
+                intent.pin = pin.map(str::to_string);
+                let send = ResumableSend {
+                    session_id: session_id.clone(),
+                    intent,
+                };
+                self.resumable.insert(&session_id, send);
+            }
+            return Err(e);
+        }
 
-            // 为每个文件打开一个新的流
-            let (mut file_send, _) = connection
-                .open_bi()
-                .await
-                .map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
+        // 发送完成消息，并等待对端确认已全部写入
+        let complete = Message::TransferComplete {
+            session_id: session_id.clone(),
+        };
+        send_message(&mut send, &complete).await?;
 
-            // 发送文件头
-            let header = Message::FileHeader {
-                file_id: meta.id.clone(),
-                token: token.clone(),
-                file_name: meta.name.clone(),
-                size: meta.size,
-            };
-            send_message(&mut file_send, &header).await?;
+        match recv_message::<Message>(&mut recv).await? {
+            Message::TransferComplete { .. } => {}
+            Message::Error { message } => {
+                emit(
+                    &self.event_tx,
+                    Event::transfer_failed(&session_id, &message),
+                )
+                .await;
+                return Err(unidrop_core::Error::TransferFailed(message));
+            }
+            other => warn!("Unexpected message at end of transfer: {:?}", other),
+        }
+        connection.close(0u32.into(), b"done");
 
-            // 发送文件内容
-            let mut file = File::open(path).await?;
-            let mut buffer = vec![0u8; 64 * 1024]; // 64KB buffer
-            let mut sent = 0u64;
+        let progress = tracker.lock().complete();
+        emit(&self.event_tx, Event::transfer_progress(progress)).await;
+        emit(&self.event_tx, Event::transfer_completed(&session_id)).await;
 
-            loop {
-                let n = file.read(&mut buffer).await?;
-                if n == 0 {
-                    break;
-                }
-                file_send
-                    .write_all(&buffer[..n])
-                    .await
-                    .map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
-                sent += n as u64;
+        info!("Transfer complete: {}", session_id);
+        Ok(session_id)
+    }
 
-                // 进度
-                let progress = (sent as f64 / meta.size as f64 * 100.0) as u32;
-                debug!("Progress: {}% ({}/{})", progress, sent, meta.size);
+    /// 为每个文件打开独立的流并发送内容，最多同时发送 `concurrency` 个文件
+    ///
+    /// `grants` 为接收方返回的每个文件的 token 和续传偏移。
+    async fn send_file_streams(
+        &self,
+        connection: &quinn::Connection,
+        files: &[TransferSource],
+        file_metas: &[FileMetadata],
+        grants: (&[String], &[u64]),
+        tracker: &Mutex<ProgressTracker>,
+    ) -> unidrop_core::Result<()> {
+        let (tokens, offsets) = grants;
+        let mut streams = Vec::with_capacity(files.len());
+        for (i, source) in files.iter().enumerate() {
+            let meta = &file_metas[i];
+            let token = tokens
+                .get(i)
+                .ok_or_else(|| unidrop_core::Error::Protocol("Missing token".into()))?;
+
+            if token.is_empty() {
+                debug!("Receiver skipped {}", meta.name);
+                tracker.lock().skip_file(meta.size);
+                continue;
             }
 
+            let offset = offsets.get(i).copied().unwrap_or(0).min(meta.size);
+            streams.push(self.send_file_stream(connection, source, meta, token, offset, tracker));
+        }
+
+        futures::stream::iter(streams)
+            .buffer_unordered(self.concurrency)
+            .try_collect()
+            .await
+    }
+
+    /// 在独立的流上发送单个文件，从 `offset` 处开始
+    ///
+    /// 接收方的流数上限和接收窗口限制发送速度，写入在窗口用尽时等待。
+    async fn send_file_stream(
+        &self,
+        connection: &quinn::Connection,
+        source: &TransferSource,
+        meta: &FileMetadata,
+        token: &str,
+        offset: u64,
+        tracker: &Mutex<ProgressTracker>,
+    ) -> unidrop_core::Result<()> {
+        let (mut file_send, _) = connection
+            .open_bi()
+            .await
+            .map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
+
+        if offset > 0 {
+            info!(
+                "Resuming file: {} at {} of {} bytes",
+                meta.name, offset, meta.size
+            );
+        } else {
+            info!("Sending file: {} ({} bytes)", meta.name, meta.size);
+        }
+        tracker.lock().begin_file(&meta.id, meta.size, offset);
+
+        // 发送文件头
+        let header = Message::FileHeader {
+            file_id: meta.id.clone(),
+            token: token.to_string(),
+            file_name: meta.name.clone(),
+            size: meta.size,
+            offset,
+        };
+        send_message(&mut file_send, &header).await?;
+
+        // 发送文件内容
+        let mut file = source.open_at(offset).await?;
+        let mut buffer = vec![0u8; 64 * 1024]; // 64KB buffer
+
+        loop {
+            let n = file.read(&mut buffer).await?;
+            if n == 0 {
+                break;
+            }
             file_send
-                .finish()
+                .write_all(&buffer[..n])
+                .await
                 .map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
 
-            info!("File sent: {}", meta.name);
+            let progress = tracker.lock().advance_file(&meta.id, n as u64);
+            if let Some(progress) = progress {
+                debug!(
+                    "Progress: {:.0}% ({}/{})",
+                    progress.progress_percent(),
+                    progress.bytes_transferred,
+                    progress.bytes_total
+                );
+                emit_progress(&self.event_tx, progress);
+            }
         }
 
-        // 发送完成消息
-        let complete = Message::TransferComplete {
-            session_id: session_id.clone(),
-        };
-        send_message(&mut send, &complete).await?;
+        file_send
+            .finish()
+            .map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
 
-        info!("Transfer complete: {}", session_id);
-        Ok(session_id)
+        let progress = tracker.lock().end_file(&meta.id);
+        emit(&self.event_tx, Event::transfer_progress(progress)).await;
+        info!("File sent: {}", meta.name);
+        Ok(())
     }
 }
 
@@ -275,7 +745,13 @@ impl Default for QuicClient {
 async fn handle_connection(
     connection: quinn::Connection,
     save_dir: PathBuf,
+    naming: (CollisionPolicy, bool),
+    transfers: CancelRegistry,
+    pending: Option<Arc<PendingUploads>>,
+    pin: Arc<PinGuard>,
+    event_tx: Option<mpsc::Sender<Event>>,
 ) -> unidrop_core::Result<()> {
+    let (collision_policy, keep_subdirs) = naming;
     let remote = connection.remote_address();
 
     // 接受控制流
@@ -287,8 +763,83 @@ async fn handle_connection(
     // 读取传输请求
     let request: Message = recv_message(&mut recv).await?;
 
-    let (session_id, files) = match request {
-        Message::TransferRequest { session_id, files } => (session_id, files),
+    // 出示了证书的发送方，其声明的设备 ID 必须与证书一致
+    let claimed = match &request {
+        Message::TransferRequest { sender, .. }
+        | Message::ResumeRequest { sender, .. }
+        | Message::Text { sender, .. } => sender.as_ref(),
+        _ => None,
+    };
+    let verified = match (peer_cert_hash(&connection), claimed) {
+        (Some(hash), Some(info)) if is_identity(&info.fingerprint, &hash) => true,
+        (Some(hash), Some(info)) => {
+            warn!(
+                "{} claimed {} but presented {}",
+                remote, info.fingerprint, hash
+            );
+            let error = Message::Error {
+                message: "Certificate does not match device fingerprint".to_string(),
+            };
+            send_message(&mut send, &error).await?;
+            return Err(unidrop_core::Error::CertificateMismatch(format!(
+                "{} presented {}",
+                info.fingerprint, hash
+            )));
+        }
+        _ => false,
+    };
+
+    // 设置了 PIN 时，请求必须携带正确的 PIN
+    let given = match &request {
+        Message::TransferRequest { pin, .. }
+        | Message::ResumeRequest { pin, .. }
+        | Message::Text { pin, .. } => pin.as_deref(),
+        _ => None,
+    };
+    let status = pin.check(&remote.ip().to_string(), given);
+    if status != PinStatus::Accepted {
+        warn!("PIN check failed for {}: {:?}", remote, status);
+        if send_message(&mut send, &Message::PinRejected { status })
+            .await
+            .is_ok()
+        {
+            let _ = send.finish();
+            let _ = tokio::time::timeout(CANCEL_GRACE, connection.closed()).await;
+        }
+        return Ok(());
+    }
+
+    let (session_id, files, sender, resume) = match request {
+        Message::TransferRequest {
+            session_id,
+            files,
+            sender,
+            ..
+        } => (session_id, files, sender, false),
+        Message::ResumeRequest {
+            session_id,
+            files,
+            sender,
+            ..
+        } => (session_id, files, sender, true),
+        Message::Text {
+            session_id,
+            sender,
+            text,
+            ..
+        } => {
+            let from = match sender {
+                Some(info) => crate::server::device_from_info(&info, remote.ip()),
+                None => unknown_sender(remote),
+            };
+            info!("Text message from {}", remote);
+            emit(&event_tx, Event::message_received(from, text)).await;
+
+            send_message(&mut send, &Message::TransferComplete { session_id }).await?;
+            let _ = send.finish();
+            connection.closed().await;
+            return Ok(());
+        }
         _ => {
             let error = Message::Error {
                 message: "Expected TransferRequest".to_string(),
@@ -306,177 +857,530 @@ async fn handle_connection(
         files.len()
     );
 
-    // 自动接受，生成 tokens
+    // 等待上层确认，决定保存目录和接收哪些文件
+    let (save_dir, selected) = match &pending {
+        Some(pending) => {
+            let from = match sender {
+                Some(info) => crate::server::device_from_info(&info, remote.ip()),
+                None => unknown_sender(remote),
+            };
+            let request = transfer_request(&session_id, from, &files).with_verified(verified);
+            match wait_decision(&connection, pending, request, &event_tx).await {
+                Some(decision) => decision,
+                None => {
+                    let response = Message::TransferResponse {
+                        session_id,
+                        accepted: false,
+                        tokens: Vec::new(),
+                        offsets: Vec::new(),
+                    };
+                    if send_message(&mut send, &response).await.is_ok() {
+                        let _ = send.finish();
+                        let _ = tokio::time::timeout(CANCEL_GRACE, connection.closed()).await;
+                    }
+                    return Ok(());
+                }
+            }
+        }
+        None => (save_dir, None),
+    };
+
+    // 生成 tokens，未选中或按冲突策略跳过的文件不分配 token
     let tokens: Vec<String> = files
         .iter()
-        .map(|_| uuid::Uuid::new_v4().to_string())
+        .map(|f| {
+            let wanted = selected.as_ref().is_none_or(|s| s.contains(&f.id));
+            match resolve_save_path(&save_dir, &f.name, keep_subdirs, collision_policy) {
+                Some(_) if wanted => uuid::Uuid::new_v4().to_string(),
+                _ => String::new(),
+            }
+        })
         .collect();
 
+    // 续传时返回保留的未完成文件已接收的字节数
+    let mut offsets = vec![0; files.len()];
+    if resume {
+        for (i, (file, token)) in files.iter().zip(&tokens).enumerate() {
+            if !token.is_empty() {
+                offsets[i] = part_file(&save_dir, keep_subdirs, &session_id, file)
+                    .resume_offset()
+                    .await;
+            }
+        }
+    }
+
     let response = Message::TransferResponse {
         session_id: session_id.clone(),
         accepted: true,
         tokens: tokens.clone(),
+        offsets: offsets.clone(),
     };
     send_message(&mut send, &response).await?;
 
-    // 创建保存目录
-    tokio::fs::create_dir_all(&save_dir).await?;
+    let total_size = files.iter().map(|f| f.size).sum();
+    let mut tracker = ProgressTracker::new(&session_id, total_size, files.len());
+    for (file, token) in files.iter().zip(&tokens) {
+        if token.is_empty() {
+            tracker.skip_file(file.size);
+        }
+    }
+    let tracker = Mutex::new(tracker);
+
+    let cancel = transfers.register(&session_id);
+    let result = {
+        let context = ReceiveContext {
+            session_id: &session_id,
+            save_dir: &save_dir,
+            naming,
+            files: &files,
+            tokens: &tokens,
+            offsets: &offsets,
+            tracker: &tracker,
+            event_tx: &event_tx,
+            received: Mutex::new(HashSet::new()),
+        };
+        let receiving = receive_files(&connection, &context);
+        tokio::pin!(receiving);
+        watch_cancel(
+            receiving,
+            &connection,
+            &mut send,
+            &mut recv,
+            &session_id,
+            &cancel,
+            true,
+        )
+        .await
+    };
+    transfers.remove(&session_id);
+
+    if let Err(e) = result {
+        // 取消时删除未写完的临时文件，中断时保留供续传
+        for (file, token) in files.iter().zip(&tokens) {
+            if !token.is_empty() {
+                let mut part = part_file(&save_dir, keep_subdirs, &session_id, file);
+                match e {
+                    unidrop_core::Error::Cancelled => part.remove().await,
+                    _ => part.keep().await,
+                }
+            }
+        }
 
-    // 接收文件
-    let mut received = 0;
-    while received < files.len() {
-        // 接受文件流
-        let (_, mut file_recv) = connection
-            .accept_bi()
-            .await
-            .map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
+        let progress = match e {
+            unidrop_core::Error::Cancelled => tracker.lock().cancel(),
+            _ => tracker.lock().fail(e.to_string()),
+        };
+        emit(&event_tx, Event::transfer_progress(progress)).await;
+        emit(
+            &event_tx,
+            Event::transfer_failed(&session_id, e.to_string()),
+        )
+        .await;
+
+        // 告知发送方失败原因（如哈希不符），等待其关闭连接
+        if !matches!(e, unidrop_core::Error::Cancelled) {
+            let error = Message::Error {
+                message: e.to_string(),
+            };
+            if send_message(&mut send, &error).await.is_ok() {
+                let _ = send.finish();
+                let _ = tokio::time::timeout(CANCEL_GRACE, connection.closed()).await;
+            }
+        }
+        return Err(e);
+    }
 
-        // 读取文件头
-        let header: Message = recv_message(&mut file_recv).await?;
+    let progress = tracker.lock().complete();
+    emit(&event_tx, Event::transfer_progress(progress)).await;
+    emit(&event_tx, Event::transfer_completed(&session_id)).await;
+    info!("Transfer session {} completed", session_id);
 
-        let (file_id, token, file_name, size) = match header {
-            Message::FileHeader {
-                file_id,
-                token,
-                file_name,
-                size,
-            } => (file_id, token, file_name, size),
-            _ => {
-                warn!("Expected FileHeader, got {:?}", header);
-                continue;
-            }
-        };
+    // 回复确认后等待发送方关闭连接
+    send_message(&mut send, &Message::TransferComplete { session_id }).await?;
+    let _ = send.finish();
+    connection.closed().await;
 
-        // 验证 token
-        let expected_idx = files.iter().position(|f| f.id == file_id);
-        if let Some(idx) = expected_idx {
-            if tokens[idx] != token {
-                warn!("Invalid token for file {}", file_id);
-                continue;
+    Ok(())
+}
+
+/// 通知上层收到传输请求并等待确认
+///
+/// 返回接受时指定的保存目录和选中的文件；拒绝、超时或发送方断开时返回 None。
+async fn wait_decision(
+    connection: &quinn::Connection,
+    pending: &PendingUploads,
+    request: TransferRequest,
+    event_tx: &Option<mpsc::Sender<Event>>,
+) -> Option<(PathBuf, Option<HashSet<String>>)> {
+    let session_id = request.id.clone();
+
+    // 登记等待确认，再通知上层
+    let reply_rx = pending.register(&session_id);
+    if let Some(tx) = event_tx {
+        let event = Event::transfer_requested(request).with_protocol(crate::PROTOCOL_ID);
+        if tx.send(event).await.is_err() {
+            pending.remove(&session_id);
+            return None;
+        }
+    }
+
+    let decision = tokio::select! {
+        decision = tokio::time::timeout(ACCEPT_TIMEOUT, reply_rx) => decision,
+        _ = connection.closed() => {
+            info!("Sender of {} disconnected before confirmation", session_id);
+            pending.remove(&session_id);
+            return None;
+        }
+    };
+    pending.remove(&session_id);
+
+    match decision {
+        Ok(Ok(UploadDecision::Accept { save_dir, files })) => Some((save_dir, files)),
+        Ok(Ok(UploadDecision::Reject)) | Ok(Err(_)) => {
+            info!("Transfer request {} rejected", session_id);
+            None
+        }
+        Err(_) => {
+            warn!("Transfer request {} timed out", session_id);
+            None
+        }
+    }
+}
+
+/// 接收会话中各文件流共用的状态
+struct ReceiveContext<'a> {
+    session_id: &'a str,
+    save_dir: &'a Path,
+    naming: (CollisionPolicy, bool),
+    files: &'a [FileMetadata],
+    /// 每个文件的 token，空字符串表示跳过
+    tokens: &'a [String],
+    /// 每个文件的续传偏移
+    offsets: &'a [u64],
+    tracker: &'a Mutex<ProgressTracker>,
+    event_tx: &'a Option<mpsc::Sender<Event>>,
+    /// 已开始接收的文件 ID，每个文件只接收一个流
+    received: Mutex<HashSet<String>>,
+}
+
+/// 接收会话中的所有文件流，各文件流并行写入
+///
+/// 同时打开的流数由连接的 [`MAX_CONCURRENT_STREAMS`] 限制。
+async fn receive_files(
+    connection: &quinn::Connection,
+    context: &ReceiveContext<'_>,
+) -> unidrop_core::Result<()> {
+    let expected = context.tokens.iter().filter(|t| !t.is_empty()).count();
+    let mut receiving = FuturesUnordered::new();
+
+    let mut received = 0;
+    while received < expected {
+        tokio::select! {
+            stream = connection.accept_bi() => {
+                let (_, file_recv) =
+                    stream.map_err(|e| unidrop_core::Error::Network(e.to_string()))?;
+                receiving.push(receive_file(file_recv, context));
+            }
+            Some(result) = receiving.next() => {
+                if result? {
+                    received += 1;
+                }
             }
-        } else {
-            warn!("Unknown file_id: {}", file_id);
-            continue;
         }
+    }
 
-        // 接收文件内容
-        let save_path = save_dir.join(&file_name);
-        let mut file = File::create(&save_path).await?;
-        let mut total = 0u64;
+    Ok(())
+}
 
-        while let Some(chunk) = file_recv
-            .read_chunk(64 * 1024, true)
-            .await
-            .map_err(|e| unidrop_core::Error::Network(e.to_string()))?
-        {
-            file.write_all(&chunk.bytes).await?;
-            total += chunk.bytes.len() as u64;
+/// 接收单个文件流，返回是否为本会话中尚未接收的有效文件
+async fn receive_file(
+    mut file_recv: RecvStream,
+    context: &ReceiveContext<'_>,
+) -> unidrop_core::Result<bool> {
+    let (collision_policy, keep_subdirs) = context.naming;
+    let save_dir = context.save_dir;
+    let files = context.files;
+
+    // 读取文件头
+    let header: Message = recv_message(&mut file_recv).await?;
+
+    let (file_id, token, offset) = match header {
+        Message::FileHeader {
+            file_id,
+            token,
+            offset,
+            ..
+        } => (file_id, token, offset),
+        _ => {
+            warn!("Expected FileHeader, got {:?}", header);
+            return Ok(false);
         }
+    };
 
-        info!("Received file: {} ({} bytes) -> {:?}", file_name, total, save_path);
-        received += 1;
+    // 验证 token，文件名以请求中的为准
+    let Some(idx) = files.iter().position(|f| f.id == file_id) else {
+        warn!("Unknown file_id: {}", file_id);
+        return Ok(false);
+    };
+    if context.tokens[idx].is_empty() || context.tokens[idx] != token {
+        warn!("Invalid token for file {}", file_id);
+        return Ok(false);
+    }
+    if offset != context.offsets[idx] {
+        return Err(unidrop_core::Error::Protocol(format!(
+            "File {} resumed at {}, expected {}",
+            file_id, offset, context.offsets[idx]
+        )));
+    }
+    if !context.received.lock().insert(file_id.clone()) {
+        warn!("Duplicate stream for file {}", file_id);
+        return Ok(false);
+    }
+    // 大小以接受的请求为准
+    let file_name = &files[idx].name;
+    let size = files[idx].size;
+
+    context
+        .tracker
+        .lock()
+        .begin_file(&file_id, files[idx].size, offset);
+
+    // 先写入临时文件，完成后再按冲突策略确定文件名
+    let mut part = part_file(save_dir, keep_subdirs, context.session_id, &files[idx]);
+    create_parent_dirs(save_dir, part.path()).await?;
+    let (mut file, mut hasher) = part.open(offset).await?;
+    let mut total = offset;
+
+    // 写盘完成后才读取下一块，接收窗口据此限制发送方
+    while let Some(chunk) = file_recv
+        .read_chunk(64 * 1024, true)
+        .await
+        .map_err(|e| unidrop_core::Error::Network(e.to_string()))?
+    {
+        if total + chunk.bytes.len() as u64 > size {
+            drop(file);
+            part.remove().await;
+            return Err(unidrop_core::Error::TransferFailed(format!(
+                "{} exceeds the accepted size of {} bytes",
+                file_name, size
+            )));
+        }
+        file.write_all(&chunk.bytes).await?;
+        hasher.update(&chunk.bytes);
+        total += chunk.bytes.len() as u64;
+
+        let progress = context
+            .tracker
+            .lock()
+            .advance_file(&file_id, chunk.bytes.len() as u64);
+        if let Some(progress) = progress {
+            emit_progress(context.event_tx, progress);
+        }
+    }
+    file.flush().await?;
+    drop(file);
+
+    if total != size {
+        part.remove().await;
+        return Err(unidrop_core::Error::TransferFailed(format!(
+            "Incomplete file {}: {} of {} bytes",
+            file_name, total, size
+        )));
+    }
+
+    if let Err(e) = hasher.verify(file_name, files[idx].sha256.as_deref()) {
+        part.remove().await;
+        return Err(e);
     }
 
-    // 等待完成消息
-    let complete: Message = recv_message(&mut recv).await?;
-    match complete {
-        Message::TransferComplete { session_id: sid } if sid == session_id => {
-            info!("Transfer session {} completed", session_id);
+    match resolve_save_path(save_dir, file_name, keep_subdirs, collision_policy) {
+        Some(save_path) => {
+            part.finish(&save_path).await?;
+            info!(
+                "Received file: {} ({} bytes) -> {:?}",
+                file_name, total, save_path
+            );
         }
-        _ => {
-            warn!("Unexpected message at end of transfer");
+        None => {
+            info!("Skipped existing file: {}", file_name);
+            part.remove().await;
+        }
+    }
+
+    let progress = context.tracker.lock().end_file(&file_id);
+    emit(context.event_tx, Event::transfer_progress(progress)).await;
+    Ok(true)
+}
+
+/// 文件接收期间的临时文件（位于目标子目录中）
+fn part_file(
+    save_dir: &Path,
+    keep_subdirs: bool,
+    session_id: &str,
+    file: &FileMetadata,
+) -> PartFile {
+    PartFile::new(
+        save_dir,
+        &file.name,
+        keep_subdirs,
+        session_id,
+        &file.id,
+        file.size,
+        file.sha256.as_deref(),
+    )
+}
+
+/// 在传输文件的同时监听取消
+///
+/// 本端取消时通过控制流通知对端，并等待对端关闭连接；
+/// 收到对端的取消消息时关闭连接。两种情况都返回 `Error::Cancelled`。
+/// `expect_complete` 为 true 时（接收方）还需等到发送方的完成消息才返回。
+async fn watch_cancel<F>(
+    mut transfer: std::pin::Pin<&mut F>,
+    connection: &quinn::Connection,
+    send: &mut SendStream,
+    recv: &mut RecvStream,
+    session_id: &str,
+    cancel: &CancellationToken,
+    expect_complete: bool,
+) -> unidrop_core::Result<()>
+where
+    F: std::future::Future<Output = unidrop_core::Result<()>>,
+{
+    let mut transferred = false;
+    let mut completed = !expect_complete;
+    // 控制流在传输期间最多收到一条消息：取消或完成
+    let message = recv_message::<Message>(recv);
+    tokio::pin!(message);
+    let mut received = false;
+
+    while !(transferred && completed) {
+        tokio::select! {
+            result = transfer.as_mut(), if !transferred => {
+                result?;
+                transferred = true;
+            }
+            _ = cancel.cancelled() => {
+                let message = Message::Cancel { session_id: session_id.to_string() };
+                if send_message(send, &message).await.is_ok() {
+                    let _ = tokio::time::timeout(CANCEL_GRACE, connection.closed()).await;
+                }
+                connection.close(CANCELLED_CODE.into(), b"cancelled");
+                return Err(unidrop_core::Error::Cancelled);
+            }
+            message = message.as_mut(), if !received => {
+                received = true;
+                match message? {
+                    Message::Cancel { .. } => {
+                        info!("Peer cancelled transfer {}", session_id);
+                        connection.close(CANCELLED_CODE.into(), b"cancelled");
+                        return Err(unidrop_core::Error::Cancelled);
+                    }
+                    Message::TransferComplete { .. } if expect_complete => completed = true,
+                    Message::Error { message } => {
+                        return Err(unidrop_core::Error::TransferFailed(message));
+                    }
+                    other => {
+                        return Err(unidrop_core::Error::Protocol(format!(
+                            "Unexpected message during transfer: {:?}",
+                            other
+                        )));
+                    }
+                }
+            }
         }
     }
 
     Ok(())
 }
 
-/// 创建服务器 TLS 配置
-fn create_server_config(cert_info: &CertInfo) -> unidrop_core::Result<ServerConfig> {
-    let cert = CertificateDer::from(cert_info.cert_der.clone());
-    let key = PrivateKeyDer::try_from(cert_info.key_der.clone())
-        .map_err(|e| unidrop_core::Error::Protocol(format!("Invalid key: {}", e)))?;
+/// 由 QUIC 文件元数据构造传输请求
+fn transfer_request(
+    session_id: &str,
+    from: unidrop_core::Device,
+    files: &[FileMetadata],
+) -> TransferRequest {
+    let infos = files
+        .iter()
+        .map(|f| {
+            let mime = f.mime_type.as_deref().unwrap_or("application/octet-stream");
+            let mut info = FileInfo::new(&f.id, &f.name, f.size).with_mime(mime);
+            info.hash = f.sha256.clone();
+            info
+        })
+        .collect();
+    TransferRequest::new(session_id, from, infos)
+}
 
-    let _ = rustls::crypto::ring::default_provider().install_default();
+/// 对端出示的客户端证书的 SHA-256
+fn peer_cert_hash(connection: &quinn::Connection) -> Option<String> {
+    let certs = connection
+        .peer_identity()?
+        .downcast::<Vec<CertificateDer<'static>>>()
+        .ok()?;
+    certs.first().map(|cert| cert_sha256(cert))
+}
 
-    let server_crypto = rustls::ServerConfig::builder()
-        .with_no_client_auth()
-        .with_single_cert(vec![cert], key)
-        .map_err(|e| unidrop_core::Error::Protocol(e.to_string()))?;
+/// 未携带设备信息的发送方，以地址标识
+fn unknown_sender(remote: SocketAddr) -> unidrop_core::Device {
+    let peer = unidrop_core::Peer::new(
+        unidrop_core::ProtocolId::new(crate::PROTOCOL_ID),
+        remote.ip().to_string(),
+        remote.ip().to_string(),
+    );
+    unidrop_core::Device::new(peer, remote.ip(), remote.port())
+}
 
-    let server_config = ServerConfig::with_crypto(Arc::new(
+/// 发送事件，通道已满时等待，保证完成、失败等事件送达
+async fn emit(event_tx: &Option<mpsc::Sender<Event>>, event: Event) {
+    if let Some(tx) = event_tx {
+        let _ = tx.send(event.with_protocol(crate::PROTOCOL_ID)).await;
+    }
+}
+
+/// 传输中的进度事件，通道已满时丢弃
+fn emit_progress(event_tx: &Option<mpsc::Sender<Event>>, progress: TransferProgress) {
+    if let Some(tx) = event_tx {
+        let _ = tx.try_send(Event::transfer_progress(progress).with_protocol(crate::PROTOCOL_ID));
+    }
+}
+
+/// 创建服务器 TLS 配置（接受任意自签名客户端证书）
+fn create_server_config(cert_info: &CertInfo) -> unidrop_core::Result<ServerConfig> {
+    let server_crypto = ClientIdentityVerifier::new().server_config(cert_info)?;
+
+    let mut server_config = ServerConfig::with_crypto(Arc::new(
         quinn::crypto::rustls::QuicServerConfig::try_from(server_crypto)
             .map_err(|e| unidrop_core::Error::Protocol(e.to_string()))?,
     ));
+    server_config.transport_config(transport_config());
 
     Ok(server_config)
 }
 
-/// 创建客户端 TLS 配置（跳过证书验证，用于自签名证书）
-fn create_client_config() -> unidrop_core::Result<ClientConfig> {
-    let _ = rustls::crypto::ring::default_provider().install_default();
-
-    let client_crypto = rustls::ClientConfig::builder()
-        .dangerous()
-        .with_custom_certificate_verifier(Arc::new(SkipServerVerification))
-        .with_no_client_auth();
-
-    let client_config = ClientConfig::new(Arc::new(
-        quinn::crypto::rustls::QuicClientConfig::try_from(client_crypto)
+/// 创建客户端 TLS 配置（按设备指纹校验自签名证书，并出示本机证书）
+fn create_client_config(
+    verifier: &Arc<PinnedVerifier>,
+    identity: Option<&CertInfo>,
+) -> unidrop_core::Result<ClientConfig> {
+    let mut client_config = ClientConfig::new(Arc::new(
+        quinn::crypto::rustls::QuicClientConfig::try_from(verifier.client_config(identity)?)
             .map_err(|e| unidrop_core::Error::Protocol(e.to_string()))?,
     ));
+    client_config.transport_config(transport_config());
 
     Ok(client_config)
 }
 
-/// 跳过服务器证书验证（用于自签名证书）
-#[derive(Debug)]
-struct SkipServerVerification;
-
-impl rustls::client::danger::ServerCertVerifier for SkipServerVerification {
-    fn verify_server_cert(
-        &self,
-        _end_entity: &CertificateDer<'_>,
-        _intermediates: &[CertificateDer<'_>],
-        _server_name: &rustls::pki_types::ServerName<'_>,
-        _ocsp_response: &[u8],
-        _now: rustls::pki_types::UnixTime,
-    ) -> Result<rustls::client::danger::ServerCertVerified, rustls::Error> {
-        Ok(rustls::client::danger::ServerCertVerified::assertion())
-    }
-
-    fn verify_tls12_signature(
-        &self,
-        _message: &[u8],
-        _cert: &CertificateDer<'_>,
-        _dss: &rustls::DigitallySignedStruct,
-    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
-        Ok(rustls::client::danger::HandshakeSignatureValid::assertion())
-    }
-
-    fn verify_tls13_signature(
-        &self,
-        _message: &[u8],
-        _cert: &CertificateDer<'_>,
-        _dss: &rustls::DigitallySignedStruct,
-    ) -> Result<rustls::client::danger::HandshakeSignatureValid, rustls::Error> {
-        Ok(rustls::client::danger::HandshakeSignatureValid::assertion())
-    }
-
-    fn supported_verify_schemes(&self) -> Vec<rustls::SignatureScheme> {
-        vec![
-            rustls::SignatureScheme::RSA_PKCS1_SHA256,
-            rustls::SignatureScheme::RSA_PKCS1_SHA384,
-            rustls::SignatureScheme::RSA_PKCS1_SHA512,
-            rustls::SignatureScheme::ECDSA_NISTP256_SHA256,
-            rustls::SignatureScheme::ECDSA_NISTP384_SHA384,
-            rustls::SignatureScheme::ECDSA_NISTP521_SHA512,
-            rustls::SignatureScheme::RSA_PSS_SHA256,
-            rustls::SignatureScheme::RSA_PSS_SHA384,
-            rustls::SignatureScheme::RSA_PSS_SHA512,
-            rustls::SignatureScheme::ED25519,
-        ]
-    }
+/// 并行文件流的流控参数（双方相同）
+fn transport_config() -> Arc<quinn::TransportConfig> {
+    let mut transport = quinn::TransportConfig::default();
+    transport
+        .max_concurrent_bidi_streams(MAX_CONCURRENT_STREAMS.into())
+        .stream_receive_window(STREAM_RECEIVE_WINDOW.into())
+        .receive_window(CONNECTION_WINDOW.into())
+        .send_window(CONNECTION_WINDOW.into());
+    Arc::new(transport)
 }
 
 /// 发送消息
@@ -525,4 +1429,408 @@ mod tests {
         let client = QuicClient::new();
         assert!(client.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_quic_transfer_reports_progress() {
+        let cert = crate::cert::generate_self_signed("UniDrop").unwrap();
+        let dir = std::env::temp_dir().join(format!("unidrop-quic-{}", uuid::Uuid::new_v4()));
+        let src = dir.join("source.bin");
+        let save_dir = dir.join("inbox");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&src, vec![7u8; 1_000_000]).unwrap();
+
+        let (server_tx, mut server_rx) = mpsc::channel(256);
+        let server = QuicServer::new(0, &cert, save_dir.clone())
+            .unwrap()
+            .with_event_sender(server_tx);
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move { server.run().await });
+
+        let (client_tx, mut client_rx) = mpsc::channel(256);
+        let client = QuicClient::new().unwrap().with_event_sender(client_tx);
+        let target: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        let session_id = client
+            .send_files(
+                target,
+                &cert.device_id,
+                vec![TransferSource::file(src)],
+                None,
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(
+            std::fs::metadata(save_dir.join("source.bin"))
+                .unwrap()
+                .len(),
+            1_000_000
+        );
+
+        for rx in [&mut client_rx, &mut server_rx] {
+            let mut completed = false;
+            while let Ok(event) = rx.try_recv() {
+                if let unidrop_core::EventKind::TransferCompleted { transfer_id } = event.kind {
+                    assert_eq!(transfer_id, session_id);
+                    completed = true;
+                }
+            }
+            assert!(completed);
+        }
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_quic_rejects_duplicate_and_oversized_streams() {
+        let cert = crate::cert::generate_self_signed("UniDrop").unwrap();
+        let save_dir = std::env::temp_dir().join(format!("unidrop-quic-{}", uuid::Uuid::new_v4()));
+
+        let (server_tx, mut server_rx) = mpsc::channel(256);
+        let server = QuicServer::new(0, &cert, save_dir.clone())
+            .unwrap()
+            .with_event_sender(server_tx);
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move { server.run().await });
+
+        // 手工构造的发送方：按协议请求，但文件流不守规矩
+        let client = QuicClient::new().unwrap();
+        let target: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        let connection = client.connect(target, &cert.device_id).await.unwrap();
+        let (mut send, mut recv) = connection.open_bi().await.unwrap();
+        let files = ["a", "b"]
+            .map(|id| FileMetadata {
+                id: id.into(),
+                name: format!("{}.txt", id),
+                size: 4,
+                mime_type: None,
+                sha256: None,
+            })
+            .to_vec();
+        let request = Message::TransferRequest {
+            session_id: "s1".into(),
+            files,
+            sender: None,
+            pin: None,
+        };
+        send_message(&mut send, &request).await.unwrap();
+        let Message::TransferResponse { tokens, .. } = recv_message(&mut recv).await.unwrap()
+        else {
+            panic!("transfer not accepted");
+        };
+
+        let send_file = |file_id: &str, token: &str, data: &'static [u8]| {
+            let connection = connection.clone();
+            let header = Message::FileHeader {
+                file_id: file_id.into(),
+                token: token.into(),
+                file_name: format!("{}.txt", file_id),
+                size: 4,
+                offset: 0,
+            };
+            async move {
+                let (mut file_send, _) = connection.open_bi().await.unwrap();
+                send_message(&mut file_send, &header).await.unwrap();
+                let _ = file_send.write_all(data).await;
+                let _ = file_send.finish();
+            }
+        };
+        let finished = |rx: &mut mpsc::Receiver<Event>| {
+            std::iter::from_fn(|| rx.try_recv().ok()).find_map(|event| match event.kind {
+                unidrop_core::EventKind::TransferCompleted { .. } => Some(true),
+                unidrop_core::EventKind::TransferFailed { .. } => Some(false),
+                _ => None,
+            })
+        };
+
+        // 同一文件的第二个流不计入完成，另一个文件仍在等待
+        send_file("a", &tokens[0], b"abcd").await;
+        send_file("a", &tokens[0], b"abcd").await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(finished(&mut server_rx), None);
+        assert_eq!(std::fs::read(save_dir.join("a.txt")).unwrap(), b"abcd");
+
+        // 超出接受时的大小，传输失败且不保存
+        send_file("b", &tokens[1], b"abcdefgh").await;
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert_eq!(finished(&mut server_rx), Some(false));
+        assert!(!save_dir.join("b.txt").exists());
+
+        let _ = std::fs::remove_dir_all(&save_dir);
+    }
+
+    #[tokio::test]
+    async fn test_quic_parallel_files() {
+        let cert = crate::cert::generate_self_signed("UniDrop").unwrap();
+        let save_dir = std::env::temp_dir().join(format!("unidrop-quic-{}", uuid::Uuid::new_v4()));
+
+        let server = QuicServer::new(0, &cert, save_dir.clone()).unwrap();
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move { server.run().await });
+
+        let (client_tx, mut client_rx) = mpsc::channel(1024);
+        let client = QuicClient::new()
+            .unwrap()
+            .with_concurrency(4)
+            .with_event_sender(client_tx);
+        let target: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        let sources = (0..20)
+            .map(|i| TransferSource::bytes(format!("photo_{}.jpg", i), vec![i as u8; 100_000]))
+            .collect();
+        client
+            .send_files(target, &cert.device_id, sources, None)
+            .await
+            .unwrap();
+
+        for i in 0..20 {
+            let data = std::fs::read(save_dir.join(format!("photo_{}.jpg", i))).unwrap();
+            assert_eq!(data, vec![i as u8; 100_000]);
+        }
+
+        // 最终进度汇总所有文件，且没有遗留的活动文件
+        let mut last = None;
+        while let Ok(event) = client_rx.try_recv() {
+            if let unidrop_core::EventKind::TransferProgress(progress) = event.kind {
+                last = Some(progress);
+            }
+        }
+        let last = last.unwrap();
+        assert_eq!(last.state, unidrop_core::TransferState::Completed);
+        assert_eq!(last.files_completed, 20);
+        assert_eq!(last.bytes_transferred, 2_000_000);
+        assert!(last.active_files.is_empty());
+
+        let _ = std::fs::remove_dir_all(&save_dir);
+    }
+
+    #[tokio::test]
+    async fn test_quic_waits_for_decision() {
+        let cert = crate::cert::generate_self_signed("UniDrop").unwrap();
+        let dir = std::env::temp_dir().join(format!("unidrop-quic-{}", uuid::Uuid::new_v4()));
+
+        let pending = Arc::new(PendingUploads::new());
+        let (server_tx, mut server_rx) = mpsc::channel(256);
+        let server = QuicServer::new(0, &cert, dir.join("default"))
+            .unwrap()
+            .with_pending(pending.clone())
+            .with_event_sender(server_tx);
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move { server.run().await });
+
+        let client = QuicClient::new().unwrap();
+        let target: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        let send = |client: QuicClient, fingerprint: String| {
+            tokio::spawn(async move {
+                let sources = vec![
+                    TransferSource::bytes("a.txt", &b"a"[..]),
+                    TransferSource::bytes("b.txt", &b"b"[..]),
+                ];
+                client.send_files(target, &fingerprint, sources, None).await
+            })
+        };
+        let requested = |rx: &mut mpsc::Receiver<Event>| {
+            let event = rx.try_recv().ok()?;
+            match event.kind {
+                unidrop_core::EventKind::TransferRequested(request) => Some(request),
+                _ => None,
+            }
+        };
+
+        // 只接受其中一个文件，保存到接受时指定的目录
+        let sending = send(client.clone(), cert.device_id.clone());
+        let request = loop {
+            if let Some(request) = requested(&mut server_rx) {
+                break request;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        assert_eq!(request.files.len(), 2);
+        let decision = UploadDecision::Accept {
+            save_dir: dir.join("inbox"),
+            files: Some(HashSet::from(["file_1".to_string()])),
+        };
+        pending.resolve(&request.id, decision).unwrap();
+        sending.await.unwrap().unwrap();
+        assert!(!dir.join("inbox/a.txt").exists());
+        assert_eq!(std::fs::read(dir.join("inbox/b.txt")).unwrap(), b"b");
+
+        // 拒绝后发送方收到 Rejected
+        let sending = send(client, cert.device_id.clone());
+        let request = loop {
+            if let Some(request) = requested(&mut server_rx) {
+                break request;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+        pending
+            .resolve(&request.id, UploadDecision::Reject)
+            .unwrap();
+        assert!(matches!(
+            sending.await.unwrap(),
+            Err(unidrop_core::Error::Rejected)
+        ));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_quic_hash_mismatch() {
+        let cert = crate::cert::generate_self_signed("UniDrop").unwrap();
+        let save_dir = std::env::temp_dir().join(format!("unidrop-hash-{}", uuid::Uuid::new_v4()));
+
+        let (server_tx, mut server_rx) = mpsc::channel(256);
+        let server = QuicServer::new(0, &cert, save_dir.clone())
+            .unwrap()
+            .with_event_sender(server_tx);
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move { server.run().await });
+
+        let client = QuicClient::new().unwrap();
+        let target: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+
+        let good = TransferSource::bytes("good.txt", &b"payload"[..])
+            .hashed(None)
+            .await
+            .unwrap();
+        client
+            .send_files(target, &cert.device_id, vec![good], None)
+            .await
+            .unwrap();
+        assert!(save_dir.join("good.txt").exists());
+
+        let bad = TransferSource::bytes("bad.txt", &b"payload"[..]).with_hash("00");
+        let result = client
+            .send_files(target, &cert.device_id, vec![bad], None)
+            .await;
+        assert!(matches!(
+            result,
+            Err(unidrop_core::Error::TransferFailed(_))
+        ));
+        assert!(!save_dir.join("bad.txt").exists());
+
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let mut failed = false;
+        while let Ok(event) = server_rx.try_recv() {
+            if let unidrop_core::EventKind::TransferFailed { error, .. } = event.kind {
+                assert!(error.contains("Hash mismatch"));
+                failed = true;
+            }
+        }
+        assert!(failed);
+
+        let _ = std::fs::remove_dir_all(&save_dir);
+    }
+
+    #[tokio::test]
+    async fn test_quic_cancel_cleans_up_both_sides() {
+        let cert = crate::cert::generate_self_signed("UniDrop").unwrap();
+        let dir = std::env::temp_dir().join(format!("unidrop-quic-{}", uuid::Uuid::new_v4()));
+        let src = dir.join("large.bin");
+        let save_dir = dir.join("inbox");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&src, vec![7u8; 64 * 1024 * 1024]).unwrap();
+
+        let (server_tx, mut server_rx) = mpsc::channel(1024);
+        let server = QuicServer::new(0, &cert, save_dir.clone())
+            .unwrap()
+            .with_event_sender(server_tx);
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move { server.run().await });
+
+        let transfers = CancelRegistry::new();
+        let (client_tx, mut client_rx) = mpsc::channel(1024);
+        let client = QuicClient::new()
+            .unwrap()
+            .with_cancel_registry(transfers.clone())
+            .with_event_sender(client_tx);
+        let target: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        let fingerprint = cert.device_id.clone();
+        let send = tokio::spawn(async move {
+            client
+                .send_files(target, &fingerprint, vec![TransferSource::file(src)], None)
+                .await
+        });
+
+        // 收到第一条进度后取消
+        let session_id = loop {
+            let event = client_rx.recv().await.unwrap();
+            if let unidrop_core::EventKind::TransferProgress(progress) = event.kind {
+                break progress.transfer_id;
+            }
+        };
+        assert!(transfers.cancel(&session_id));
+        assert!(matches!(
+            send.await.unwrap(),
+            Err(unidrop_core::Error::Cancelled)
+        ));
+
+        let cancelled = loop {
+            let event = server_rx.recv().await.unwrap();
+            match event.kind {
+                unidrop_core::EventKind::TransferProgress(progress)
+                    if progress.state.is_terminal() =>
+                {
+                    break progress.state;
+                }
+                _ => {}
+            }
+        };
+        assert_eq!(cancelled, unidrop_core::TransferState::Cancelled);
+
+        // 临时文件已删除，也没有留下不完整的文件
+        let leftovers = std::fs::read_dir(&save_dir)
+            .map(|entries| entries.count())
+            .unwrap_or(0);
+        assert_eq!(leftovers, 0);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_quic_rejects_mismatched_certificate() {
+        let cert = crate::cert::generate_self_signed("UniDrop").unwrap();
+        let other = crate::cert::generate_self_signed("UniDrop").unwrap();
+        let dir = std::env::temp_dir().join(format!("unidrop-quic-{}", uuid::Uuid::new_v4()));
+        let src = dir.join("source.bin");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&src, b"hello").unwrap();
+
+        let server = QuicServer::new(0, &cert, dir.join("inbox")).unwrap();
+        let port = server.local_addr().unwrap().port();
+        tokio::spawn(async move { server.run().await });
+
+        let client = QuicClient::new().unwrap();
+        let target: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        let result = client
+            .send_files(
+                target,
+                &other.device_id,
+                vec![TransferSource::file(src)],
+                None,
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(unidrop_core::Error::CertificateMismatch(_))
+        ));
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_quic_dead_port_fails_to_connect() {
+        let cert = crate::cert::generate_self_signed("UniDrop").unwrap();
+        let port = std::net::UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        // 没有服务监听的端口在超时内返回 `Connection`，发送方据此回退到 HTTPS
+        let client = QuicClient::new().unwrap();
+        let target: SocketAddr = format!("127.0.0.1:{}", port).parse().unwrap();
+        let result = client
+            .send_text(target, &cert.device_id, "hello", None)
+            .await;
+        assert!(matches!(result, Err(unidrop_core::Error::Connection(_))));
+    }
 }
@@ -0,0 +1,91 @@
+//! HTTP 子网扫描 - 组播被屏蔽时的兜底发现
+//!
+//! 向本机所在 /24 网段的每个地址 POST `/register`，能应答的即为 LocalSend 设备。
+//! 与官方客户端一致，扫描时使用自己的 HTTP 协议。
+
+use futures::StreamExt;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tracing::{debug, info};
+
+use unidrop_core::Event;
+
+use crate::client::HttpClient;
+use crate::devices::DeviceTable;
+use crate::models::Scheme;
+use crate::server::device_from_info;
+
+/// 同时进行的注册请求数
+const SCAN_CONCURRENCY: usize = 32;
+
+/// 本机所在 /24 网段中除自己以外的主机地址
+fn subnet_hosts(local_ip: Ipv4Addr) -> impl Iterator<Item = Ipv4Addr> {
+    let [a, b, c, _] = local_ip.octets();
+    (1..=254)
+        .map(move |d| Ipv4Addr::new(a, b, c, d))
+        .filter(move |ip| *ip != local_ip)
+}
+
+/// 扫描本机所在子网，发现的设备写入设备表并发出事件
+pub async fn scan_subnet(
+    client: HttpClient,
+    local_ip: Ipv4Addr,
+    port: u16,
+    scheme: Scheme,
+    local_fingerprint: String,
+    devices: Arc<DeviceTable>,
+    event_tx: mpsc::Sender<Event>,
+) {
+    info!("Scanning {}/24 for LocalSend devices", local_ip);
+
+    futures::stream::iter(subnet_hosts(local_ip))
+        .for_each_concurrent(SCAN_CONCURRENCY, |ip| {
+            let client = &client;
+            let local_fingerprint = &local_fingerprint;
+            let devices = &devices;
+            let event_tx = &event_tx;
+            async move {
+                let info = match client.register(IpAddr::V4(ip), port, scheme).await {
+                    Ok(info) => info,
+                    Err(e) => {
+                        debug!("No LocalSend device at {}: {}", ip, e);
+                        return;
+                    }
+                };
+                if &info.fingerprint == local_fingerprint {
+                    return;
+                }
+
+                let device = device_from_info(&info, IpAddr::V4(ip));
+                info!(
+                    "Discovered device: {} ({})",
+                    device.name(),
+                    device.address()
+                );
+                let evt = if devices.upsert(device.clone(), info.scheme()) {
+                    Event::device_discovered(device)
+                } else {
+                    Event::new(unidrop_core::EventKind::DeviceUpdated(device))
+                };
+                let _ = event_tx.send(evt).await;
+            }
+        })
+        .await;
+
+    debug!("Subnet scan finished");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subnet_hosts() {
+        let hosts: Vec<_> = subnet_hosts(Ipv4Addr::new(192, 168, 1, 20)).collect();
+        assert_eq!(hosts.len(), 253);
+        assert_eq!(hosts[0], Ipv4Addr::new(192, 168, 1, 1));
+        assert_eq!(hosts[252], Ipv4Addr::new(192, 168, 1, 254));
+        assert!(!hosts.contains(&Ipv4Addr::new(192, 168, 1, 20)));
+    }
+}
@@ -1,28 +1,51 @@
 //! LocalSend HTTP 服务器 - 接收文件
 
 use axum::{
-    extract::{Multipart, Query, State},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{
+        ConnectInfo, DefaultBodyLimit, Extension, FromRequest, Multipart, Query, Request, State,
+    },
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     routing::{get, post},
     Json, Router,
 };
-use parking_lot::RwLock;
-use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use bytes::Bytes;
+use futures::{Stream, StreamExt};
+use parking_lot::{Mutex, RwLock};
 use serde::Deserialize;
-use std::collections::HashMap;
-use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::mpsc;
+use std::time::Duration;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{mpsc, oneshot};
 use tokio_rustls::TlsAcceptor;
 use tower::ServiceExt;
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
-use unidrop_core::{Device, Event};
+use unidrop_core::{
+    create_parent_dirs, resolve_save_path, sanitize_file_name, CancellationToken, Capability,
+    CollisionPolicy, Device, DeviceType, Event, FileHasher, PairingGuard, PartFile, Peer, PinGuard,
+    PinStatus, ProgressTracker, ProtocolId, TransferRequest, MAX_MESSAGE_LEN,
+};
 
 use crate::cert::CertInfo;
+use crate::devices::DeviceTable;
 use crate::models::*;
+use crate::pairing;
+use crate::pinning::{cert_sha256, is_identity, ClientIdentityVerifier};
+use crate::share::{self, Share};
+
+/// 等待用户确认的最长时间，超时视为拒绝
+pub const ACCEPT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// 会话已被取消时上传请求的响应状态
+pub const CANCELLED_STATUS: StatusCode = StatusCode::CONFLICT;
+
+/// 收到的文件与声明的 SHA-256 不符时上传请求的响应状态
+pub const HASH_MISMATCH_STATUS: StatusCode = StatusCode::UNPROCESSABLE_ENTITY;
 
 /// 传输会话
 pub struct TransferSession {
@@ -30,81 +53,317 @@ pub struct TransferSession {
     pub from: Device,
     pub files: HashMap<String, FileInfo>,
     pub tokens: HashMap<String, String>,
+    /// 未完成文件使用的会话 ID，续传时为最初的会话
+    pub part_key: String,
+    /// 续传时每个文件已接收的字节数
+    pub offsets: HashMap<String, u64>,
+    /// 接受时指定的保存目录
+    pub save_dir: PathBuf,
+    /// 接收进度
+    pub progress: Arc<Mutex<ProgressTracker>>,
+    /// 取消令牌，取消后正在进行的上传立即中止
+    pub cancel: CancellationToken,
+    /// 发送方客户端证书的 SHA-256，设置后上传必须来自出示同一证书的连接
+    pub identity: Option<String>,
+}
+
+/// 连接上客户端证书的 SHA-256，对端未出示证书（如 LocalSend、明文 http）时为 None
+#[derive(Debug, Clone, Default)]
+pub struct ClientIdentity(pub Option<String>);
+
+/// 用户对上传请求的决定
+#[derive(Debug)]
+pub enum UploadDecision {
+    /// 接受，文件保存到指定目录；`files` 为 None 时接受全部文件
+    Accept {
+        save_dir: PathBuf,
+        files: Option<HashSet<String>>,
+    },
+    /// 拒绝
+    Reject,
+}
+
+/// 等待用户确认的接收请求（HTTP 与 QUIC 共用）：session_id -> 决定通道
+#[derive(Default)]
+pub struct PendingUploads {
+    replies: RwLock<HashMap<String, oneshot::Sender<UploadDecision>>>,
+}
+
+impl PendingUploads {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 登记等待确认的请求，返回接收决定的通道
+    pub fn register(&self, session_id: &str) -> oneshot::Receiver<UploadDecision> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        self.replies
+            .write()
+            .insert(session_id.to_string(), reply_tx);
+        reply_rx
+    }
+
+    /// 移除等待中的请求（超时、发送方断开或取消）
+    pub fn remove(&self, session_id: &str) -> Option<oneshot::Sender<UploadDecision>> {
+        self.replies.write().remove(session_id)
+    }
+
+    /// 送达用户的决定
+    pub fn resolve(&self, session_id: &str, decision: UploadDecision) -> unidrop_core::Result<()> {
+        let reply = self
+            .remove(session_id)
+            .ok_or_else(|| unidrop_core::Error::InvalidSession(session_id.to_string()))?;
+
+        // 发送方可能已断开，此时等待确认的请求已不存在
+        reply
+            .send(decision)
+            .map_err(|_| unidrop_core::Error::InvalidSession(session_id.to_string()))
+    }
 }
 
 /// 服务器状态
 pub struct ServerState {
     pub local_info: DeviceInfo,
+    /// 已发现设备表，`/register` 的调用方会被加入
+    pub devices: Arc<DeviceTable>,
     pub sessions: RwLock<HashMap<String, TransferSession>>,
-    pub save_dir: PathBuf,
-    pub pin: Option<String>,
+    /// 等待确认的上传请求（与 QUIC 服务器共享）
+    pub pending: Arc<PendingUploads>,
+    /// 接收 PIN 及猜测限制（与 QUIC 服务器共享）
+    pub pin: Arc<PinGuard>,
+    /// 当前配对短码
+    pub pairing: PairingGuard,
+    /// 重名文件处理策略
+    pub collision_policy: CollisionPolicy,
+    /// 是否保留子目录
+    pub keep_subdirs: bool,
+    /// 当前分享（反向传输）
+    pub share: RwLock<Option<Arc<Share>>>,
+    /// 等待用户确认的最长时间
+    pub accept_timeout: Duration,
     pub event_tx: mpsc::Sender<Event>,
 }
 
-/// HTTPS 服务器
+impl ServerState {
+    /// 本机设备信息，分享中时声明支持下载
+    pub fn device_info(&self) -> DeviceInfo {
+        let mut info = self.local_info.clone();
+        info.download = Some(self.share.read().is_some());
+        info
+    }
+
+    /// 当前分享
+    pub fn share(&self) -> Option<Arc<Share>> {
+        self.share.read().clone()
+    }
+
+    /// 发布分享，替换之前的分享，返回分享 ID
+    pub fn start_share(&self, share: Share) -> String {
+        let id = share.id.clone();
+        info!("Sharing {} files (share {})", share.files.len(), id);
+        *self.share.write() = Some(Arc::new(share));
+        id
+    }
+
+    /// 停止分享，返回之前是否在分享
+    pub fn stop_share(&self) -> bool {
+        self.share.write().take().is_some()
+    }
+
+    /// 接受等待中的上传请求
+    pub fn accept(&self, session_id: &str, save_dir: PathBuf) -> unidrop_core::Result<()> {
+        self.resolve(
+            session_id,
+            UploadDecision::Accept {
+                save_dir,
+                files: None,
+            },
+        )
+    }
+
+    /// 只接受上传请求中的部分文件
+    pub fn accept_files(
+        &self,
+        session_id: &str,
+        file_ids: Vec<String>,
+        save_dir: PathBuf,
+    ) -> unidrop_core::Result<()> {
+        self.resolve(
+            session_id,
+            UploadDecision::Accept {
+                save_dir,
+                files: Some(file_ids.into_iter().collect()),
+            },
+        )
+    }
+
+    /// 拒绝等待中的上传请求
+    pub fn reject(&self, session_id: &str) -> unidrop_core::Result<()> {
+        self.resolve(session_id, UploadDecision::Reject)
+    }
+
+    /// 取消等待确认的请求或进行中的接收会话，返回会话是否存在
+    pub async fn cancel(&self, session_id: &str) -> bool {
+        if let Some(reply) = self.pending.remove(session_id) {
+            let _ = reply.send(UploadDecision::Reject);
+        } else {
+            let session = self.sessions.write().remove(session_id);
+            let Some(session) = session else {
+                return false;
+            };
+            session.cancel.cancel();
+            let progress = session.progress.lock().cancel();
+            self.emit(Event::transfer_progress(progress)).await;
+        }
+
+        info!("Cancelled session: {}", session_id);
+        self.emit(Event::transfer_failed(
+            session_id,
+            unidrop_core::Error::Cancelled.to_string(),
+        ))
+        .await;
+        true
+    }
+
+    /// 计算文件的保存路径，None 表示跳过
+    fn save_path(&self, save_dir: &Path, file_name: &str) -> Option<PathBuf> {
+        resolve_save_path(
+            save_dir,
+            file_name,
+            self.keep_subdirs,
+            self.collision_policy,
+        )
+    }
+
+    /// 发送事件
+    pub(crate) async fn emit(&self, event: Event) {
+        let _ = self
+            .event_tx
+            .send(event.with_protocol(crate::PROTOCOL_ID))
+            .await;
+    }
+
+    fn resolve(&self, session_id: &str, decision: UploadDecision) -> unidrop_core::Result<()> {
+        self.pending.resolve(session_id, decision)
+    }
+}
+
+/// HTTP(S) 服务器
 pub struct HttpServer {
     state: Arc<ServerState>,
     port: u16,
-    tls_acceptor: TlsAcceptor,
+    /// 为 `None` 时提供明文 http（本机声明的协议为 http）
+    tls_acceptor: Option<TlsAcceptor>,
+    shutdown: CancellationToken,
 }
 
 impl HttpServer {
     pub fn new(
         local_info: DeviceInfo,
-        save_dir: PathBuf,
+        devices: Arc<DeviceTable>,
         pin: Option<String>,
+        collision_policy: CollisionPolicy,
+        keep_subdirs: bool,
         event_tx: mpsc::Sender<Event>,
         cert_info: &CertInfo,
     ) -> unidrop_core::Result<Self> {
         let port = local_info.port;
+        let scheme = local_info.scheme();
         let state = Arc::new(ServerState {
             local_info,
+            devices,
             sessions: RwLock::new(HashMap::new()),
-            save_dir,
-            pin,
+            pending: Arc::new(PendingUploads::new()),
+            pin: Arc::new(PinGuard::new(pin)),
+            pairing: PairingGuard::new(),
+            collision_policy,
+            keep_subdirs,
+            share: RwLock::new(None),
+            accept_timeout: ACCEPT_TIMEOUT,
             event_tx,
         });
 
-        // 创建 TLS 配置
-        let cert = CertificateDer::from(cert_info.cert_der.clone());
-        let key = PrivateKeyDer::try_from(cert_info.key_der.clone())
-            .map_err(|e| unidrop_core::Error::Protocol(format!("Invalid key: {}", e)))?;
+        if scheme == Scheme::Http {
+            return Ok(Self {
+                state,
+                port,
+                tls_acceptor: None,
+                shutdown: CancellationToken::new(),
+            });
+        }
 
         // 确保 crypto provider 已安装
         let _ = rustls::crypto::ring::default_provider().install_default();
 
-        let tls_config = rustls::ServerConfig::builder()
-            .with_no_client_auth()
-            .with_single_cert(vec![cert], key)
-            .map_err(|e| unidrop_core::Error::Protocol(format!("TLS config error: {}", e)))?;
+        // 创建 TLS 配置，接受（可选的）自签名客户端证书以验证发送方身份
+        let tls_config = ClientIdentityVerifier::new().server_config(cert_info)?;
 
         let tls_acceptor = TlsAcceptor::from(Arc::new(tls_config));
 
         Ok(Self {
             state,
             port,
-            tls_acceptor,
+            tls_acceptor: Some(tls_acceptor),
+            shutdown: CancellationToken::new(),
         })
     }
 
-    /// 启动 HTTPS 服务器
+    /// 设置停止令牌，取消后关闭监听端口和已有连接
+    pub fn with_shutdown(mut self, shutdown: CancellationToken) -> Self {
+        self.shutdown = shutdown;
+        self
+    }
+
+    /// 获取共享状态（用于协议层接受/拒绝请求）
+    pub fn state(&self) -> Arc<ServerState> {
+        self.state.clone()
+    }
+
+    /// 启动服务器
     pub async fn start(&self) -> unidrop_core::Result<()> {
         let app = Router::new()
             .route("/api/localsend/v2/register", post(register))
             .route("/api/localsend/v2/prepare-upload", post(prepare_upload))
-            .route("/api/localsend/v2/upload", post(upload_simple))
+            .route(
+                "/api/localsend/v2/upload",
+                post(upload_simple).layer(DefaultBodyLimit::disable()),
+            )
             .route("/api/localsend/v2/cancel", post(cancel))
             .route("/api/localsend/v2/info", get(info))
+            .route("/api/localsend/v2/pair/commit", post(pairing::commit))
+            .route("/api/localsend/v2/pair/reveal", post(pairing::reveal))
+            .route("/api/localsend/v2/pair", post(pairing::pair))
+            .route(
+                "/api/localsend/v2/prepare-download",
+                post(share::prepare_download),
+            )
+            .route("/api/localsend/v2/download", get(share::download))
+            .route("/", get(share::index))
             .with_state(self.state.clone());
 
         let addr = SocketAddr::from(([0, 0, 0, 0], self.port));
-        info!("LocalSend HTTPS server listening on {}", addr);
+        info!(
+            "LocalSend {} server listening on {}",
+            if self.tls_acceptor.is_some() {
+                "HTTPS"
+            } else {
+                "HTTP"
+            },
+            addr
+        );
 
         let listener = tokio::net::TcpListener::bind(addr).await?;
         let tls_acceptor = self.tls_acceptor.clone();
 
         loop {
-            let (stream, peer_addr) = match listener.accept().await {
+            let accepted = tokio::select! {
+                _ = self.shutdown.cancelled() => {
+                    info!("LocalSend server on {} stopped", addr);
+                    return Ok(());
+                }
+                accepted = listener.accept() => accepted,
+            };
+            let (stream, peer_addr) = match accepted {
                 Ok(conn) => conn,
                 Err(e) => {
                     error!("Accept error: {}", e);
@@ -114,46 +373,96 @@ impl HttpServer {
 
             let tls_acceptor = tls_acceptor.clone();
             let app = app.clone();
+            let shutdown = self.shutdown.clone();
 
-            tokio::spawn(async move {
+            let serve = async move {
+                let Some(tls_acceptor) = tls_acceptor else {
+                    serve_connection(stream, app, peer_addr, ClientIdentity::default()).await;
+                    return;
+                };
                 match tls_acceptor.accept(stream).await {
                     Ok(tls_stream) => {
-                        let io = hyper_util::rt::TokioIo::new(tls_stream);
-                        let service = hyper::service::service_fn(move |req| {
-                            let app = app.clone();
-                            async move { app.oneshot(req).await }
-                        });
-
-                        if let Err(e) = hyper_util::server::conn::auto::Builder::new(
-                            hyper_util::rt::TokioExecutor::new(),
-                        )
-                        .serve_connection(io, service)
-                        .await
-                        {
-                            // 忽略连接关闭错误
-                            if !e.to_string().contains("connection closed") {
-                                error!("Connection error from {}: {}", peer_addr, e);
-                            }
-                        }
+                        let identity = tls_stream
+                            .get_ref()
+                            .1
+                            .peer_certificates()
+                            .and_then(|certs| certs.first())
+                            .map(|cert| cert_sha256(cert));
+                        serve_connection(tls_stream, app, peer_addr, ClientIdentity(identity)).await
                     }
                     Err(e) => {
                         // TLS 握手失败通常是正常的（比如客户端探测）
                         tracing::debug!("TLS handshake failed from {}: {}", peer_addr, e);
                     }
                 }
+            };
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = shutdown.cancelled() => {}
+                    _ = serve => {}
+                }
             });
         }
     }
 }
 
+/// 在单个连接上提供服务
+async fn serve_connection<S>(
+    stream: S,
+    app: Router,
+    peer_addr: SocketAddr,
+    identity: ClientIdentity,
+) where
+    S: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + 'static,
+{
+    let io = hyper_util::rt::TokioIo::new(stream);
+    let service =
+        hyper::service::service_fn(move |mut req: hyper::Request<hyper::body::Incoming>| {
+            let app = app.clone();
+            // 让 handler 能拿到对端真实地址和客户端证书
+            req.extensions_mut().insert(ConnectInfo(peer_addr));
+            req.extensions_mut().insert(identity.clone());
+            async move { app.oneshot(req).await }
+        });
+
+    if let Err(e) =
+        hyper_util::server::conn::auto::Builder::new(hyper_util::rt::TokioExecutor::new())
+            .serve_connection(io, service)
+            .await
+    {
+        // 忽略连接关闭错误
+        if !e.to_string().contains("connection closed") {
+            error!("Connection error from {}: {}", peer_addr, e);
+        }
+    }
+}
+
 // === HTTP Handlers ===
 
 /// POST /register - 设备注册
+///
+/// 对方收到组播公告或扫描子网时调用，按其真实 IP 记录为已发现设备。
 async fn register(
     State(state): State<Arc<ServerState>>,
-    Json(_device): Json<DeviceInfo>,
+    ConnectInfo(remote): ConnectInfo<SocketAddr>,
+    Json(info): Json<DeviceInfo>,
 ) -> impl IntoResponse {
-    Json(state.local_info.clone())
+    if info.fingerprint != state.local_info.fingerprint {
+        let device = device_from_info(&info, remote.ip());
+        info!(
+            "Device registered: {} ({})",
+            device.name(),
+            device.address()
+        );
+        let evt = if state.devices.upsert(device.clone(), info.scheme()) {
+            Event::device_discovered(device)
+        } else {
+            Event::new(unidrop_core::EventKind::DeviceUpdated(device))
+        };
+        let _ = state.event_tx.send(evt).await;
+    }
+
+    Json(state.device_info())
 }
 
 #[derive(Debug, Deserialize)]
@@ -162,35 +471,158 @@ pub struct PrepareUploadQuery {
 }
 
 /// POST /prepare-upload - 准备上传
+///
+/// 请求会一直挂起，直到上层调用 accept/reject 或超时。
+/// 带 [`RESUME_HEADER`] 时为 UniDrop 续传，响应中返回保留的未完成文件的偏移。
+/// 客户端出示的证书须与其声明的指纹一致，此时请求的发送方身份视为已验证。
 async fn prepare_upload(
     State(state): State<Arc<ServerState>>,
+    ConnectInfo(remote): ConnectInfo<SocketAddr>,
+    Extension(identity): Extension<ClientIdentity>,
     Query(query): Query<PrepareUploadQuery>,
+    headers: HeaderMap,
     Json(request): Json<PrepareUploadRequest>,
-) -> Result<Json<PrepareUploadResponse>, StatusCode> {
-    // 验证 PIN
-    if let Some(required_pin) = &state.pin {
-        match &query.pin {
-            Some(pin) if pin == required_pin => {}
-            _ => return Err(StatusCode::UNAUTHORIZED),
+) -> Result<Response, StatusCode> {
+    // 验证 PIN，同一 IP 连续输错后暂时锁定
+    match state
+        .pin
+        .check(&remote.ip().to_string(), query.pin.as_deref())
+    {
+        PinStatus::Accepted => {}
+        PinStatus::Required => return Err(StatusCode::UNAUTHORIZED),
+        PinStatus::LockedOut => return Err(StatusCode::TOO_MANY_REQUESTS),
+    }
+
+    let verified = match &identity.0 {
+        Some(hash) if is_identity(&request.info.fingerprint, hash) => true,
+        Some(hash) => {
+            warn!(
+                "{} claimed {} but presented {}",
+                remote, request.info.fingerprint, hash
+            );
+            return Err(StatusCode::FORBIDDEN);
         }
+        None => false,
+    };
+
+    let from = device_from_info(&request.info, remote.ip());
+
+    // 文本消息无需上传，按规范返回 204
+    if let Some(text) = message_text(&request) {
+        info!("Message from {} ({})", from.name(), remote);
+        state.emit(Event::message_received(from, text)).await;
+        return Ok(StatusCode::NO_CONTENT.into_response());
     }
 
-    // 创建会话
     let session_id = uuid::Uuid::new_v4().to_string();
-    let mut file_tokens = HashMap::new();
 
-    for (file_id, _) in &request.files {
-        file_tokens.insert(file_id.clone(), uuid::Uuid::new_v4().to_string());
+    let files = request
+        .files
+        .values()
+        .map(|f| {
+            let mut info =
+                unidrop_core::FileInfo::new(&f.id, &f.file_name, f.size).with_mime(&f.file_type);
+            info.hash = f.sha256.clone();
+            info
+        })
+        .collect();
+    let transfer_request =
+        TransferRequest::new(&session_id, from.clone(), files).with_verified(verified);
+
+    // 登记等待确认，再通知上层
+    let reply_rx = state.pending.register(&session_id);
+
+    info!(
+        "Upload request {} from {} ({}), waiting for confirmation",
+        session_id,
+        from.name(),
+        remote
+    );
+
+    if state
+        .event_tx
+        .send(Event::transfer_requested(transfer_request))
+        .await
+        .is_err()
+    {
+        state.pending.remove(&session_id);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    let decision = tokio::time::timeout(state.accept_timeout, reply_rx).await;
+    state.pending.remove(&session_id);
+
+    let (save_dir, selected) = match decision {
+        Ok(Ok(UploadDecision::Accept { save_dir, files })) => (save_dir, files),
+        Ok(Ok(UploadDecision::Reject)) | Ok(Err(_)) => {
+            info!("Upload request {} rejected", session_id);
+            return Err(StatusCode::FORBIDDEN);
+        }
+        Err(_) => {
+            warn!("Upload request {} timed out", session_id);
+            return Err(StatusCode::FORBIDDEN);
+        }
+    };
+
+    // 创建会话，未选中或按冲突策略跳过的文件不分配 token
+    let file_tokens: HashMap<String, String> = request
+        .files
+        .iter()
+        .filter(|(file_id, _)| selected.as_ref().is_none_or(|s| s.contains(*file_id)))
+        .filter(|(_, f)| state.save_path(&save_dir, &f.file_name).is_some())
+        .map(|(file_id, _)| (file_id.clone(), uuid::Uuid::new_v4().to_string()))
+        .collect();
+
+    if file_tokens.is_empty() {
+        info!("No files of {} to receive", session_id);
+        state.emit(Event::transfer_completed(&session_id)).await;
+        return Ok(Json(PrepareUploadResponse {
+            session_id,
+            files: file_tokens,
+            offsets: HashMap::new(),
+        })
+        .into_response());
+    }
+
+    let resume_of = headers.get(RESUME_HEADER).and_then(|v| v.to_str().ok());
+    let part_key = resume_of.unwrap_or(&session_id).to_string();
+    let mut offsets = HashMap::new();
+    if resume_of.is_some() {
+        for file_id in file_tokens.keys() {
+            let f = &request.files[file_id];
+            let part = PartFile::new(
+                &save_dir,
+                &f.file_name,
+                state.keep_subdirs,
+                &part_key,
+                file_id,
+                f.size,
+                f.sha256.as_deref(),
+            );
+            offsets.insert(file_id.clone(), part.resume_offset().await);
+        }
+        info!("Resuming {} as {}", part_key, session_id);
     }
 
-    // TODO: 构造 Device 从 request.info
-    // 暂时跳过事件发送，后续完善
+    let total_size = request
+        .files
+        .iter()
+        .filter(|(file_id, _)| file_tokens.contains_key(*file_id))
+        .map(|(_, f)| f.size)
+        .sum();
+    let progress = ProgressTracker::new(&session_id, total_size, file_tokens.len());
 
     let session = TransferSession {
         id: session_id.clone(),
-        from: create_temp_device(&request.info),
-        files: request.files.clone(),
+        from,
+        files: request.files,
         tokens: file_tokens.clone(),
+        part_key,
+        offsets: offsets.clone(),
+        save_dir,
+        progress: Arc::new(Mutex::new(progress)),
+        cancel: CancellationToken::new(),
+        identity: identity.0,
     };
 
     state.sessions.write().insert(session_id.clone(), session);
@@ -200,7 +632,21 @@ async fn prepare_upload(
     Ok(Json(PrepareUploadResponse {
         session_id,
         files: file_tokens,
-    }))
+        offsets,
+    })
+    .into_response())
+}
+
+/// 只有一个带预览的纯文本文件时视为消息（与官方 LocalSend 一致）
+fn message_text(request: &PrepareUploadRequest) -> Option<&str> {
+    let mut files = request.files.values();
+    let file = files.next()?;
+    if files.next().is_some() || !file.file_type.starts_with("text/plain") {
+        return None;
+    }
+    file.preview
+        .as_deref()
+        .filter(|text| text.len() <= MAX_MESSAGE_LEN)
 }
 
 #[derive(Debug, Deserialize)]
@@ -212,14 +658,29 @@ pub struct UploadQuery {
     pub token: String,
 }
 
-/// POST /upload - 简化版上传处理
+/// POST /upload - 上传文件
+///
+/// 同时支持官方 LocalSend 的原始请求体和 multipart 表单，
+/// 数据边接收边写入临时文件，完成后再重命名。
+/// 续传的上传在 [`OFFSET_HEADER`] 中给出起始偏移，须与 prepare-upload 返回的一致。
 async fn upload_simple(
     State(state): State<Arc<ServerState>>,
     Query(query): Query<UploadQuery>,
-    mut multipart: Multipart,
+    request: Request,
 ) -> StatusCode {
+    let offset: u64 = request
+        .headers()
+        .get(OFFSET_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(0);
+    let identity = request
+        .extensions()
+        .get::<ClientIdentity>()
+        .and_then(|identity| identity.0.clone());
+
     // 提取所需数据，尽快释放锁
-    let (file_name, save_dir, valid) = {
+    let (file, save_dir, part_key, tracker, cancel) = {
         let sessions = state.sessions.read();
         let Some(session) = sessions.get(&query.session_id) else {
             return StatusCode::NOT_FOUND;
@@ -233,65 +694,301 @@ async fn upload_simple(
             return StatusCode::UNAUTHORIZED;
         }
 
+        // 会话绑定到发送方的证书
+        if session.identity.is_some() && session.identity != identity {
+            return StatusCode::FORBIDDEN;
+        }
+
         let Some(file_info) = session.files.get(&query.file_id) else {
             return StatusCode::NOT_FOUND;
         };
 
-        (file_info.file_name.clone(), state.save_dir.clone(), true)
+        if offset > 0 && session.offsets.get(&query.file_id) != Some(&offset) {
+            return StatusCode::BAD_REQUEST;
+        }
+
+        (
+            file_info.clone(),
+            session.save_dir.clone(),
+            session.part_key.clone(),
+            session.progress.clone(),
+            session.cancel.clone(),
+        )
     };
 
-    if !valid {
-        return StatusCode::BAD_REQUEST;
+    {
+        let mut tracker = tracker.lock();
+        tracker.start_file(&query.file_id);
+        tracker.resume(offset);
     }
 
-    // 解析 multipart 表单，提取文件内容
-    let file_data = match multipart.next_field().await {
-        Ok(Some(field)) => {
-            match field.bytes().await {
-                Ok(data) => data,
-                Err(e) => {
-                    error!("Failed to read field bytes: {}", e);
-                    return StatusCode::BAD_REQUEST;
-                }
+    let event_tx = state.event_tx.clone();
+    let on_chunk = |len: usize| {
+        if let Some(progress) = tracker.lock().advance(len as u64) {
+            let _ = event_tx
+                .try_send(Event::transfer_progress(progress).with_protocol(crate::PROTOCOL_ID));
+        }
+    };
+
+    let result = receive_file(
+        &state,
+        request,
+        &save_dir,
+        &file,
+        (&part_key, offset),
+        &cancel,
+        on_chunk,
+    )
+    .await;
+
+    match result {
+        Ok(()) => {
+            let (progress, done) = {
+                let mut tracker = tracker.lock();
+                let progress = tracker.finish_file();
+                (progress, tracker.is_done())
+            };
+            state.emit(Event::transfer_progress(progress)).await;
+
+            if done {
+                state.sessions.write().remove(&query.session_id);
+                let progress = tracker.lock().complete();
+                state.emit(Event::transfer_progress(progress)).await;
+                state
+                    .emit(Event::transfer_completed(&query.session_id))
+                    .await;
+                info!("Upload session {} completed", query.session_id);
             }
+
+            StatusCode::OK
         }
-        Ok(None) => {
-            error!("No file field in multipart form");
-            return StatusCode::BAD_REQUEST;
+        // 取消时的事件已由 cancel 发出
+        Err(_) if cancel.is_cancelled() => CANCELLED_STATUS,
+        Err(status) => {
+            state.sessions.write().remove(&query.session_id);
+            let error = if status == HASH_MISMATCH_STATUS {
+                unidrop_core::Error::HashMismatch(file.file_name.clone()).to_string()
+            } else {
+                format!("Failed to receive {}: {}", file.file_name, status)
+            };
+            let progress = tracker.lock().fail(&error);
+            state.emit(Event::transfer_progress(progress)).await;
+            state
+                .emit(Event::transfer_failed(&query.session_id, error))
+                .await;
+            status
         }
-        Err(e) => {
+    }
+}
+
+/// 接收单个文件的请求体并保存
+///
+/// 先从 `offset` 处写入会话 `part_key` 的临时文件，校验声明的 SHA-256 后
+/// 再按冲突策略决定最终文件名。超出接受时的大小返回 413，中途取消或校验失败时
+/// 删除临时文件；发送方断开或请求体短于声明的大小时保留供续传。
+async fn receive_file(
+    state: &Arc<ServerState>,
+    request: Request,
+    save_dir: &Path,
+    file: &FileInfo,
+    resume: (&str, u64),
+    cancel: &CancellationToken,
+    on_chunk: impl FnMut(usize),
+) -> Result<(), StatusCode> {
+    let (part_key, offset) = resume;
+    let file_name = file.file_name.as_str();
+    let relative = sanitize_file_name(file_name, state.keep_subdirs);
+    let target = save_dir.join(&relative);
+    let target_dir = target.parent().unwrap_or(save_dir);
+
+    // 确保保存目录存在
+    if let Err(e) = create_parent_dirs(save_dir, &target).await {
+        error!("Failed to create save dir {:?}: {}", target_dir, e);
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+    let mut part = PartFile::new(
+        save_dir,
+        file_name,
+        state.keep_subdirs,
+        part_key,
+        &file.id,
+        file.size,
+        file.sha256.as_deref(),
+    );
+    let (mut handle, mut hasher) = part.open(offset).await.map_err(|e| {
+        error!("Failed to open {:?}: {}", part.path(), e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let is_multipart = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("multipart/form-data"));
+
+    // 只接收用户确认过的大小
+    let limit = file.size.saturating_sub(offset);
+    let written = if is_multipart {
+        let mut multipart = Multipart::from_request(request, state).await.map_err(|e| {
             error!("Failed to parse multipart form: {}", e);
-            return StatusCode::BAD_REQUEST;
+            StatusCode::BAD_REQUEST
+        })?;
+
+        match multipart.next_field().await {
+            Ok(Some(field)) => {
+                write_stream(field, &mut handle, &mut hasher, limit, cancel, on_chunk).await
+            }
+            Ok(None) => {
+                error!("No file field in multipart form");
+                return Err(StatusCode::BAD_REQUEST);
+            }
+            Err(e) => {
+                error!("Failed to parse multipart form: {}", e);
+                return Err(StatusCode::BAD_REQUEST);
+            }
         }
+    } else {
+        let body = request.into_body().into_data_stream();
+        write_stream(body, &mut handle, &mut hasher, limit, cancel, on_chunk).await
     };
+    drop(handle);
 
-    // 确保保存目录存在
-    let _ = std::fs::create_dir_all(&save_dir);
-    let save_path = save_dir.join(&file_name);
+    let size = match written {
+        Ok(written) if written == limit => offset + written,
+        // 发送方断开或请求体不完整，保留已收到的部分
+        Ok(written) => {
+            warn!(
+                "{} ended after {} of {} bytes",
+                file_name,
+                offset + written,
+                file.size
+            );
+            part.keep().await;
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Err(StatusCode::BAD_REQUEST) => {
+            part.keep().await;
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Err(status) => {
+            part.remove().await;
+            return Err(status);
+        }
+    };
 
-    // 保存文件
-    if let Err(e) = tokio::fs::write(&save_path, &file_data).await {
-        error!("Write error: {}", e);
-        return StatusCode::INTERNAL_SERVER_ERROR;
+    if let Err(e) = hasher.verify(file_name, file.sha256.as_deref()) {
+        error!("{}", e);
+        part.remove().await;
+        return Err(HASH_MISMATCH_STATUS);
+    }
+
+    // 接收期间可能出现同名文件，此时再按策略解析一次
+    let Some(save_path) = state.save_path(save_dir, file_name) else {
+        info!("Skipped existing file: {:?}", relative);
+        part.remove().await;
+        return Ok(());
+    };
+
+    if let Err(e) = part.finish(&save_path).await {
+        error!("Failed to move {:?} to {:?}: {}", part.path(), save_path, e);
+        part.remove().await;
+        return Err(StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    info!("Saved file: {:?} ({} bytes)", save_path, size);
+    Ok(())
+}
+
+/// 将请求体数据流逐块写入文件并计入哈希，返回写入的字节数
+///
+/// 数据超过 `limit` 时返回 413，超出的部分不写入。
+async fn write_stream<S, E>(
+    stream: S,
+    file: &mut File,
+    hasher: &mut FileHasher,
+    limit: u64,
+    cancel: &CancellationToken,
+    mut on_chunk: impl FnMut(usize),
+) -> Result<u64, StatusCode>
+where
+    S: Stream<Item = Result<Bytes, E>>,
+    E: std::fmt::Display,
+{
+    futures::pin_mut!(stream);
+
+    let mut written = 0u64;
+    loop {
+        let chunk = tokio::select! {
+            _ = cancel.cancelled() => return Err(CANCELLED_STATUS),
+            chunk = stream.next() => chunk,
+        };
+        let Some(chunk) = chunk else {
+            break;
+        };
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                error!("Upload stream error: {}", e);
+                let _ = file.flush().await;
+                return Err(StatusCode::BAD_REQUEST);
+            }
+        };
+
+        if written + chunk.len() as u64 > limit {
+            error!("Upload exceeds the accepted size of {} bytes", limit);
+            return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        }
+
+        file.write_all(&chunk).await.map_err(|e| {
+            error!("Write error: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        hasher.update(&chunk);
+        written += chunk.len() as u64;
+        on_chunk(chunk.len());
     }
 
-    info!("Saved file: {:?}", save_path);
-    StatusCode::OK
+    file.flush().await.map_err(|e| {
+        error!("Write error: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(written)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CancelQuery {
+    #[serde(rename = "sessionId")]
+    pub session_id: Option<String>,
 }
 
 /// POST /cancel - 取消传输
+///
+/// 官方 LocalSend 通过查询参数传递会话 ID，旧版 UniDrop 使用 JSON 请求体。
 async fn cancel(
     State(state): State<Arc<ServerState>>,
-    Json(request): Json<CancelRequest>,
+    Query(query): Query<CancelQuery>,
+    body: Bytes,
 ) -> StatusCode {
-    state.sessions.write().remove(&request.session_id);
-    info!("Cancelled session: {}", request.session_id);
-    StatusCode::OK
+    let session_id = query.session_id.or_else(|| {
+        serde_json::from_slice::<CancelRequest>(&body)
+            .ok()
+            .map(|r| r.session_id)
+    });
+    let Some(session_id) = session_id else {
+        return StatusCode::BAD_REQUEST;
+    };
+
+    if state.cancel(&session_id).await {
+        StatusCode::OK
+    } else {
+        StatusCode::NOT_FOUND
+    }
 }
 
 /// GET /info - 设备信息
 async fn info(State(state): State<Arc<ServerState>>) -> Json<DeviceInfo> {
-    Json(state.local_info.clone())
+    Json(state.device_info())
 }
 
 /// 解析上传查询参数
@@ -320,11 +1017,8 @@ fn parse_upload_query(query: &str) -> Option<UploadQuery> {
     })
 }
 
-/// 临时创建 Device（后续需要完善）
-fn create_temp_device(info: &DeviceInfo) -> Device {
-    use std::net::Ipv4Addr;
-    use unidrop_core::{DeviceType, Peer, ProtocolId};
-
+/// 根据对端上报的 DeviceInfo 和真实 IP 构造 Device
+pub(crate) fn device_from_info(info: &DeviceInfo, ip: IpAddr) -> Device {
     let peer = Peer::new(
         ProtocolId::new(crate::PROTOCOL_ID),
         info.fingerprint.clone(),
@@ -332,15 +1026,491 @@ fn create_temp_device(info: &DeviceInfo) -> Device {
     )
     .with_device_type(
         info.device_type
-            .as_ref()
-            .map(|t| DeviceType::from_str(t))
+            .as_deref()
+            .map(DeviceType::from_str)
             .unwrap_or(DeviceType::Desktop),
     )
     .with_version(&info.version);
 
-    Device::new(
-        peer,
-        std::net::IpAddr::V4(Ipv4Addr::UNSPECIFIED),
-        info.port,
-    )
+    let peer = match &info.device_model {
+        Some(model) => peer.with_model(model),
+        None => peer,
+    };
+
+    let device = Device::new(peer, ip, info.port);
+    match info.quic_port {
+        Some(port) => device.with_capability(Capability::Quic { port }),
+        None => device,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::HttpClient;
+    use crate::pinning::PinStore;
+    use unidrop_core::TransferSource;
+
+    const LOCALHOST: IpAddr = IpAddr::V4(std::net::Ipv4Addr::LOCALHOST);
+
+    /// 在空闲端口上启动服务器
+    async fn start_server(
+        scheme: Scheme,
+        pin: Option<&str>,
+    ) -> (DeviceInfo, Arc<ServerState>, mpsc::Receiver<Event>) {
+        start_server_with_timeout(scheme, pin, ACCEPT_TIMEOUT).await
+    }
+
+    async fn start_server_with_timeout(
+        scheme: Scheme,
+        pin: Option<&str>,
+        accept_timeout: Duration,
+    ) -> (DeviceInfo, Arc<ServerState>, mpsc::Receiver<Event>) {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let cert = crate::cert::generate_self_signed("UniDrop").unwrap();
+        let info =
+            DeviceInfo::new("Server".into(), cert.device_id.clone(), port).with_scheme(scheme);
+        let (event_tx, event_rx) = mpsc::channel(256);
+        let mut server = HttpServer::new(
+            info.clone(),
+            Arc::new(DeviceTable::new()),
+            pin.map(str::to_string),
+            CollisionPolicy::default(),
+            false,
+            event_tx,
+            &cert,
+        )
+        .unwrap();
+        Arc::get_mut(&mut server.state).unwrap().accept_timeout = accept_timeout;
+        let state = server.state();
+        tokio::spawn(async move { server.start().await });
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        (info, state, event_rx)
+    }
+
+    fn http_client(scheme: Scheme, devices: Arc<DeviceTable>) -> HttpClient {
+        let (event_tx, _) = mpsc::channel(256);
+        HttpClient::new(
+            DeviceInfo::new("Client".into(), "CLIENT-FINGERPRINT".into(), 53317)
+                .with_scheme(scheme),
+            Arc::new(PinStore::in_memory()),
+            event_tx,
+        )
+        .with_devices(devices)
+    }
+
+    #[tokio::test]
+    async fn test_register_adds_caller() {
+        let (server_info, state, mut server_rx) = start_server(Scheme::Https, None).await;
+
+        let client = http_client(Scheme::Https, Arc::new(DeviceTable::new()));
+        let info = client
+            .register(LOCALHOST, server_info.port, Scheme::Https)
+            .await
+            .unwrap();
+        assert_eq!(info.fingerprint, server_info.fingerprint);
+
+        let device = state.devices.get("CLIENT-FINGERPRINT").unwrap();
+        assert_eq!(device.ip, LOCALHOST);
+        assert_eq!(device.port, 53317);
+        assert!(matches!(
+            server_rx.try_recv().unwrap().kind,
+            unidrop_core::EventKind::DeviceDiscovered(_)
+        ));
+    }
+
+    #[test]
+    fn test_quic_capability() {
+        // 未启用 QUIC 时不输出扩展字段，保持与 LocalSend 兼容
+        let info = DeviceInfo::new("Peer".into(), "PEER".into(), 53317);
+        assert!(!serde_json::to_string(&info)
+            .unwrap()
+            .contains("unidropQuicPort"));
+        assert_eq!(device_from_info(&info, LOCALHOST).quic_port(), None);
+
+        let json = serde_json::to_string(&info.with_quic_port(40000)).unwrap();
+        let info: DeviceInfo = serde_json::from_str(&json).unwrap();
+        assert_eq!(device_from_info(&info, LOCALHOST).quic_port(), Some(40000));
+    }
+
+    #[tokio::test]
+    async fn test_plain_http_transfer() {
+        let dir = std::env::temp_dir().join(format!("unidrop-http-{}", uuid::Uuid::new_v4()));
+        let save_dir = dir.join("inbox");
+
+        let (server_info, state, mut server_rx) = start_server(Scheme::Http, None).await;
+        let accept_dir = save_dir.clone();
+        tokio::spawn(async move {
+            while let Some(event) = server_rx.recv().await {
+                if let unidrop_core::EventKind::TransferRequested(request) = event.kind {
+                    state.accept(&request.id, accept_dir.clone()).unwrap();
+                }
+            }
+        });
+
+        // 对端通过注册响应声明 http，客户端据此选择协议
+        let devices = Arc::new(DeviceTable::new());
+        let client = http_client(Scheme::Http, devices.clone());
+        let info = client
+            .register(LOCALHOST, server_info.port, Scheme::Http)
+            .await
+            .unwrap();
+        assert_eq!(info.scheme(), Scheme::Http);
+        let target = device_from_info(&info, LOCALHOST);
+        devices.upsert(target.clone(), info.scheme());
+
+        // 内存数据直接上传，不经过临时文件
+        let source = TransferSource::bytes("note.txt", &b"hello over http"[..]);
+        client
+            .send_files(&target, vec![source], None)
+            .await
+            .unwrap();
+        assert_eq!(
+            std::fs::read(save_dir.join("note.txt")).unwrap(),
+            b"hello over http"
+        );
+
+        // https 客户端连不上明文服务器
+        let https_client = http_client(Scheme::Https, Arc::new(DeviceTable::new()));
+        assert!(https_client.info(&target).await.is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    /// 连接到服务器的客户端和目标设备
+    async fn connect(server_info: &DeviceInfo) -> (HttpClient, Device) {
+        let devices = Arc::new(DeviceTable::new());
+        let client = http_client(Scheme::Https, devices.clone());
+        let info = client
+            .register(LOCALHOST, server_info.port, Scheme::Https)
+            .await
+            .unwrap();
+        let target = device_from_info(&info, LOCALHOST);
+        devices.upsert(target.clone(), info.scheme());
+        (client, target)
+    }
+
+    #[tokio::test]
+    async fn test_rejected_upload() {
+        let (server_info, state, mut server_rx) = start_server(Scheme::Https, None).await;
+        let reject_state = state.clone();
+        tokio::spawn(async move {
+            while let Some(event) = server_rx.recv().await {
+                if let unidrop_core::EventKind::TransferRequested(request) = event.kind {
+                    reject_state.reject(&request.id).unwrap();
+                }
+            }
+        });
+
+        let (client, target) = connect(&server_info).await;
+        let source = TransferSource::bytes("a.txt", &b"unwanted"[..]);
+        let result = client.send_files(&target, vec![source], None).await;
+        assert!(matches!(result, Err(unidrop_core::Error::Rejected)));
+        assert!(state.sessions.read().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_upload_times_out() {
+        let (server_info, state, _server_rx) =
+            start_server_with_timeout(Scheme::Https, None, Duration::from_millis(200)).await;
+
+        // 无人确认时超时视为拒绝
+        let (client, target) = connect(&server_info).await;
+        let source = TransferSource::bytes("a.txt", &b"unwanted"[..]);
+        let result = client.send_files(&target, vec![source], None).await;
+        assert!(matches!(result, Err(unidrop_core::Error::Rejected)));
+        assert!(state.sessions.read().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_upload_must_match_accepted_size() {
+        let dir = std::env::temp_dir().join(format!("unidrop-size-{}", uuid::Uuid::new_v4()));
+        let (server_info, state, mut server_rx) = start_server(Scheme::Http, None).await;
+        let accept_dir = dir.clone();
+        tokio::spawn(async move {
+            while let Some(event) = server_rx.recv().await {
+                if let unidrop_core::EventKind::TransferRequested(request) = event.kind {
+                    state.accept(&request.id, accept_dir.clone()).unwrap();
+                }
+            }
+        });
+
+        let http = reqwest::Client::new();
+        let base_url = format!("http://127.0.0.1:{}/api/localsend/v2", server_info.port);
+        let upload = |body: &'static [u8]| {
+            let (http, base_url) = (http.clone(), base_url.clone());
+            async move {
+                let file = FileInfo {
+                    id: "f".into(),
+                    file_name: "a.txt".into(),
+                    size: 4,
+                    file_type: "text/plain".into(),
+                    sha256: None,
+                    preview: None,
+                };
+                let request = PrepareUploadRequest {
+                    info: DeviceInfo::new("Client".into(), "CLIENT".into(), 53317),
+                    files: HashMap::from([("f".to_string(), file)]),
+                };
+                let prepared: PrepareUploadResponse = http
+                    .post(format!("{}/prepare-upload", base_url))
+                    .json(&request)
+                    .send()
+                    .await
+                    .unwrap()
+                    .json()
+                    .await
+                    .unwrap();
+                http.post(format!("{}/upload", base_url))
+                    .query(&[
+                        ("sessionId", prepared.session_id.as_str()),
+                        ("fileId", "f"),
+                        ("token", prepared.files["f"].as_str()),
+                    ])
+                    .body(body)
+                    .send()
+                    .await
+                    .unwrap()
+                    .status()
+            }
+        };
+        let parts = || {
+            std::fs::read_dir(&dir)
+                .unwrap()
+                .filter(|e| e.as_ref().unwrap().path().extension() == Some("part".as_ref()))
+                .count()
+        };
+
+        // 超出确认的大小时中止并丢弃
+        let status = upload(b"much more than four bytes").await;
+        assert_eq!(status, reqwest::StatusCode::PAYLOAD_TOO_LARGE);
+        assert!(!dir.join("a.txt").exists());
+        assert_eq!(parts(), 0);
+
+        // 不完整的请求体按断开处理，保留临时文件
+        let status = upload(b"ab").await;
+        assert_eq!(status, reqwest::StatusCode::BAD_REQUEST);
+        assert!(!dir.join("a.txt").exists());
+        assert_eq!(parts(), 1);
+
+        assert_eq!(upload(b"abcd").await, reqwest::StatusCode::OK);
+        assert_eq!(std::fs::read(dir.join("a.txt")).unwrap(), b"abcd");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_mutual_tls_identity() {
+        let dir = std::env::temp_dir().join(format!("unidrop-mtls-{}", uuid::Uuid::new_v4()));
+        let (server_info, state, mut server_rx) = start_server(Scheme::Https, None).await;
+        let (verified_tx, mut verified_rx) = mpsc::channel(4);
+        let accept_dir = dir.clone();
+        tokio::spawn(async move {
+            while let Some(event) = server_rx.recv().await {
+                if let unidrop_core::EventKind::TransferRequested(request) = event.kind {
+                    let _ = verified_tx.send(request.verified).await;
+                    state.accept(&request.id, accept_dir.clone()).unwrap();
+                }
+            }
+        });
+
+        let identity_client = |info: DeviceInfo, cert: CertInfo, devices: Arc<DeviceTable>| {
+            let (event_tx, _) = mpsc::channel(256);
+            HttpClient::new(info, Arc::new(PinStore::in_memory()), event_tx)
+                .with_devices(devices)
+                .with_identity(cert)
+        };
+
+        // 出示的证书与声明的指纹一致，身份已验证
+        let cert = crate::cert::generate_self_signed("UniDrop").unwrap();
+        let info = DeviceInfo::new("Client".into(), cert.device_id.clone(), 53317);
+        let devices = Arc::new(DeviceTable::new());
+        let client = identity_client(info.clone(), cert, devices.clone());
+        let server = client
+            .register(LOCALHOST, server_info.port, Scheme::Https)
+            .await
+            .unwrap();
+        let target = device_from_info(&server, LOCALHOST);
+        devices.upsert(target.clone(), server.scheme());
+        let source = TransferSource::bytes("a.txt", &b"verified"[..]);
+        client
+            .send_files(&target, vec![source], None)
+            .await
+            .unwrap();
+        assert_eq!(verified_rx.recv().await, Some(true));
+
+        // 冒用他人指纹的客户端被拒绝
+        let other = crate::cert::generate_self_signed("UniDrop").unwrap();
+        let impostor = identity_client(info, other, devices);
+        let source = TransferSource::bytes("b.txt", &b"forged"[..]);
+        assert!(impostor
+            .send_files(&target, vec![source], None)
+            .await
+            .is_err());
+        assert!(verified_rx.try_recv().is_err());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_pairing() {
+        let (server_info, state, mut server_rx) = start_server(Scheme::Https, None).await;
+
+        let cert = crate::cert::generate_self_signed("UniDrop").unwrap();
+        let fingerprint = cert.device_id.clone();
+        let info = DeviceInfo::new("Client".into(), fingerprint.clone(), 53317);
+        let (event_tx, _) = mpsc::channel(256);
+        let client =
+            HttpClient::new(info, Arc::new(PinStore::in_memory()), event_tx).with_identity(cert);
+        let server = client
+            .register(LOCALHOST, server_info.port, Scheme::Https)
+            .await
+            .unwrap();
+        let target = device_from_info(&server, LOCALHOST);
+
+        // 未在配对时拒绝
+        assert!(matches!(
+            client.pair(&target, "000000").await,
+            Err(unidrop_core::Error::PairingFailed(_))
+        ));
+
+        // 输错后短码作废，需要重新生成
+        let code = state.pairing.start();
+        let wrong = if code == "000000" { "111111" } else { "000000" };
+        assert!(client.pair(&target, wrong).await.is_err());
+        assert!(client.pair(&target, &code).await.is_err());
+
+        let code = state.pairing.start();
+        client.pair(&target, &code).await.unwrap();
+
+        let mut paired = false;
+        while let Ok(event) = server_rx.try_recv() {
+            if let unidrop_core::EventKind::DevicePaired(device) = event.kind {
+                assert_eq!(device.peer.id.fingerprint, fingerprint);
+                paired = true;
+            }
+        }
+        assert!(paired);
+
+        // 短码只能使用一次
+        assert!(client.pair(&target, &code).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_text_message() {
+        let (server_info, _state, mut server_rx) = start_server(Scheme::Https, None).await;
+
+        let client = http_client(Scheme::Https, Arc::new(DeviceTable::new()));
+        let info = client
+            .register(LOCALHOST, server_info.port, Scheme::Https)
+            .await
+            .unwrap();
+        let target = device_from_info(&info, LOCALHOST);
+        while server_rx.try_recv().is_ok() {}
+
+        client
+            .send_text(&target, "https://example.com", None)
+            .await
+            .unwrap();
+        match server_rx.try_recv().unwrap().kind {
+            unidrop_core::EventKind::MessageReceived { from, text } => {
+                assert_eq!(from.peer.id.fingerprint, "CLIENT-FINGERPRINT");
+                assert_eq!(text, "https://example.com");
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+
+        let long = "x".repeat(MAX_MESSAGE_LEN + 1);
+        assert!(client.send_text(&target, &long, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pin_required() {
+        let (server_info, _state, _server_rx) = start_server(Scheme::Https, Some("4321")).await;
+
+        let client = http_client(Scheme::Https, Arc::new(DeviceTable::new()));
+        let info = client
+            .register(LOCALHOST, server_info.port, Scheme::Https)
+            .await
+            .unwrap();
+        let target = device_from_info(&info, LOCALHOST);
+
+        let result = client.send_text(&target, "hi", None).await;
+        assert!(matches!(result, Err(unidrop_core::Error::PinRequired(_))));
+        client.send_text(&target, "hi", Some("4321")).await.unwrap();
+
+        // 连续输错后锁定，正确的 PIN 也被拒绝
+        for _ in 1..unidrop_core::MAX_PIN_ATTEMPTS {
+            let result = client.send_text(&target, "hi", Some("0000")).await;
+            assert!(matches!(result, Err(unidrop_core::Error::PinRequired(_))));
+        }
+        let result = client.send_text(&target, "hi", Some("0000")).await;
+        assert!(matches!(result, Err(unidrop_core::Error::PinLockedOut(_))));
+        let result = client.send_text(&target, "hi", Some("4321")).await;
+        assert!(matches!(result, Err(unidrop_core::Error::PinLockedOut(_))));
+    }
+
+    #[tokio::test]
+    async fn test_share_download() {
+        let dir = std::env::temp_dir().join(format!("unidrop-share-{}", uuid::Uuid::new_v4()));
+        let src = dir.join("photo.jpg");
+        let save_dir = dir.join("downloads");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&src, vec![3u8; 100_000]).unwrap();
+
+        let (server_info, state, mut server_rx) = start_server(Scheme::Http, None).await;
+        let share = Share::new(vec![src], Some("1234".into())).await.unwrap();
+        let share_id = state.start_share(share);
+        assert_eq!(state.device_info().download, Some(true));
+
+        // 浏览器页面需要 PIN 才列出文件
+        let page_url = format!("http://127.0.0.1:{}/", server_info.port);
+        let page = reqwest::get(&page_url).await.unwrap().text().await.unwrap();
+        assert!(!page.contains("photo.jpg"));
+        let page = reqwest::get(format!("{}?pin=1234", page_url))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(page.contains("photo.jpg"));
+
+        let devices = Arc::new(DeviceTable::new());
+        let target = device_from_info(&server_info, LOCALHOST);
+        devices.upsert(target.clone(), Scheme::Http);
+        let client = http_client(Scheme::Http, devices);
+
+        let wrong_pin = client
+            .download(&target, Some("0000"), &save_dir, CollisionPolicy::default())
+            .await;
+        assert!(matches!(
+            wrong_pin,
+            Err(unidrop_core::Error::PinRequired(_))
+        ));
+
+        let session_id = client
+            .download(&target, Some("1234"), &save_dir, CollisionPolicy::default())
+            .await
+            .unwrap();
+        assert_eq!(session_id, share_id);
+        assert_eq!(
+            std::fs::read(save_dir.join("photo.jpg")).unwrap().len(),
+            100_000
+        );
+
+        let mut fetched = false;
+        while let Ok(event) = server_rx.try_recv() {
+            if let unidrop_core::EventKind::ShareFileFetched { file_name, .. } = event.kind {
+                assert_eq!(file_name, "photo.jpg");
+                fetched = true;
+            }
+        }
+        assert!(fetched);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
 }
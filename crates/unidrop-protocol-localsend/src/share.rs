@@ -0,0 +1,269 @@
+//! 分享模式 - LocalSend 反向传输
+//!
+//! 本机发布一组文件，其他设备通过 `/prepare-download` 与 `/download` 拉取，
+//! 浏览器则访问根路径的下载页面。
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Query, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio_util::io::ReaderStream;
+use tracing::{error, info};
+
+use unidrop_core::{mime_from_name, Event, EventKind, PinGuard, PinStatus};
+
+use crate::models::{FileInfo, PrepareDownloadResponse};
+use crate::server::ServerState;
+
+/// 分享的文件
+pub struct SharedFile {
+    pub info: FileInfo,
+    pub path: PathBuf,
+}
+
+/// 分享会话
+pub struct Share {
+    pub id: String,
+    /// 分享的 PIN，限制每个地址的猜测次数
+    pub pin: PinGuard,
+    pub files: HashMap<String, SharedFile>,
+}
+
+impl Share {
+    /// 读取文件信息创建分享
+    pub async fn new(paths: Vec<PathBuf>, pin: Option<String>) -> unidrop_core::Result<Self> {
+        let mut files = HashMap::new();
+        for (idx, path) in paths.into_iter().enumerate() {
+            let metadata = tokio::fs::metadata(&path)
+                .await
+                .ok()
+                .filter(|m| m.is_file())
+                .ok_or_else(|| unidrop_core::Error::FileNotFound(path.display().to_string()))?;
+            let file_name = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("unknown")
+                .to_string();
+
+            let file_id = format!("file_{}", idx);
+            let info = FileInfo {
+                id: file_id.clone(),
+                file_type: mime_from_name(&file_name),
+                file_name,
+                size: metadata.len(),
+                sha256: None,
+                preview: None,
+            };
+            files.insert(file_id, SharedFile { info, path });
+        }
+
+        Ok(Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            pin: PinGuard::new(pin),
+            files,
+        })
+    }
+
+    /// 按文件名排序的文件列表
+    fn sorted_files(&self) -> Vec<&SharedFile> {
+        let mut files: Vec<_> = self.files.values().collect();
+        files.sort_by(|a, b| a.info.file_name.cmp(&b.info.file_name));
+        files
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PrepareDownloadQuery {
+    pub pin: Option<String>,
+}
+
+/// POST /prepare-download - 获取分享的文件列表
+pub async fn prepare_download(
+    State(state): State<Arc<ServerState>>,
+    ConnectInfo(remote): ConnectInfo<SocketAddr>,
+    Query(query): Query<PrepareDownloadQuery>,
+) -> Result<Json<PrepareDownloadResponse>, StatusCode> {
+    let share = state.share().ok_or(StatusCode::FORBIDDEN)?;
+    match share
+        .pin
+        .check(&remote.ip().to_string(), query.pin.as_deref())
+    {
+        PinStatus::Accepted => {}
+        PinStatus::Required => return Err(StatusCode::UNAUTHORIZED),
+        PinStatus::LockedOut => return Err(StatusCode::TOO_MANY_REQUESTS),
+    }
+
+    Ok(Json(PrepareDownloadResponse {
+        info: state.device_info(),
+        session_id: share.id.clone(),
+        files: share
+            .files
+            .iter()
+            .map(|(id, file)| (id.clone(), file.info.clone()))
+            .collect(),
+    }))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DownloadQuery {
+    #[serde(rename = "sessionId")]
+    pub session_id: String,
+    #[serde(rename = "fileId")]
+    pub file_id: String,
+}
+
+/// GET /download - 下载分享的文件
+pub async fn download(
+    State(state): State<Arc<ServerState>>,
+    ConnectInfo(remote): ConnectInfo<SocketAddr>,
+    Query(query): Query<DownloadQuery>,
+) -> Result<Response, StatusCode> {
+    let share = state
+        .share()
+        .filter(|share| share.id == query.session_id)
+        .ok_or(StatusCode::FORBIDDEN)?;
+    let file = share
+        .files
+        .get(&query.file_id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let handle = tokio::fs::File::open(&file.path).await.map_err(|e| {
+        error!("Failed to open shared file {:?}: {}", file.path, e);
+        StatusCode::NOT_FOUND
+    })?;
+    let size = handle
+        .metadata()
+        .await
+        .map(|m| m.len())
+        .unwrap_or(file.info.size);
+
+    info!("Shared file {} fetched by {}", file.info.file_name, remote);
+    let _ = state
+        .event_tx
+        .send(
+            Event::new(EventKind::ShareFileFetched {
+                share_id: share.id.clone(),
+                file_id: file.info.id.clone(),
+                file_name: file.info.file_name.clone(),
+                remote: remote.ip().to_string(),
+            })
+            .with_protocol(crate::PROTOCOL_ID),
+        )
+        .await;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, file.info.file_type.clone()),
+            (header::CONTENT_LENGTH, size.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                content_disposition(&file.info.file_name),
+            ),
+        ],
+        Body::from_stream(ReaderStream::new(handle)),
+    )
+        .into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IndexQuery {
+    pub pin: Option<String>,
+}
+
+/// GET / - 浏览器下载页面
+pub async fn index(
+    State(state): State<Arc<ServerState>>,
+    ConnectInfo(remote): ConnectInfo<SocketAddr>,
+    Query(query): Query<IndexQuery>,
+) -> Html<String> {
+    let alias = &state.local_info.alias;
+    let Some(share) = state.share() else {
+        return Html(page(alias, "<p>No files are being shared.</p>"));
+    };
+
+    let hint = match share
+        .pin
+        .check(&remote.ip().to_string(), query.pin.as_deref())
+    {
+        PinStatus::Accepted => None,
+        PinStatus::Required if query.pin.is_some() => Some("<p>Wrong PIN.</p>"),
+        PinStatus::Required => Some(""),
+        PinStatus::LockedOut => Some("<p>Too many wrong PINs, try again later.</p>"),
+    };
+    if let Some(hint) = hint {
+        let form = format!(
+            "{}<form method=\"get\" action=\"/\">\
+             <input type=\"password\" name=\"pin\" placeholder=\"PIN\" autofocus> \
+             <button type=\"submit\">Open</button></form>",
+            hint
+        );
+        return Html(page(alias, &form));
+    }
+
+    let items: String = share
+        .sorted_files()
+        .iter()
+        .map(|file| {
+            format!(
+                "<li><a href=\"/api/localsend/v2/download?sessionId={}&amp;fileId={}\">{}</a> ({} bytes)</li>",
+                share.id,
+                file.info.id,
+                escape_html(&file.info.file_name),
+                file.info.size
+            )
+        })
+        .collect();
+    Html(page(alias, &format!("<ul>{}</ul>", items)))
+}
+
+/// 下载页面
+fn page(alias: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\">\
+         <meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\
+         <title>{alias}</title></head><body><h1>{alias}</h1>{body}</body></html>",
+        alias = escape_html(alias),
+        body = body
+    )
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// 附件响应头，非 ASCII 文件名使用 RFC 5987 编码
+fn content_disposition(file_name: &str) -> String {
+    let fallback: String = file_name
+        .chars()
+        .map(|c| match c {
+            '"' | '\\' => '_',
+            c if c.is_ascii() && !c.is_ascii_control() => c,
+            _ => '_',
+        })
+        .collect();
+    let encoded: String = file_name
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect();
+    format!(
+        "attachment; filename=\"{}\"; filename*=UTF-8''{}",
+        fallback, encoded
+    )
+}
@@ -1,13 +1,15 @@
 //! P2P 网络行为定义
 
-use std::time::Duration;
 use libp2p::{
     dcutr, identify, ping, relay,
-    request_response::{self, ProtocolSupport, cbor::Behaviour as CborBehaviour},
+    request_response::{self, cbor::Behaviour as CborBehaviour, ProtocolSupport},
     swarm::NetworkBehaviour,
     StreamProtocol,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
+use unidrop_core::PinStatus;
 
 /// 文件信息
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -20,6 +22,9 @@ pub struct P2pFileInfo {
     pub size: u64,
     /// MIME 类型
     pub mime_type: Option<String>,
+    /// SHA-256（小写十六进制），接收方据此校验
+    #[serde(default)]
+    pub sha256: Option<String>,
 }
 
 /// 文件传输请求
@@ -29,6 +34,15 @@ pub struct FileRequest {
     pub transfer_id: String,
     /// 文件列表
     pub files: Vec<P2pFileInfo>,
+    /// 文本消息，设置时不传输文件
+    #[serde(default)]
+    pub text: Option<String>,
+    /// 续传之前中断的同一传输
+    #[serde(default)]
+    pub resume: bool,
+    /// 接收方要求的 PIN
+    #[serde(default)]
+    pub pin: Option<String>,
 }
 
 /// 文件传输响应
@@ -40,6 +54,15 @@ pub struct FileResponse {
     pub accepted: bool,
     /// 消息
     pub message: Option<String>,
+    /// 续传时每个文件已接收的字节数（块大小的整数倍）
+    #[serde(default)]
+    pub offsets: HashMap<String, u64>,
+    /// PIN 校验未通过时的原因
+    #[serde(default)]
+    pub pin: Option<PinStatus>,
+    /// 接收方只接受的文件 ID，`None` 时接受全部
+    #[serde(default)]
+    pub files: Option<Vec<String>>,
 }
 
 /// 文件数据块（支持分块传输）
@@ -57,6 +80,9 @@ pub struct FileChunk {
     pub total_chunks: u64,
     /// 数据内容（最大 64KB 以确保在 128KB 中继限制内）
     pub data: Vec<u8>,
+    /// 发送方取消了传输（此时不携带数据）
+    #[serde(default)]
+    pub cancelled: bool,
 }
 
 /// 文件数据确认
@@ -70,11 +96,17 @@ pub struct FileChunkAck {
     pub chunk_index: u64,
     /// 是否成功
     pub success: bool,
+    /// 接收方已取消传输
+    #[serde(default)]
+    pub cancelled: bool,
 }
 
 /// 默认块大小 (64KB)
 pub const DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
 
+/// 等待接收方确认的时间，超时未答复的请求按拒绝处理
+pub const ACCEPT_TIMEOUT: Duration = Duration::from_secs(60);
+
 /// P2P 客户端行为
 #[derive(NetworkBehaviour)]
 pub struct P2pClientBehaviour {
@@ -112,7 +144,9 @@ impl P2pClientBehaviour {
                     StreamProtocol::new("/unidrop/file/1.0.0"),
                     ProtocolSupport::Full,
                 )],
-                request_response::Config::default(),
+                // 请求要等接收方确认，留出收到超时拒绝的余量
+                request_response::Config::default()
+                    .with_request_timeout(ACCEPT_TIMEOUT + Duration::from_secs(10)),
             ),
             file_data: CborBehaviour::new(
                 [(
@@ -0,0 +1,26 @@
+//! P2P 身份持久化 - 保证 Peer ID 在重启后不变
+
+use libp2p::identity::Keypair;
+use tracing::warn;
+
+use unidrop_core::{IdentityStore, Result};
+
+/// 身份存储中的密钥文件名
+const KEYPAIR_FILE: &str = "p2p.key";
+
+/// 从身份存储加载密钥对，不存在或损坏时生成新的 Ed25519 密钥对并保存
+pub fn load_or_create_keypair(store: &IdentityStore) -> Result<Keypair> {
+    if let Some(bytes) = store.load(KEYPAIR_FILE)? {
+        match Keypair::from_protobuf_encoding(&bytes) {
+            Ok(keypair) => return Ok(keypair),
+            Err(e) => warn!("已保存的 P2P 密钥无效，重新生成: {}", e),
+        }
+    }
+
+    let keypair = Keypair::generate_ed25519();
+    let bytes = keypair
+        .to_protobuf_encoding()
+        .map_err(|e| unidrop_core::Error::Internal(e.to_string()))?;
+    store.save(KEYPAIR_FILE, &bytes)?;
+    Ok(keypair)
+}
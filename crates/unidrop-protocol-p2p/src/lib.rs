@@ -6,6 +6,7 @@
 //! - 中转传输 (Circuit Relay v2)
 
 mod behaviour;
+mod identity;
 mod protocol;
 mod transfer;
 
@@ -1,33 +1,37 @@
 //! P2P 协议实现
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::net::{IpAddr, Ipv4Addr};
 use std::path::PathBuf;
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use async_trait::async_trait;
 use futures::StreamExt;
 use libp2p::{
-    Multiaddr, PeerId, SwarmBuilder,
-    dcutr, identify, noise, ping, relay, tcp, yamux,
+    dcutr, identify, noise, ping, relay,
+    request_response::{self, OutboundRequestId, ResponseChannel},
     swarm::SwarmEvent,
-    request_response::{self, OutboundRequestId},
+    tcp, yamux, Multiaddr, PeerId, SwarmBuilder,
 };
 use parking_lot::RwLock;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::sync::{mpsc, oneshot};
-use tracing::{info, warn, debug};
+use tracing::{debug, info, warn};
 
 use unidrop_core::{
-    Device, DeviceId, DeviceType, Event, Protocol, ProtocolBuilder, ProtocolConfig,
-    ProtocolFactory, ProtocolInfo, ProtocolId, Peer, Result, TransferIntent,
-    TransferRequest, FileInfo,
+    create_parent_dirs, resolve_save_path, sanitize_file_name, CancelRegistry, CancellationToken,
+    CollisionPolicy, Device, DeviceId, DeviceType, Event, FileHasher, FileInfo, PartFile, Peer,
+    PinGuard, PinStatus, ProgressTracker, Protocol, ProtocolBuilder, ProtocolConfig,
+    ProtocolFactory, ProtocolId, ProtocolInfo, Result, ResumableSend, ResumeRegistry, SourceReader,
+    TransferIntent, TransferRequest, TransferSource, MAX_MESSAGE_LEN,
 };
 
 use crate::behaviour::{
-    P2pClientBehaviour, P2pClientBehaviourEvent, FileRequest, FileResponse,
-    FileChunk, FileChunkAck,
+    FileChunk, FileChunkAck, FileRequest, FileResponse, P2pClientBehaviour,
+    P2pClientBehaviourEvent, ACCEPT_TIMEOUT, DEFAULT_CHUNK_SIZE,
 };
+use crate::identity::load_or_create_keypair;
 use crate::transfer::{TransferManager, TransferSession};
 
 /// P2P 协议 ID
@@ -42,15 +46,48 @@ pub const DEFAULT_RELAY_SERVERS: &[&str] = &[
 /// Swarm 命令
 enum SwarmCommand {
     /// 连接到对端
-    Dial { addr: Multiaddr, reply: oneshot::Sender<anyhow::Result<()>> },
-    /// 发送文件请求
-    SendRequest { peer_id: PeerId, request: FileRequest, reply: oneshot::Sender<OutboundRequestId> },
+    Dial {
+        addr: Multiaddr,
+        reply: oneshot::Sender<anyhow::Result<()>>,
+    },
+    /// 发送文件请求，等待对端响应
+    SendRequest {
+        peer_id: PeerId,
+        request: FileRequest,
+        reply: oneshot::Sender<Result<FileResponse>>,
+    },
     /// 获取本地 Peer ID
     GetLocalPeerId { reply: oneshot::Sender<PeerId> },
-    /// 发送文件数据块
-    SendFileChunk { peer_id: PeerId, chunk: FileChunk, reply: oneshot::Sender<OutboundRequestId> },
+    /// 发送文件数据块，等待对端确认
+    SendFileChunk {
+        peer_id: PeerId,
+        chunk: FileChunk,
+        reply: oneshot::Sender<Result<FileChunkAck>>,
+    },
+    /// 取消接收中的传输，返回传输是否存在
+    CancelReceive {
+        transfer_id: String,
+        reply: oneshot::Sender<bool>,
+    },
+    /// 答复等待确认的文件请求，`accept` 为 `None` 时拒绝；返回请求是否存在
+    AnswerRequest {
+        transfer_id: String,
+        accept: Option<Acceptance>,
+        reply: oneshot::Sender<bool>,
+    },
 }
 
+/// 接受文件请求时选择的保存目录和文件
+struct Acceptance {
+    /// 保存目录
+    save_dir: PathBuf,
+    /// 只接收的文件 ID，`None` 时接收全部
+    file_ids: Option<HashSet<String>>,
+}
+
+/// 共享的事件发送端（subscribe 可能晚于 start 调用）
+type SharedEventTx = Arc<RwLock<Option<mpsc::Sender<Event>>>>;
+
 /// P2P 协议配置
 #[derive(Debug, Clone)]
 pub struct P2pConfig {
@@ -77,8 +114,8 @@ impl Default for P2pConfig {
 struct SendingFile {
     /// 文件 ID
     file_id: String,
-    /// 文件路径
-    path: PathBuf,
+    /// 数据来源
+    source: TransferSource,
     /// 文件大小
     size: u64,
     /// 已发送的块数
@@ -88,7 +125,7 @@ struct SendingFile {
 }
 
 /// 接收中的文件信息
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 struct ReceivingFile {
     /// 文件 ID
     file_id: String,
@@ -102,6 +139,14 @@ struct ReceivingFile {
     chunks_received: u64,
     /// 总块数
     total_chunks: u64,
+    /// 正在写入的临时文件
+    handle: Option<tokio::fs::File>,
+    /// 临时文件及续传清单
+    part: PartFile,
+    /// 发送方声明的 SHA-256
+    sha256: Option<String>,
+    /// 已接收数据的哈希
+    hasher: FileHasher,
 }
 
 /// 发送会话
@@ -115,6 +160,18 @@ struct SendSession {
     current_file: usize,
 }
 
+/// 等待本端确认的文件请求
+struct PendingRequest {
+    /// 来源 Peer ID
+    peer_id: PeerId,
+    /// 对端的请求
+    request: FileRequest,
+    /// 用于答复对端
+    channel: ResponseChannel<FileResponse>,
+    /// 收到请求的时间
+    received_at: Instant,
+}
+
 /// 接收会话
 #[derive(Debug)]
 struct ReceiveSession {
@@ -124,6 +181,12 @@ struct ReceiveSession {
     files: Vec<ReceivingFile>,
     /// 保存目录
     save_dir: PathBuf,
+    /// 重名文件处理策略
+    collision_policy: CollisionPolicy,
+    /// 是否保留子目录
+    keep_subdirs: bool,
+    /// 接收进度
+    progress: ProgressTracker,
 }
 
 /// P2P 协议实现
@@ -133,12 +196,14 @@ pub struct P2pProtocol {
     running: RwLock<bool>,
     devices: RwLock<Vec<Device>>,
     transfers: Arc<TransferManager>,
-    event_tx: RwLock<Option<mpsc::Sender<Event>>>,
+    /// 发送中传输的取消令牌
+    send_cancels: CancelRegistry,
+    /// 失败后可以续传的发送
+    resumable: ResumeRegistry,
+    event_tx: SharedEventTx,
     local_peer_id: RwLock<Option<PeerId>>,
     shutdown_tx: RwLock<Option<oneshot::Sender<()>>>,
     command_tx: RwLock<Option<mpsc::Sender<SwarmCommand>>>,
-    /// 待处理的传输请求
-    pending_requests: RwLock<HashMap<String, TransferRequest>>,
     /// 中继地址
     relay_addr: RwLock<Option<String>>,
     /// 发送会话
@@ -162,11 +227,12 @@ impl P2pProtocol {
             running: RwLock::new(false),
             devices: RwLock::new(Vec::new()),
             transfers: Arc::new(TransferManager::new()),
-            event_tx: RwLock::new(None),
+            send_cancels: CancelRegistry::new(),
+            resumable: ResumeRegistry::new(),
+            event_tx: Arc::new(RwLock::new(None)),
             local_peer_id: RwLock::new(None),
             shutdown_tx: RwLock::new(None),
             command_tx: RwLock::new(None),
-            pending_requests: RwLock::new(HashMap::new()),
             relay_addr: RwLock::new(None),
             send_sessions: RwLock::new(HashMap::new()),
             receive_sessions: RwLock::new(HashMap::new()),
@@ -199,8 +265,304 @@ impl P2pProtocol {
 
     /// 发送事件
     fn emit_event(&self, event: Event) {
-        if let Some(tx) = self.event_tx.read().as_ref() {
-            let _ = tx.try_send(event);
+        emit(&self.event_tx, event);
+    }
+
+    /// 发送文本消息，对端接受即完成
+    async fn send_text(
+        &self,
+        tx: &mpsc::Sender<SwarmCommand>,
+        peer_id: PeerId,
+        transfer_id: String,
+        text: String,
+        pin: Option<String>,
+    ) -> Result<String> {
+        if text.len() > MAX_MESSAGE_LEN {
+            return Err(unidrop_core::Error::Protocol(format!(
+                "Text message exceeds {} bytes",
+                MAX_MESSAGE_LEN
+            )));
+        }
+
+        let request = FileRequest {
+            transfer_id: transfer_id.clone(),
+            files: Vec::new(),
+            text: Some(text),
+            resume: false,
+            pin,
+        };
+
+        let (reply_tx, reply_rx) = oneshot::channel();
+        tx.send(SwarmCommand::SendRequest {
+            peer_id,
+            request,
+            reply: reply_tx,
+        })
+        .await
+        .map_err(|e| unidrop_core::Error::Protocol(e.to_string()))?;
+        let response = reply_rx
+            .await
+            .map_err(|_| unidrop_core::Error::Protocol("Swarm stopped".into()))??;
+
+        if !response.accepted {
+            return Err(rejection(&response, peer_id));
+        }
+
+        self.emit_event(Event::transfer_completed(&transfer_id));
+        info!("文本消息已发送: {}", transfer_id);
+        Ok(transfer_id)
+    }
+
+    /// 发送文件并按块传输，`resume` 时接收方返回已接收的偏移
+    async fn send_files(
+        &self,
+        tx: &mpsc::Sender<SwarmCommand>,
+        peer_id: PeerId,
+        transfer_id: String,
+        intent: TransferIntent,
+        resume: bool,
+    ) -> Result<String> {
+        // 构建文件请求
+        // 失败时记录未定大小的来源，续传时重新打开
+        let resumable = ResumableSend {
+            session_id: transfer_id.clone(),
+            intent: intent.clone(),
+        };
+        let TransferIntent {
+            target,
+            sources,
+            pin,
+            ..
+        } = intent;
+        let sources =
+            futures::future::try_join_all(sources.into_iter().map(TransferSource::sized)).await?;
+        let mut sending = Vec::with_capacity(sources.len());
+        let mut files = Vec::with_capacity(sources.len());
+        for (i, source) in sources.into_iter().enumerate() {
+            let size = source.size().unwrap_or(0);
+            // 文件 ID 按顺序生成，续传时保持一致
+            let file_id = format!("file_{}", i);
+
+            files.push(crate::behaviour::P2pFileInfo {
+                id: file_id.clone(),
+                name: source.name.clone(),
+                size,
+                mime_type: Some(source.mime_type.clone()),
+                sha256: source.hash().map(str::to_string),
+            });
+            sending.push(SendingFile {
+                file_id,
+                source,
+                size,
+                chunks_sent: 0,
+                total_chunks: size.div_ceil(DEFAULT_CHUNK_SIZE as u64).max(1),
+            });
+        }
+
+        let request = FileRequest {
+            transfer_id: transfer_id.clone(),
+            files,
+            text: None,
+            resume,
+            pin,
+        };
+
+        // 发送请求，等待对端响应
+        let (reply_tx, reply_rx) = oneshot::channel();
+        tx.send(SwarmCommand::SendRequest {
+            peer_id,
+            request,
+            reply: reply_tx,
+        })
+        .await
+        .map_err(|e| unidrop_core::Error::Protocol(e.to_string()))?;
+        let response = reply_rx
+            .await
+            .map_err(|_| unidrop_core::Error::Protocol("Swarm stopped".into()))??;
+
+        if !response.accepted {
+            return Err(rejection(&response, peer_id));
+        }
+
+        // 接收方只接受了部分文件
+        if let Some(ids) = &response.files {
+            sending.retain(|f| ids.contains(&f.file_id));
+        }
+        for file in &mut sending {
+            let offset = response.offsets.get(&file.file_id).copied().unwrap_or(0);
+            file.chunks_sent = resume_chunks(offset, file.size);
+        }
+
+        // 创建传输会话
+        let total_size = sending.iter().map(|f| f.size).sum();
+        let session = TransferSession::new(
+            transfer_id.clone(),
+            target.fingerprint.clone(),
+            sending
+                .first()
+                .map(|f| f.source.name.clone())
+                .unwrap_or_default(),
+            total_size,
+        );
+        self.transfers.add_session(session);
+
+        let mut tracker = ProgressTracker::new(&transfer_id, total_size, sending.len());
+        let mut send_session = SendSession {
+            peer_id,
+            files: sending,
+            current_file: 0,
+        };
+
+        let cancel = self.send_cancels.register(&transfer_id);
+        let result = self
+            .send_chunks(tx, &transfer_id, &mut send_session, &mut tracker, &cancel)
+            .await;
+        self.send_cancels.remove(&transfer_id);
+
+        match result {
+            Ok(()) => {
+                self.transfers.complete(&transfer_id);
+                self.emit_event(Event::transfer_progress(tracker.complete()));
+                self.emit_event(Event::transfer_completed(&transfer_id));
+                info!("传输完成: {}", transfer_id);
+                Ok(transfer_id)
+            }
+            Err(e @ unidrop_core::Error::Cancelled) => {
+                self.transfers.cancel(&transfer_id);
+                self.emit_event(Event::transfer_progress(tracker.cancel()));
+                self.emit_event(Event::transfer_failed(&transfer_id, e.to_string()));
+                info!("传输已取消: {}", transfer_id);
+                Err(e)
+            }
+            Err(e) => {
+                self.resumable.insert(&transfer_id, resumable);
+                self.transfers.fail(&transfer_id, e.to_string());
+                self.emit_event(Event::transfer_progress(tracker.fail(e.to_string())));
+                self.emit_event(Event::transfer_failed(&transfer_id, e.to_string()));
+                Err(e)
+            }
+        }
+    }
+
+    /// 按块发送会话中的所有文件，每块等待对端确认
+    ///
+    /// 本端取消时发送取消块通知对端；对端确认中带有取消标记时停止发送。
+    async fn send_chunks(
+        &self,
+        tx: &mpsc::Sender<SwarmCommand>,
+        transfer_id: &str,
+        session: &mut SendSession,
+        tracker: &mut ProgressTracker,
+        cancel: &CancellationToken,
+    ) -> Result<()> {
+        let mut buffer = vec![0u8; DEFAULT_CHUNK_SIZE];
+
+        while let Some(file) = session.files.get_mut(session.current_file) {
+            let file_name = file.source.name.clone();
+            let offset = file.chunks_sent * DEFAULT_CHUNK_SIZE as u64;
+            let mut reader = file.source.open_at(offset).await?;
+            tracker.start_file(&file.file_id);
+            if offset > 0 {
+                info!("续传文件: {} (从 {} 字节)", file_name, offset);
+                tracker.resume(offset);
+            }
+
+            while file.chunks_sent < file.total_chunks {
+                if cancel.is_cancelled() {
+                    let chunk = FileChunk {
+                        transfer_id: transfer_id.to_string(),
+                        file_id: file.file_id.clone(),
+                        file_name: file_name.clone(),
+                        chunk_index: file.chunks_sent,
+                        total_chunks: file.total_chunks,
+                        data: Vec::new(),
+                        cancelled: true,
+                    };
+                    let (reply_tx, reply_rx) = oneshot::channel();
+                    if tx
+                        .send(SwarmCommand::SendFileChunk {
+                            peer_id: session.peer_id,
+                            chunk,
+                            reply: reply_tx,
+                        })
+                        .await
+                        .is_ok()
+                    {
+                        let _ = reply_rx.await;
+                    }
+                    return Err(unidrop_core::Error::Cancelled);
+                }
+
+                let n = read_full(&mut reader, &mut buffer).await?;
+                let chunk = FileChunk {
+                    transfer_id: transfer_id.to_string(),
+                    file_id: file.file_id.clone(),
+                    file_name: file_name.clone(),
+                    chunk_index: file.chunks_sent,
+                    total_chunks: file.total_chunks,
+                    data: buffer[..n].to_vec(),
+                    cancelled: false,
+                };
+
+                let (reply_tx, reply_rx) = oneshot::channel();
+                tx.send(SwarmCommand::SendFileChunk {
+                    peer_id: session.peer_id,
+                    chunk,
+                    reply: reply_tx,
+                })
+                .await
+                .map_err(|e| unidrop_core::Error::Protocol(e.to_string()))?;
+                let ack = reply_rx
+                    .await
+                    .map_err(|_| unidrop_core::Error::Protocol("Swarm stopped".into()))??;
+
+                if ack.cancelled {
+                    info!("对端取消传输: {}", transfer_id);
+                    return Err(unidrop_core::Error::Cancelled);
+                }
+
+                if !ack.success {
+                    return Err(unidrop_core::Error::TransferFailed(format!(
+                        "Peer failed to store chunk {} of {}",
+                        ack.chunk_index, file_name
+                    )));
+                }
+
+                file.chunks_sent += 1;
+                if let Some(progress) = tracker.advance(n as u64) {
+                    self.transfers
+                        .update_progress(transfer_id, progress.bytes_transferred);
+                    self.emit_event(Event::transfer_progress(progress));
+                }
+            }
+
+            self.emit_event(Event::transfer_progress(tracker.finish_file()));
+            session.current_file += 1;
+        }
+
+        Ok(())
+    }
+
+    /// 答复等待确认的文件请求，请求不存在或对端已断开时返回错误
+    async fn answer_request(&self, transfer_id: &str, accept: Option<Acceptance>) -> Result<()> {
+        let tx = self
+            .command_tx
+            .read()
+            .clone()
+            .ok_or_else(|| unidrop_core::Error::Protocol("Protocol not started".into()))?;
+        let (reply_tx, reply_rx) = oneshot::channel();
+        tx.send(SwarmCommand::AnswerRequest {
+            transfer_id: transfer_id.to_string(),
+            accept,
+            reply: reply_tx,
+        })
+        .await
+        .map_err(|e| unidrop_core::Error::Protocol(e.to_string()))?;
+
+        if reply_rx.await.unwrap_or(false) {
+            Ok(())
+        } else {
+            Err(unidrop_core::Error::InvalidSession(transfer_id.to_string()))
         }
     }
 
@@ -234,15 +596,18 @@ impl Protocol for P2pProtocol {
         &self.info
     }
 
-    async fn start(&self, _config: ProtocolConfig) -> Result<()> {
+    async fn start(&self, config: ProtocolConfig) -> Result<()> {
         if *self.running.read() {
             return Ok(());
         }
 
         info!("启动 P2P 协议...");
 
+        // 加载持久化的密钥对，保证 Peer ID 在重启后不变
+        let keypair = load_or_create_keypair(&config.identity_store())?;
+
         // 创建 libp2p swarm
-        let mut swarm = SwarmBuilder::with_new_identity()
+        let mut swarm = SwarmBuilder::with_existing_identity(keypair)
             .with_tokio()
             .with_tcp(
                 tcp::Config::default(),
@@ -264,37 +629,49 @@ impl Protocol for P2pProtocol {
         *self.local_peer_id.write() = Some(local_peer_id);
         info!("本地 Peer ID: {}", local_peer_id);
 
-        // 监听本地地址
-        let listen_addr: Multiaddr = "/ip4/0.0.0.0/tcp/0".parse().unwrap();
-        swarm.listen_on(listen_addr).map_err(|e| unidrop_core::Error::Protocol(e.to_string()))?;
+        // 监听本地地址，未设置 P2P 配置时自动分配端口
+        let port = self.config.read().as_ref().map_or(0, |c| c.port);
+        let listen_addr: Multiaddr = format!("/ip4/0.0.0.0/tcp/{}", port).parse().unwrap();
+        swarm
+            .listen_on(listen_addr)
+            .map_err(|e| unidrop_core::Error::Protocol(e.to_string()))?;
 
         // 创建通道
         let (shutdown_tx, mut shutdown_rx) = oneshot::channel();
         let (command_tx, mut command_rx) = mpsc::channel::<SwarmCommand>(100);
-        let (event_tx, _) = mpsc::channel(100);
 
         *self.shutdown_tx.write() = Some(shutdown_tx);
         *self.command_tx.write() = Some(command_tx);
-        *self.event_tx.write() = Some(event_tx);
 
         // 克隆需要的数据
         let relay_servers = self.get_relay_servers();
         let devices = Arc::new(RwLock::new(Vec::<Device>::new()));
         let devices_clone = devices.clone();
-        let event_tx_clone = self.event_tx.read().clone();
-        let pending_requests = Arc::new(RwLock::new(HashMap::<String, TransferRequest>::new()));
-        let pending_requests_clone = pending_requests.clone();
+        let event_tx_clone = self.event_tx.clone();
+        let (collision_policy, keep_subdirs) = (config.collision_policy, config.keep_subdirs);
+        let pin_guard = PinGuard::new(config.pin.clone());
         let relay_addr_storage = Arc::new(RwLock::new(None::<String>));
         let relay_addr_clone = relay_addr_storage.clone();
 
-        // 接收文件的缓冲区: transfer_id -> (file_id -> data)
-        let receive_buffers: Arc<RwLock<HashMap<String, HashMap<String, Vec<u8>>>>> =
-            Arc::new(RwLock::new(HashMap::new()));
-        let receive_buffers_clone = receive_buffers.clone();
-
         // 启动 swarm 事件循环
         tokio::spawn(async move {
             let mut relay_reserved = false;
+            // 等待本端确认的文件请求: transfer_id -> request
+            let mut pending_requests: HashMap<String, PendingRequest> = HashMap::new();
+            let mut expiry = tokio::time::interval(Duration::from_secs(1));
+            // 接收会话: transfer_id -> session
+            let mut receive_sessions: HashMap<String, ReceiveSession> = HashMap::new();
+            // 本端已取消、尚未通知发送方的接收传输
+            let mut cancelled_receives: HashSet<String> = HashSet::new();
+            // 等待响应的出站请求
+            let mut pending_responses: HashMap<
+                OutboundRequestId,
+                oneshot::Sender<Result<FileResponse>>,
+            > = HashMap::new();
+            let mut pending_acks: HashMap<
+                OutboundRequestId,
+                oneshot::Sender<Result<FileChunkAck>>,
+            > = HashMap::new();
 
             // 连接中继服务器
             for relay_addr in &relay_servers {
@@ -319,15 +696,82 @@ impl Protocol for P2pProtocol {
                                 }
                                 SwarmCommand::SendRequest { peer_id, request, reply } => {
                                     let req_id = swarm.behaviour_mut().file_transfer.send_request(&peer_id, request);
-                                    let _ = reply.send(req_id);
+                                    pending_responses.insert(req_id, reply);
                                 }
                                 SwarmCommand::GetLocalPeerId { reply } => {
                                     let _ = reply.send(*swarm.local_peer_id());
                                 }
                                 SwarmCommand::SendFileChunk { peer_id, chunk, reply } => {
                                     let req_id = swarm.behaviour_mut().file_data.send_request(&peer_id, chunk);
-                                    let _ = reply.send(req_id);
+                                    pending_acks.insert(req_id, reply);
                                 }
+                                SwarmCommand::CancelReceive { transfer_id, reply } => {
+                                    let found = match receive_sessions.remove(&transfer_id) {
+                                        Some(session) => {
+                                            abort_receive(session, &transfer_id, &event_tx_clone).await;
+                                            // 发送方的下一块确认中带上取消标记
+                                            cancelled_receives.insert(transfer_id);
+                                            true
+                                        }
+                                        None => false,
+                                    };
+                                    let _ = reply.send(found);
+                                }
+                                SwarmCommand::AnswerRequest { transfer_id, accept, reply } => {
+                                    let found = match (pending_requests.remove(&transfer_id), accept) {
+                                        (Some(pending), Some(accept)) => {
+                                            // 同一传输之前的会话（续传时）先关闭，临时文件保留
+                                            if let Some(old) = receive_sessions.remove(&transfer_id) {
+                                                close_receive(old).await;
+                                            }
+                                            let (session, offsets) = open_receive(
+                                                pending.peer_id,
+                                                &pending.request,
+                                                &accept,
+                                                collision_policy,
+                                                keep_subdirs,
+                                            )
+                                            .await;
+                                            // 只接收部分文件时告知发送方
+                                            let files = accept.file_ids.is_some().then(|| {
+                                                session.files.iter().map(|f| f.file_id.clone()).collect()
+                                            });
+                                            let response = FileResponse {
+                                                transfer_id: transfer_id.clone(),
+                                                accepted: true,
+                                                message: None,
+                                                offsets,
+                                                pin: None,
+                                                files,
+                                            };
+                                            let sent = swarm.behaviour_mut().file_transfer.send_response(pending.channel, response).is_ok();
+                                            if sent {
+                                                receive_sessions.insert(transfer_id, session);
+                                            }
+                                            sent
+                                        }
+                                        (Some(pending), None) => {
+                                            let response = rejected(transfer_id, None);
+                                            swarm.behaviour_mut().file_transfer.send_response(pending.channel, response).is_ok()
+                                        }
+                                        (None, _) => false,
+                                    };
+                                    let _ = reply.send(found);
+                                }
+                            }
+                        }
+                    }
+                    _ = expiry.tick() => {
+                        // 超时未答复的请求按拒绝处理
+                        let expired: Vec<String> = pending_requests
+                            .iter()
+                            .filter(|(_, p)| p.received_at.elapsed() >= ACCEPT_TIMEOUT)
+                            .map(|(id, _)| id.clone())
+                            .collect();
+                        for transfer_id in expired {
+                            if let Some(pending) = pending_requests.remove(&transfer_id) {
+                                warn!("文件请求超时: {}", transfer_id);
+                                let _ = swarm.behaviour_mut().file_transfer.send_response(pending.channel, rejected(transfer_id, None));
                             }
                         }
                     }
@@ -361,10 +805,8 @@ impl Protocol for P2pProtocol {
                                     let mut devs = devices_clone.write();
                                     devs.retain(|d| d.id().fingerprint != peer_id.to_string());
 
-                                    if let Some(ref tx) = event_tx_clone {
-                                        let device_id = DeviceId::new(ProtocolId::new(P2P_PROTOCOL_ID), peer_id.to_string());
-                                        let _ = tx.try_send(Event::device_lost(device_id));
-                                    }
+                                    let device_id = DeviceId::new(ProtocolId::new(P2P_PROTOCOL_ID), peer_id.to_string());
+                                    emit(&event_tx_clone, Event::device_lost(device_id));
                                 }
                                 SwarmEvent::Behaviour(P2pClientBehaviourEvent::RelayClient(
                                     relay::client::Event::ReservationReqAccepted { relay_peer_id, .. },
@@ -402,9 +844,7 @@ impl Protocol for P2pProtocol {
                                         devs.push(device.clone());
                                         drop(devs);
 
-                                        if let Some(ref tx) = event_tx_clone {
-                                            let _ = tx.try_send(Event::device_discovered(device));
-                                        }
+                                        emit(&event_tx_clone, Event::device_discovered(device));
                                     }
                                 }
                                 SwarmEvent::Behaviour(P2pClientBehaviourEvent::Ping(ping::Event { peer, result, .. })) => {
@@ -422,7 +862,44 @@ impl Protocol for P2pProtocol {
                                 SwarmEvent::Behaviour(P2pClientBehaviourEvent::FileTransfer(
                                     request_response::Event::Message { peer, message }
                                 )) => {
+                                    // 设置了 PIN 时，请求必须携带正确的 PIN
+                                    let pin_status = match &message {
+                                        request_response::Message::Request { request, .. } => {
+                                            pin_guard.check(&peer.to_string(), request.pin.as_deref())
+                                        }
+                                        _ => PinStatus::Accepted,
+                                    };
                                     match message {
+                                        request_response::Message::Request { request, channel, .. }
+                                            if pin_status != PinStatus::Accepted =>
+                                        {
+                                            warn!("PIN 校验未通过: {} ({:?})", peer, pin_status);
+                                            let response = rejected(request.transfer_id, Some(pin_status));
+                                            let _ = swarm.behaviour_mut().file_transfer.send_response(channel, response);
+                                        }
+                                        request_response::Message::Request {
+                                            request: FileRequest { transfer_id, text: Some(text), .. },
+                                            channel,
+                                            ..
+                                        } => {
+                                            info!("收到文本消息 from {}", peer);
+
+                                            let protocol_id = ProtocolId::new(P2P_PROTOCOL_ID);
+                                            let from_peer = Peer::new(protocol_id, peer.to_string(), peer.to_string())
+                                                .with_device_type(DeviceType::Desktop);
+                                            let from_device = Device::new(from_peer, IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+                                            emit(&event_tx_clone, Event::message_received(from_device, text));
+
+                                            let response = FileResponse {
+                                                transfer_id,
+                                                accepted: true,
+                                                message: None,
+                                                offsets: HashMap::new(),
+                                                pin: None,
+                                                files: None,
+                                            };
+                                            let _ = swarm.behaviour_mut().file_transfer.send_response(channel, response);
+                                        }
                                         request_response::Message::Request { request, channel, .. } => {
                                             info!("收到文件请求: {:?} from {}", request, peer);
 
@@ -438,67 +915,106 @@ impl Protocol for P2pProtocol {
                                                     name: f.name.clone(),
                                                     size: f.size,
                                                     mime_type: f.mime_type.clone().unwrap_or_else(|| "application/octet-stream".to_string()),
-                                                    hash: None,
+                                                    hash: f.sha256.clone(),
                                                     preview: None,
                                                 }
                                             }).collect();
 
+                                            // libp2p 连接已验证对端的 PeerId
                                             let transfer_req = TransferRequest::new(
                                                 request.transfer_id.clone(),
                                                 from_device,
                                                 files,
-                                            );
-
-                                            pending_requests_clone.write().insert(request.transfer_id.clone(), transfer_req.clone());
+                                            )
+                                            .with_verified(true);
 
-                                            if let Some(ref tx) = event_tx_clone {
-                                                let _ = tx.try_send(Event::transfer_requested(transfer_req));
-                                            }
+                                            emit(&event_tx_clone, Event::transfer_requested(transfer_req));
 
-                                            // 发送响应
-                                            let response = FileResponse {
-                                                transfer_id: request.transfer_id,
-                                                accepted: true,
-                                                message: None,
-                                            };
-                                            let _ = swarm.behaviour_mut().file_transfer.send_response(channel, response);
+                                            // 等待本端接受或拒绝后再答复
+                                            pending_requests.insert(
+                                                request.transfer_id.clone(),
+                                                PendingRequest {
+                                                    peer_id: peer,
+                                                    request,
+                                                    channel,
+                                                    received_at: Instant::now(),
+                                                },
+                                            );
                                         }
-                                        request_response::Message::Response { response, .. } => {
+                                        request_response::Message::Response { request_id, response } => {
                                             info!("收到文件响应: {:?}", response);
+                                            if let Some(reply) = pending_responses.remove(&request_id) {
+                                                let _ = reply.send(Ok(response));
+                                            }
                                         }
                                     }
                                 }
+                                SwarmEvent::Behaviour(P2pClientBehaviourEvent::FileTransfer(
+                                    request_response::Event::OutboundFailure { request_id, error, .. }
+                                )) => {
+                                    warn!("文件请求失败: {}", error);
+                                    if let Some(reply) = pending_responses.remove(&request_id) {
+                                        let _ = reply.send(Err(unidrop_core::Error::Network(error.to_string())));
+                                    }
+                                }
                                 SwarmEvent::Behaviour(P2pClientBehaviourEvent::FileData(
                                     request_response::Event::Message { peer: _, message }
                                 )) => {
                                     match message {
                                         request_response::Message::Request { request: chunk, channel, .. } => {
-                                            info!("收到文件块: transfer={}, file={}, chunk={}/{}, size={}",
+                                            debug!("收到文件块: transfer={}, file={}, chunk={}/{}, size={}",
                                                 chunk.transfer_id, chunk.file_id, chunk.chunk_index + 1, chunk.total_chunks, chunk.data.len());
 
-                                            // 保存数据到缓冲区
-                                            {
-                                                let mut buffers = receive_buffers_clone.write();
-                                                let transfer_buffers = buffers.entry(chunk.transfer_id.clone()).or_insert_with(HashMap::new);
-                                                let file_buffer = transfer_buffers.entry(chunk.file_id.clone()).or_insert_with(Vec::new);
-                                                file_buffer.extend_from_slice(&chunk.data);
-                                            }
+                                            let (success, cancelled) = if chunk.cancelled {
+                                                if let Some(session) = receive_sessions.remove(&chunk.transfer_id) {
+                                                    info!("对端取消传输: {}", chunk.transfer_id);
+                                                    abort_receive(session, &chunk.transfer_id, &event_tx_clone).await;
+                                                }
+                                                (true, false)
+                                            } else if cancelled_receives.remove(&chunk.transfer_id) {
+                                                (false, true)
+                                            } else {
+                                                match receive_chunk(&mut receive_sessions, &chunk, &event_tx_clone).await {
+                                                    Ok(()) => (true, false),
+                                                    Err(e) => {
+                                                        warn!("写入文件块失败: {}", e);
+                                                        if let Some(mut session) = receive_sessions.remove(&chunk.transfer_id) {
+                                                            remove_temp_files(&mut session).await;
+                                                            emit(&event_tx_clone, Event::transfer_progress(session.progress.fail(e.to_string())));
+                                                            emit(&event_tx_clone, Event::transfer_failed(&chunk.transfer_id, e.to_string()));
+                                                        }
+                                                        (false, false)
+                                                    }
+                                                }
+                                            };
 
                                             // 发送确认
                                             let ack = FileChunkAck {
                                                 transfer_id: chunk.transfer_id,
                                                 file_id: chunk.file_id,
                                                 chunk_index: chunk.chunk_index,
-                                                success: true,
+                                                success,
+                                                cancelled,
                                             };
                                             let _ = swarm.behaviour_mut().file_data.send_response(channel, ack);
                                         }
-                                        request_response::Message::Response { response: ack, .. } => {
-                                            info!("收到块确认: transfer={}, file={}, chunk={}, success={}",
+                                        request_response::Message::Response { request_id, response: ack } => {
+                                            debug!("收到块确认: transfer={}, file={}, chunk={}, success={}",
                                                 ack.transfer_id, ack.file_id, ack.chunk_index, ack.success);
+                                            if let Some(reply) = pending_acks.remove(&request_id) {
+                                                let _ = reply.send(Ok(ack));
+                                            }
                                         }
                                     }
                                 }
+                                SwarmEvent::Behaviour(P2pClientBehaviourEvent::FileData(
+                                    request_response::Event::OutboundFailure { request_id, error, .. }
+                                )) => {
+                                    warn!("文件块发送失败: {}", error);
+                                    if let Some(reply) = pending_acks.remove(&request_id) {
+                                        let _ = reply.send(Err(unidrop_core::Error::Network(error.to_string())));
+                                    }
+                                }
                                 _ => {}
                             }
                         }
@@ -549,79 +1065,105 @@ impl Protocol for P2pProtocol {
         let transfer_id = uuid::Uuid::new_v4().to_string();
 
         // 获取目标设备的 peer_id
-        let peer_id_str = intent.target.fingerprint.clone();
-        let peer_id: PeerId = peer_id_str.parse()
+        let peer_id: PeerId = intent
+            .target
+            .fingerprint
+            .parse()
             .map_err(|e| unidrop_core::Error::Protocol(format!("Invalid peer id: {}", e)))?;
 
-        // 构建文件请求
-        let files: Vec<crate::behaviour::P2pFileInfo> = intent.files.iter().map(|path| {
-            let name = path.file_name()
-                .map(|n| n.to_string_lossy().to_string())
-                .unwrap_or_else(|| "unknown".to_string());
-            let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
-
-            crate::behaviour::P2pFileInfo {
-                id: uuid::Uuid::new_v4().to_string(),
-                name,
-                size,
-                mime_type: None,
-            }
-        }).collect();
+        let tx = self
+            .command_tx
+            .read()
+            .clone()
+            .ok_or_else(|| unidrop_core::Error::Protocol("Protocol not started".into()))?;
 
-        let request = FileRequest {
-            transfer_id: transfer_id.clone(),
-            files,
-        };
-
-        // 发送请求
-        let tx = self.command_tx.read().clone();
-        if let Some(tx) = tx {
-            let (reply_tx, reply_rx) = oneshot::channel();
-            let cmd = SwarmCommand::SendRequest {
-                peer_id,
-                request,
-                reply: reply_tx,
-            };
-            tx.send(cmd).await.map_err(|e| unidrop_core::Error::Protocol(e.to_string()))?;
-            let _ = reply_rx.await;
+        if let Some(text) = intent.text {
+            return self
+                .send_text(&tx, peer_id, transfer_id, text, intent.pin)
+                .await;
         }
 
-        // 创建传输会话
-        let session = TransferSession::new(
-            transfer_id.clone(),
-            peer_id_str,
-            intent.files.first()
-                .and_then(|p| p.file_name())
-                .map(|n| n.to_string_lossy().to_string())
-                .unwrap_or_default(),
-            intent.files.iter()
-                .filter_map(|p| std::fs::metadata(p).ok())
-                .map(|m| m.len())
-                .sum(),
-        );
-        self.transfers.add_session(session);
+        self.send_files(&tx, peer_id, transfer_id, intent, false)
+            .await
+    }
 
-        Ok(transfer_id)
+    async fn accept(&self, request_id: &str, save_dir: PathBuf) -> Result<()> {
+        info!("接受传输请求: {}", request_id);
+        let accept = Acceptance {
+            save_dir,
+            file_ids: None,
+        };
+        self.answer_request(request_id, Some(accept)).await
     }
 
-    async fn accept(&self, request_id: &str, _save_dir: PathBuf) -> Result<()> {
-        if let Some(_request) = self.pending_requests.write().remove(request_id) {
-            info!("接受传输请求: {}", request_id);
-            // TODO: 开始接收文件
-        }
-        Ok(())
+    async fn accept_files(
+        &self,
+        request_id: &str,
+        file_ids: Vec<String>,
+        save_dir: PathBuf,
+    ) -> Result<()> {
+        info!("接受传输请求: {} ({} 个文件)", request_id, file_ids.len());
+        let accept = Acceptance {
+            save_dir,
+            file_ids: Some(file_ids.into_iter().collect()),
+        };
+        self.answer_request(request_id, Some(accept)).await
     }
 
     async fn reject(&self, request_id: &str) -> Result<()> {
-        if let Some(_request) = self.pending_requests.write().remove(request_id) {
-            info!("拒绝传输请求: {}", request_id);
-        }
-        Ok(())
+        info!("拒绝传输请求: {}", request_id);
+        self.answer_request(request_id, None).await
     }
 
     async fn cancel(&self, transfer_id: &str) -> Result<()> {
-        self.transfers.cancel(transfer_id);
-        Ok(())
+        // 发送中的传输在下一块前停止
+        if self.send_cancels.cancel(transfer_id) {
+            return Ok(());
+        }
+
+        // 接收会话由 swarm 事件循环持有
+        let tx = self
+            .command_tx
+            .read()
+            .clone()
+            .ok_or_else(|| unidrop_core::Error::Protocol("Protocol not started".into()))?;
+        let (reply_tx, reply_rx) = oneshot::channel();
+        tx.send(SwarmCommand::CancelReceive {
+            transfer_id: transfer_id.to_string(),
+            reply: reply_tx,
+        })
+        .await
+        .map_err(|e| unidrop_core::Error::Protocol(e.to_string()))?;
+
+        if reply_rx.await.unwrap_or(false) {
+            Ok(())
+        } else {
+            Err(unidrop_core::Error::InvalidSession(transfer_id.to_string()))
+        }
+    }
+
+    async fn resume(&self, transfer_id: &str) -> Result<String> {
+        let send = self
+            .resumable
+            .get(transfer_id)
+            .ok_or_else(|| unidrop_core::Error::InvalidSession(transfer_id.to_string()))?;
+        let peer_id: PeerId = send
+            .intent
+            .target
+            .fingerprint
+            .parse()
+            .map_err(|e| unidrop_core::Error::Protocol(format!("Invalid peer id: {}", e)))?;
+        let tx = self
+            .command_tx
+            .read()
+            .clone()
+            .ok_or_else(|| unidrop_core::Error::Protocol("Protocol not started".into()))?;
+
+        // 沿用原传输 ID，接收方据此找到保留的临时文件
+        info!("续传: {}", transfer_id);
+        self.resumable.remove(transfer_id);
+        self.send_files(&tx, peer_id, send.session_id, send.intent, true)
+            .await
     }
 
     fn subscribe(&self) -> mpsc::Receiver<Event> {
@@ -631,6 +1173,253 @@ impl Protocol for P2pProtocol {
     }
 }
 
+/// 对端拒绝请求时的错误，区分 PIN 校验失败和用户拒绝
+fn rejection(response: &FileResponse, peer_id: PeerId) -> unidrop_core::Error {
+    match response.pin {
+        Some(PinStatus::Required) => unidrop_core::Error::PinRequired(peer_id.to_string()),
+        Some(PinStatus::LockedOut) => unidrop_core::Error::PinLockedOut(peer_id.to_string()),
+        _ => unidrop_core::Error::Rejected,
+    }
+}
+
+/// 拒绝文件请求的响应
+fn rejected(transfer_id: String, pin: Option<PinStatus>) -> FileResponse {
+    FileResponse {
+        transfer_id,
+        accepted: false,
+        message: None,
+        offsets: HashMap::new(),
+        pin,
+        files: None,
+    }
+}
+
+/// 通过共享发送端发送事件
+fn emit(event_tx: &SharedEventTx, event: Event) {
+    if let Some(tx) = event_tx.read().as_ref() {
+        let _ = tx.try_send(event.with_protocol(P2P_PROTOCOL_ID));
+    }
+}
+
+/// 读满缓冲区或直到文件结束，返回读取的字节数
+async fn read_full(reader: &mut SourceReader, buffer: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buffer.len() {
+        let n = reader.read(&mut buffer[filled..]).await?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+    Ok(filled)
+}
+
+/// 续传的起始块：已接收的完整块数，至少留下最后一块以完成文件
+fn resume_chunks(offset: u64, size: u64) -> u64 {
+    let total_chunks = size.div_ceil(DEFAULT_CHUNK_SIZE as u64).max(1);
+    (offset / DEFAULT_CHUNK_SIZE as u64).min(total_chunks - 1)
+}
+
+/// 为接受的请求建立接收会话，返回会话和续传文件已接收的偏移
+///
+/// 只包含选中的文件，续传的文件从已接收的完整块之后继续。
+async fn open_receive(
+    peer_id: PeerId,
+    request: &FileRequest,
+    accept: &Acceptance,
+    collision_policy: CollisionPolicy,
+    keep_subdirs: bool,
+) -> (ReceiveSession, HashMap<String, u64>) {
+    let save_dir = accept.save_dir.as_path();
+    let selected: Vec<_> = request
+        .files
+        .iter()
+        .filter(|f| {
+            accept
+                .file_ids
+                .as_ref()
+                .is_none_or(|ids| ids.contains(&f.id))
+        })
+        .collect();
+    let total_size = selected.iter().map(|f| f.size).sum();
+    let mut files = Vec::with_capacity(selected.len());
+    let mut offsets = HashMap::new();
+    for f in &selected {
+        let part = PartFile::new(
+            save_dir,
+            &f.name,
+            keep_subdirs,
+            &request.transfer_id,
+            &f.id,
+            f.size,
+            f.sha256.as_deref(),
+        );
+        let chunks_received = if request.resume {
+            resume_chunks(part.resume_offset().await, f.size)
+        } else {
+            0
+        };
+        if chunks_received > 0 {
+            offsets.insert(f.id.clone(), chunks_received * DEFAULT_CHUNK_SIZE as u64);
+        }
+        files.push(ReceivingFile {
+            file_id: f.id.clone(),
+            name: f.name.clone(),
+            size: f.size,
+            save_dir: save_dir.to_path_buf(),
+            chunks_received,
+            total_chunks: 0,
+            handle: None,
+            part,
+            sha256: f.sha256.clone(),
+            hasher: FileHasher::new(),
+        });
+    }
+    if !offsets.is_empty() {
+        info!("续传: {} ({} 个文件)", request.transfer_id, offsets.len());
+    }
+
+    let session = ReceiveSession {
+        peer_id,
+        files,
+        save_dir: save_dir.to_path_buf(),
+        collision_policy,
+        keep_subdirs,
+        progress: ProgressTracker::new(&request.transfer_id, total_size, selected.len()),
+    };
+    (session, offsets)
+}
+
+/// 关闭会话中打开的临时文件，保留已写入的部分
+async fn close_receive(mut session: ReceiveSession) {
+    for file in &mut session.files {
+        if let Some(mut handle) = file.handle.take() {
+            let _ = handle.flush().await;
+        }
+    }
+}
+
+/// 将收到的数据块写入对应文件，并上报进度
+async fn receive_chunk(
+    sessions: &mut HashMap<String, ReceiveSession>,
+    chunk: &FileChunk,
+    event_tx: &SharedEventTx,
+) -> Result<()> {
+    let session = sessions
+        .get_mut(&chunk.transfer_id)
+        .ok_or_else(|| unidrop_core::Error::InvalidSession(chunk.transfer_id.clone()))?;
+
+    let file = session
+        .files
+        .iter_mut()
+        .find(|f| f.file_id == chunk.file_id)
+        .ok_or_else(|| unidrop_core::Error::Protocol(format!("Unknown file: {}", chunk.file_id)))?;
+
+    if chunk.chunk_index != file.chunks_received {
+        return Err(unidrop_core::Error::Protocol(format!(
+            "Out of order chunk {} (expected {})",
+            chunk.chunk_index, file.chunks_received
+        )));
+    }
+
+    if file.handle.is_none() {
+        // 先写入临时文件，完成后再按冲突策略确定文件名
+        let target = file
+            .save_dir
+            .join(sanitize_file_name(&file.name, session.keep_subdirs));
+        create_parent_dirs(&file.save_dir, &target).await?;
+
+        // 续传时已有的部分计入哈希
+        let offset = file.chunks_received * DEFAULT_CHUNK_SIZE as u64;
+        let (handle, hasher) = file.part.open(offset).await?;
+        file.handle = Some(handle);
+        file.hasher = hasher;
+        file.total_chunks = chunk.total_chunks;
+        session.progress.start_file(&file.file_id);
+        session.progress.resume(offset);
+    }
+
+    if let Some(handle) = file.handle.as_mut() {
+        handle.write_all(&chunk.data).await?;
+        file.hasher.update(&chunk.data);
+    }
+    file.chunks_received += 1;
+
+    if let Some(progress) = session.progress.advance(chunk.data.len() as u64) {
+        emit(event_tx, Event::transfer_progress(progress));
+    }
+
+    if file.chunks_received >= file.total_chunks {
+        if let Some(mut handle) = file.handle.take() {
+            handle.flush().await?;
+            drop(handle);
+
+            let hasher = std::mem::take(&mut file.hasher);
+            if let Err(e) = hasher.verify(&file.name, file.sha256.as_deref()) {
+                file.part.remove().await;
+                return Err(e);
+            }
+            match resolve_save_path(
+                &file.save_dir,
+                &file.name,
+                session.keep_subdirs,
+                session.collision_policy,
+            ) {
+                Some(path) => {
+                    file.part.finish(&path).await?;
+                    info!(
+                        "文件接收完成: {} ({} bytes) -> {:?}",
+                        file.name, file.size, path
+                    );
+                }
+                None => {
+                    file.part.remove().await;
+                    info!("文件已存在，跳过: {}", file.name);
+                }
+            }
+        }
+        emit(
+            event_tx,
+            Event::transfer_progress(session.progress.finish_file()),
+        );
+    }
+
+    if session.progress.is_done() {
+        if let Some(mut session) = sessions.remove(&chunk.transfer_id) {
+            emit(
+                event_tx,
+                Event::transfer_progress(session.progress.complete()),
+            );
+            emit(event_tx, Event::transfer_completed(&chunk.transfer_id));
+            info!("传输完成: {} (from {})", chunk.transfer_id, session.peer_id);
+        }
+    }
+
+    Ok(())
+}
+
+/// 关闭并删除会话中未写完的临时文件
+async fn remove_temp_files(session: &mut ReceiveSession) {
+    for file in &mut session.files {
+        file.handle = None;
+        file.part.remove().await;
+    }
+}
+
+/// 中止接收会话：删除临时文件并上报取消
+async fn abort_receive(mut session: ReceiveSession, transfer_id: &str, event_tx: &SharedEventTx) {
+    remove_temp_files(&mut session).await;
+    emit(
+        event_tx,
+        Event::transfer_progress(session.progress.cancel()),
+    );
+    emit(
+        event_tx,
+        Event::transfer_failed(transfer_id, unidrop_core::Error::Cancelled.to_string()),
+    );
+    info!("接收已取消: {} (from {})", transfer_id, session.peer_id);
+}
+
 /// P2P 协议工厂
 pub struct P2pFactory;
 
@@ -660,3 +1449,164 @@ impl Default for P2pFactory {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use unidrop_core::EventKind;
+
+    /// 在本机启动一个节点，返回节点、事件接收端和监听端口
+    async fn start_peer(
+        dir: &Path,
+        pin: Option<&str>,
+    ) -> (Arc<P2pProtocol>, mpsc::Receiver<Event>, u16) {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let protocol = P2pProtocol::new().with_config(P2pConfig {
+            relay_servers: vec![],
+            port,
+            use_default_bootstrap: false,
+        });
+        let events = protocol.subscribe();
+        let config = ProtocolConfig {
+            save_dir: dir.join("default"),
+            config_dir: dir.to_path_buf(),
+            pin: pin.map(str::to_string),
+            ..Default::default()
+        };
+        protocol.start(config).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        (Arc::new(protocol), events, port)
+    }
+
+    /// 连接到接收方并等待识别完成，返回接收方的 Peer ID
+    async fn connect(
+        sender: &P2pProtocol,
+        events: &mut mpsc::Receiver<Event>,
+        receiver: &P2pProtocol,
+        port: u16,
+    ) -> PeerId {
+        let peer_id = receiver.local_peer_id.read().unwrap();
+        let addr = format!("/ip4/127.0.0.1/tcp/{}/p2p/{}", port, peer_id);
+        let tx = sender.command_tx.read().clone().unwrap();
+        let (reply_tx, reply_rx) = oneshot::channel();
+        tx.send(SwarmCommand::Dial {
+            addr: addr.parse().unwrap(),
+            reply: reply_tx,
+        })
+        .await
+        .unwrap();
+        reply_rx.await.unwrap().unwrap();
+
+        tokio::time::timeout(Duration::from_secs(10), async {
+            while let Some(event) = events.recv().await {
+                if matches!(event.kind, EventKind::DeviceDiscovered(_)) {
+                    break;
+                }
+            }
+        })
+        .await
+        .unwrap();
+        peer_id
+    }
+
+    /// 收到请求时接受到 `save_dir`
+    fn accept_requests(
+        protocol: Arc<P2pProtocol>,
+        mut events: mpsc::Receiver<Event>,
+        save_dir: PathBuf,
+    ) {
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                if let EventKind::TransferRequested(request) = event.kind {
+                    protocol
+                        .accept(&request.id, save_dir.clone())
+                        .await
+                        .unwrap();
+                }
+            }
+        });
+    }
+
+    #[tokio::test]
+    async fn test_pin_rejection() {
+        let dir = std::env::temp_dir().join(format!("unidrop-p2p-pin-{}", uuid::Uuid::new_v4()));
+        let (receiver, receiver_rx, port) = start_peer(&dir.join("receiver"), Some("1234")).await;
+        let (sender, mut sender_rx, _) = start_peer(&dir.join("sender"), None).await;
+        let peer_id = connect(&sender, &mut sender_rx, &receiver, port).await;
+        let inbox = dir.join("inbox");
+        accept_requests(receiver.clone(), receiver_rx, inbox.clone());
+
+        // 未携带 PIN 的请求不会交给接收方确认
+        let target = DeviceId::new(ProtocolId::new(P2P_PROTOCOL_ID), peer_id.to_string());
+        let source = TransferSource::bytes("note.txt", b"hello".to_vec());
+        let intent = TransferIntent::from_sources(target, vec![source]);
+        let err = sender.send(intent.clone()).await.unwrap_err();
+        assert!(matches!(err, unidrop_core::Error::PinRequired(_)));
+        assert!(!inbox.exists());
+
+        // PIN 正确时接收方接受，文件保存到接受时选择的目录
+        sender.send(intent.with_pin("1234")).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(std::fs::read(inbox.join("note.txt")).unwrap(), b"hello");
+
+        sender.stop().await.unwrap();
+        receiver.stop().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[tokio::test]
+    async fn test_resume_from_received_offset() {
+        let dir = std::env::temp_dir().join(format!("unidrop-p2p-resume-{}", uuid::Uuid::new_v4()));
+        let (receiver, receiver_rx, port) = start_peer(&dir.join("receiver"), None).await;
+        let (sender, mut sender_rx, _) = start_peer(&dir.join("sender"), None).await;
+        let peer_id = connect(&sender, &mut sender_rx, &receiver, port).await;
+        let inbox = dir.join("inbox");
+        accept_requests(receiver.clone(), receiver_rx, inbox.clone());
+
+        // 接收方已有两块多一点的临时文件，内容与原文件不同
+        let data: Vec<u8> = (0..3 * DEFAULT_CHUNK_SIZE + 100).map(|i| i as u8).collect();
+        let received = 2 * DEFAULT_CHUNK_SIZE;
+        std::fs::create_dir_all(&inbox).unwrap();
+        let mut part = PartFile::new(
+            &inbox,
+            "data.bin",
+            true,
+            "resumed",
+            "file_0",
+            data.len() as u64,
+            None,
+        );
+        let (mut file, _) = part.open(0).await.unwrap();
+        file.write_all(&vec![0u8; received + 10]).await.unwrap();
+        file.flush().await.unwrap();
+        drop(file);
+        part.keep().await;
+
+        // 续传只发送已接收的完整块之后的数据
+        let target = DeviceId::new(ProtocolId::new(P2P_PROTOCOL_ID), peer_id.to_string());
+        let intent = TransferIntent::from_sources(
+            target,
+            vec![TransferSource::bytes("data.bin", data.clone())],
+        );
+        let tx = sender.command_tx.read().clone().unwrap();
+        sender
+            .send_files(&tx, peer_id, "resumed".into(), intent, true)
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        let saved = std::fs::read(inbox.join("data.bin")).unwrap();
+        assert_eq!(saved.len(), data.len());
+        assert!(saved[..received].iter().all(|&b| b == 0));
+        assert_eq!(saved[received..], data[received..]);
+
+        sender.stop().await.unwrap();
+        receiver.stop().await.unwrap();
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}